@@ -5,6 +5,7 @@ CREATE TABLE users (
     password VARCHAR(255) NOT NULL,
     ip VARCHAR(255) NOT NULL,
     mac VARCHAR(255) NOT NULL,
+    role VARCHAR(20) NOT NULL DEFAULT 'student', -- student, instructor
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
 );
 
@@ -17,7 +18,7 @@ CREATE TABLE user_settings (
     firewall BOOLEAN DEFAULT true,
     vpn BOOLEAN DEFAULT false,
     usb_protection BOOLEAN DEFAULT true,
-    email_filter BOOLEAN DEFAULT true,
+    email_filter BOOLEAN DEFAULT false,
     spoofed_mac VARCHAR(255) DEFAULT '00:1B:44:11:3A:B7',
     ip VARCHAR(255) DEFAULT '192.168.0.101',
     dns VARCHAR(255) DEFAULT '8.8.8.8',
@@ -60,6 +61,10 @@ CREATE TABLE bank_transactions (
     user_id INT NOT NULL,
     description VARCHAR(255) NOT NULL,
     amount DECIMAL(10, 2) NOT NULL,
+    discount DECIMAL(10, 2) NOT NULL DEFAULT 0.00,
+    discount_source VARCHAR(255) NULL, -- e.g. 'Sale: Spring Sale; Coupon: CYBOX25'
+    is_fraudulent BOOLEAN NOT NULL DEFAULT false,
+    reversed_at TIMESTAMP NULL,
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
     FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
 );
@@ -71,7 +76,7 @@ CREATE TABLE universal_tasks (
     description TEXT NOT NULL,
     learning_module TEXT,
     points INT NOT NULL,
-    task_type VARCHAR(50) NOT NULL DEFAULT 'knowledge', 
+    task_type VARCHAR(50) NOT NULL DEFAULT 'knowledge', -- picks the verifier and the shape of task_data: knowledge, password_changed, email_classification, file_encrypted, cracked_passwords, hidden_message
     task_data TEXT,
     level INT NOT NULL DEFAULT 1
 );
@@ -82,12 +87,41 @@ CREATE TABLE user_tasks (
     universal_task_id INT NOT NULL,
     status VARCHAR(50) NOT NULL DEFAULT 'To Do', 
     completed_at TIMESTAMP NULL,
+    rewarded BOOLEAN NOT NULL DEFAULT false, -- set once the points are paid; survives a reset
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
     FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
     FOREIGN KEY (universal_task_id) REFERENCES universal_tasks(id) ON DELETE CASCADE,
     UNIQUE (user_id, universal_task_id) 
 );
 
+-- Tasks that must be completed before a task unlocks. Must stay acyclic; loading the graph checks it
+CREATE TABLE task_prerequisites (
+    task_id INT NOT NULL,
+    prerequisite_id INT NOT NULL,
+    PRIMARY KEY (task_id, prerequisite_id),
+    FOREIGN KEY (task_id) REFERENCES universal_tasks(id) ON DELETE CASCADE,
+    FOREIGN KEY (prerequisite_id) REFERENCES universal_tasks(id) ON DELETE CASCADE
+);
+
+-- Seed each user's CTF flags are derived from; {{flag:<name>}} placeholders in seeded content render from it
+CREATE TABLE user_flag_seeds (
+    user_id INT PRIMARY KEY,
+    seed BIGINT UNSIGNED NOT NULL,
+    generated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- Hashes a user has cracked for a cracked_passwords task, one row per digest
+CREATE TABLE cracked_hashes (
+    user_id INT NOT NULL,
+    universal_task_id INT NOT NULL,
+    digest CHAR(32) NOT NULL,
+    cracked_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, universal_task_id, digest),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (universal_task_id) REFERENCES universal_tasks(id) ON DELETE CASCADE
+);
+
 INSERT INTO universal_tasks (id, title, description, learning_module, points, task_type, task_data, level) VALUES
 (1, 'Level 1: Welcome to Cybox!',
  'Complete this introductory task to learn the basics of the Cybox interface.',
@@ -97,21 +131,29 @@ INSERT INTO universal_tasks (id, title, description, learning_module, points, ta
 (2, 'Level 2: Create a Strong Password',
  'Go to the settings and create a new, strong password for your account. The password must be at least 12 characters long and contain a mix of uppercase and lowercase letters, numbers, and symbols.',
  NULL,
- 100, 'action', NULL, 2),
+ 100, 'password_changed', NULL, 2),
 
 (3, 'Level 3: Spotting Phishing Emails',
  'Go to the Email app and correctly classify the phishing and spam emails.',
  'Phishing is a type of social engineering attack often used to steal user data, including login credentials and credit card numbers. It occurs when an attacker, masquerading as a trusted entity, dupes a victim into opening an email, instant message, or text message. The recipient is then tricked into clicking a malicious link, which can lead to the installation of malware, the freezing of their system as part of a ransomware attack or the revealing of sensitive information. Look for red flags like generic greetings, urgent language, suspicious links, and poor grammar.',
- 100, 'action', '{"required_classifications": [{"universal_email_id": 1, "classification": "phishing"}, {"universal_email_id": 3, "classification": "spam"}]}', 3),
+ 100, 'email_classification', '{"required_classifications": [{"universal_email_id": 1, "classification": "phishing"}, {"universal_email_id": 3, "classification": "spam"}]}', 3),
 
-(4, 'Level 4: Encrypt the Evidence', 'A sensitive file is located at "/home/user/sensitive_data.txt". Encrypt it with the password "TopSecret#9900" to protect it.', NULL, 200, 'action', '{"file_path": "/home/user/sensitive_data.txt", "password": "TopSecret#9900"}', 4),
+(4, 'Level 4: Encrypt the Evidence', 'A sensitive file is located at "/home/user/sensitive_data.txt". Encrypt it with the password "{{flag:file_password}}" to protect it.', NULL, 200, 'file_encrypted', '{"file_path": "/home/user/sensitive_data.txt", "password": "{{flag:file_password}}"}', 4),
 
-(5, 'Level 5: The Multi-Hash Challenge', 'Your account is protected by a multi-hash authentication. You need to crack 5 MD5 hashes to get the passwords. Check your email from admin@cybox.app for the hashes.', NULL, 150, 'action', '{"passwords": ["User321", "adminIam230", "mod78mod", "Klein679", "Roco89080"]}', 5),
+(5, 'Level 5: The Multi-Hash Challenge', 'Your account is protected by a multi-hash authentication. You need to crack 5 MD5 hashes to get the passwords. Check your email from admin@cybox.app for the hashes.', NULL, 150, 'cracked_passwords', '{"md5_digests": ["{{flag:md5_1}}", "{{flag:md5_2}}", "{{flag:md5_3}}", "{{flag:md5_4}}", "{{flag:md5_5}}"]}', 5),
 
 (6, 'Level 6: The Encoded Message',
  'An operative left a critical message, but had to encode it to avoid detection. The message is split into two parts. Check your emails for a message from `intel@cybox.app` with the subject `Fragment 1/2`. The second fragment was appended to routine network traffic, so check other emails carefully.',
  NULL,
- 350, 'action', '{"puzzle_type": "multi-stage-2"}', 6)
+ 350, 'hidden_message', '{"puzzle_type": "multi-stage-2", "answer_sha256": "{{flag:hidden_message_sha256}}"}', 6);
+
+-- Each level builds on the one before it
+INSERT INTO task_prerequisites (task_id, prerequisite_id) VALUES
+(2, 1),
+(3, 2),
+(4, 3),
+(5, 4),
+(6, 5);
 
 
 
@@ -119,77 +161,475 @@ CREATE TABLE services (
     id INT AUTO_INCREMENT PRIMARY KEY,
     name VARCHAR(255) NOT NULL,
     description TEXT,
-    price INT,
-    category VARCHAR(255),
-    features TEXT,
+    price INT NOT NULL DEFAULT 0,
+    category VARCHAR(255) NOT NULL, -- Security, Antivirus, Utility, Malware
+    features TEXT, -- JSON: [{"name": ..., "description": ...}]
+    permissions TEXT, -- JSON: [{"scope": "network|filesystem|mail|bank", "access": "read|write|full", "reason": ...}]
+    risk_rating VARCHAR(20) NOT NULL DEFAULT 'low', -- low, medium, high, critical
     icon_path VARCHAR(255),
     version VARCHAR(50),
     developer VARCHAR(255),
     release_date DATE,
-    is_important TINYINT(1) DEFAULT 0
+    is_important TINYINT(1) NOT NULL DEFAULT 0,
+    FULLTEXT INDEX services_search (name, description, developer)
 );
 
 CREATE TABLE user_services (
     id INT AUTO_INCREMENT PRIMARY KEY,
     user_id INT NOT NULL,
     service_id INT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'owned', -- owned, installed, enabled, disabled, uninstalled
+    purchased_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    installed_at TIMESTAMP NULL,
+    enabled_at TIMESTAMP NULL,
+    disabled_at TIMESTAMP NULL,
+    uninstalled_at TIMESTAMP NULL,
+    installed_version VARCHAR(50) NULL, -- behind services.version when an update is available
+    updated_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
     FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
     FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE,
     UNIQUE (user_id, service_id)
 );
 
-INSERT INTO services (name, description, icon_path, category, version, developer, release_date, is_important, price, features) VALUES
-('SecureNet VPN', 'Encrypt your internet connection and protect your privacy with military-grade security.', '/public/Icons/Bank.svg', 'Security', '2.5.0', 'SecureNet Solutions', '2024-01-20', 1, 0, '[]'),
-('Guardian AV', 'Real-time protection against viruses, malware, and ransomware. Keep your system safe.', '/public/Icons/Task.svg', 'Security', '4.1.2', 'Guardian Security', '2024-02-10', 1, 0, '[]'),
-('Fortress Firewall', 'Control network traffic and prevent unauthorized access to your system.', '/public/Icons/Setting.svg', 'Security', '1.8.0', 'Fortress Tech', '2024-03-01', 1, 0, '[]'),
-('FreeVPN Pro', 'Enjoy unlimited, free VPN access! (Warning: May collect user data and display intrusive ads.)', '/public/Icons/Browser.svg', 'Utility', '1.0.5', 'Anonymous Devs', '2023-11-15', 0, 0, '[]'),
-('System Defender', 'Optimizes your system by removing "threats" and boosting performance. (Warning: Known for false positives and system slowdowns.)', '/public/Icons/Console.svg', 'Utility', '3.0.0', 'RogueWare Inc.', '2023-10-01', 0, 0, '[]'),
-('Data Miner', 'A powerful tool to analyze your system data for "insights". (Warning: This is spyware designed to exfiltrate personal information.)', '/public/Icons/Mail.svg', 'Malware', '1.0.0', 'ShadowCorp', '2023-09-20', 0, 0, '[]'),
-('Privacy Shield', 'Blocks trackers and enhances your online anonymity. (Legitimate privacy tool)', '/public/Icons/CybStore.svg', 'Security', '1.1.0', 'PrivacyTools Inc.', '2024-04-05', 1, 0, '[]'),
-('SecureBrowse', 'A secure web browser with built-in ad and tracker blocking.', '/public/Icons/FileManager.svg', 'Security', '1.0.0', 'SecureWeb Devs', '2024-03-15', 1, 0, '[]'),
-('MD5 Cracker', 'A tool to crack MD5 hashes from a list of common passwords.', '/public/Icons/Console.svg', 'Utility', '1.0.0', 'Cybox Devs', '2025-01-01', 0, 100, '[]');
+INSERT INTO services (name, description, icon_path, category, version, developer, release_date, is_important, price, features, permissions, risk_rating) VALUES
+('SecureNet VPN', 'Encrypt your internet connection and protect your privacy with military-grade security.', '/public/Icons/Bank.svg', 'Security', '2.5.0', 'SecureNet Solutions', '2024-01-20', 1, 0,
+ '[{"name": "Encrypted tunnel", "description": "Routes all traffic through an AES-256 tunnel."}, {"name": "Kill switch", "description": "Blocks traffic if the tunnel drops."}]',
+ '[{"scope": "network", "access": "full", "reason": "Routes your traffic through the VPN tunnel."}]', 'low'),
+('Guardian AV', 'Real-time protection against viruses, malware, and ransomware. Keep your system safe.', '/public/Icons/Task.svg', 'Security', '4.1.2', 'Guardian Security', '2024-02-10', 1, 0,
+ '[{"name": "Real-time scanning", "description": "Scans files as they are opened."}, {"name": "Quarantine", "description": "Isolates infected files."}]',
+ '[{"scope": "filesystem", "access": "full", "reason": "Scans and quarantines infected files."}, {"scope": "mail", "access": "read", "reason": "Scans attachments for malware."}]', 'low'),
+('Fortress Firewall', 'Control network traffic and prevent unauthorized access to your system.', '/public/Icons/Setting.svg', 'Security', '1.8.0', 'Fortress Tech', '2024-03-01', 1, 0,
+ '[{"name": "Rule editor", "description": "Allow or block traffic by port and address."}]',
+ '[{"scope": "network", "access": "full", "reason": "Filters inbound and outbound connections."}]', 'low'),
+('FreeVPN Pro', 'Enjoy unlimited, free VPN access! (Warning: May collect user data and display intrusive ads.)', '/public/Icons/Browser.svg', 'Utility', '1.0.5', 'Anonymous Devs', '2023-11-15', 0, 0,
+ '[{"name": "Unlimited bandwidth", "description": "No data caps, ever."}, {"name": "Partner offers", "description": "Personalised ads from our partners."}]',
+ '[{"scope": "network", "access": "full", "reason": "Routes your traffic."}, {"scope": "filesystem", "access": "read", "reason": "Improves your experience."}, {"scope": "mail", "access": "read", "reason": "Finds partner offers for you."}]', 'high'),
+('System Defender', 'Optimizes your system by removing "threats" and boosting performance. (Warning: Known for false positives and system slowdowns.)', '/public/Icons/Console.svg', 'Utility', '3.0.0', 'RogueWare Inc.', '2023-10-01', 0, 0,
+ '[{"name": "One-click boost", "description": "Disables services slowing you down."}, {"name": "Threat scan", "description": "Finds hundreds of threats instantly."}]',
+ '[{"scope": "filesystem", "access": "full", "reason": "Removes threats."}, {"scope": "network", "access": "full", "reason": "Optimises your connection."}, {"scope": "bank", "access": "write", "reason": "Handles your premium subscription."}]', 'high'),
+('Data Miner', 'A powerful tool to analyze your system data for "insights". (Warning: This is spyware designed to exfiltrate personal information.)', '/public/Icons/Mail.svg', 'Malware', '1.0.0', 'ShadowCorp', '2023-09-20', 0, 0,
+ '[{"name": "Insights dashboard", "description": "See what your data says about you."}]',
+ '[{"scope": "filesystem", "access": "full", "reason": "Analyses your documents."}, {"scope": "mail", "access": "full", "reason": "Analyses your conversations."}, {"scope": "bank", "access": "full", "reason": "Analyses your spending."}, {"scope": "network", "access": "full", "reason": "Syncs insights to the cloud."}]', 'critical'),
+('Privacy Shield', 'Blocks trackers and enhances your online anonymity. (Legitimate privacy tool)', '/public/Icons/CybStore.svg', 'Security', '1.1.0', 'PrivacyTools Inc.', '2024-04-05', 1, 0,
+ '[{"name": "Tracker blocking", "description": "Blocks known tracking domains."}]',
+ '[{"scope": "network", "access": "read", "reason": "Inspects requests for known trackers."}]', 'low'),
+('SecureBrowse', 'A secure web browser with built-in ad and tracker blocking.', '/public/Icons/FileManager.svg', 'Security', '1.0.0', 'SecureWeb Devs', '2024-03-15', 1, 0,
+ '[{"name": "Ad blocking", "description": "Removes ads from web pages."}, {"name": "HTTPS only", "description": "Refuses insecure connections."}]',
+ '[{"scope": "network", "access": "full", "reason": "Loads web pages."}, {"scope": "filesystem", "access": "write", "reason": "Saves your downloads."}]', 'low'),
+('MD5 Cracker', 'A tool to crack MD5 hashes from a list of common passwords.', '/public/Icons/Console.svg', 'Utility', '1.0.0', 'Cybox Devs', '2025-01-01', 0, 100,
+ '[{"name": "Wordlist attack", "description": "Tries a list of common passwords against a hash."}]',
+ '[]', 'medium');
+
+-- Changelog per released version; bump services.version alongside a new row here
+CREATE TABLE service_versions (
+    service_id INT NOT NULL,
+    version VARCHAR(50) NOT NULL,
+    changelog TEXT NOT NULL,
+    released_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (service_id, version),
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE
+);
+
+INSERT INTO service_versions (service_id, version, changelog, released_at)
+SELECT id, version, 'Initial CybStore release.', release_date FROM services;
+
+-- Backend capabilities unlocked by an enabled service
+CREATE TABLE service_capabilities (
+    service_id INT NOT NULL,
+    capability VARCHAR(50) NOT NULL, -- hash_cracking, firewall, vpn
+    PRIMARY KEY (service_id, capability),
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE
+);
+
+INSERT INTO service_capabilities (service_id, capability)
+SELECT id, 'hash_cracking' FROM services WHERE name = 'MD5 Cracker'
+UNION ALL SELECT id, 'firewall' FROM services WHERE name = 'Fortress Firewall'
+UNION ALL SELECT id, 'vpn' FROM services WHERE name = 'SecureNet VPN';
+
+-- Simulated consequences of installing a service; `effect` is a tagged JSON object
+CREATE TABLE service_effects (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    service_id INT NOT NULL,
+    delay_seconds INT NOT NULL DEFAULT 0,
+    effect TEXT NOT NULL,
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE
+);
+
+CREATE TABLE scheduled_effects (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    service_id INT NOT NULL,
+    effect TEXT NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending', -- pending, fired, cancelled, failed
+    fire_at TIMESTAMP NOT NULL,
+    fired_at TIMESTAMP NULL,
+    undo_state TEXT NULL, -- what the effect changed, used to check the damage was undone
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE
+);
+
+CREATE TABLE security_alerts (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    service_id INT NULL,
+    severity VARCHAR(20) NOT NULL DEFAULT 'medium', -- low, medium, high, critical
+    title VARCHAR(255) NOT NULL,
+    message TEXT NOT NULL,
+    is_acknowledged BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE SET NULL
+);
+
+CREATE TABLE remediation_tasks (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    service_id INT NOT NULL,
+    title VARCHAR(255) NOT NULL,
+    description TEXT NOT NULL,
+    points INT NOT NULL DEFAULT 50,
+    status VARCHAR(20) NOT NULL DEFAULT 'open', -- open, resolved
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    resolved_at TIMESTAMP NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE,
+    UNIQUE (user_id, service_id) -- one task per service ever, so reinstalling cannot farm points
+);
+
+INSERT INTO service_effects (service_id, delay_seconds, effect)
+SELECT id, 30, '{"type": "exfiltration_alert", "severity": "high", "title": "Unusual outbound traffic", "message": "dataminer.exe uploaded 48 MB of documents and browser data to 185.220.101.4."}' FROM services WHERE name = 'Data Miner'
+UNION ALL SELECT id, 120, '{"type": "unauthorized_debit", "amount": 75, "description": "ShadowCorp Analytics subscription"}' FROM services WHERE name = 'Data Miner'
+UNION ALL SELECT id, 240, '{"type": "spoofed_email", "from_user": "security@cyberbank-alerts.fake", "subject": "Action required: confirm your card details", "body": "<p>Dear customer,</p><p>We detected suspicious activity on your card. Confirm your details within 24 hours or your account will be suspended: <a href=\"cybox://bank/login?phishing=true\" class=\"text-blue-400 underline\">https://cyberbank.fake/verify</a></p>"}' FROM services WHERE name = 'Data Miner'
+UNION ALL SELECT id, 60, '{"type": "degrade_setting", "setting": "firewall", "value": false, "message": "System Defender turned off your firewall to \"improve performance\"."}' FROM services WHERE name = 'System Defender'
+UNION ALL SELECT id, 90, '{"type": "degrade_setting", "setting": "keylogger_detection", "value": false, "message": "System Defender flagged keylogger detection as a threat and disabled it."}' FROM services WHERE name = 'System Defender'
+UNION ALL SELECT id, 180, '{"type": "unauthorized_debit", "amount": 49.99, "description": "System Defender Premium auto-renewal"}' FROM services WHERE name = 'System Defender'
+UNION ALL SELECT id, 60, '{"type": "degrade_setting", "setting": "dns", "value": "185.220.101.53", "message": "FreeVPN Pro changed your DNS server to one it controls."}' FROM services WHERE name = 'FreeVPN Pro'
+UNION ALL SELECT id, 150, '{"type": "exfiltration_alert", "severity": "medium", "title": "Browsing history shared", "message": "FreeVPN Pro sent your browsing history to adtrack.freevpn.fake."}' FROM services WHERE name = 'FreeVPN Pro';
+
+CREATE TABLE service_bundles (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    price INT NOT NULL
+);
+
+CREATE TABLE service_bundle_items (
+    bundle_id INT NOT NULL,
+    service_id INT NOT NULL,
+    PRIMARY KEY (bundle_id, service_id),
+    FOREIGN KEY (bundle_id) REFERENCES service_bundles(id) ON DELETE CASCADE,
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE
+);
+
+-- A sale applies to one service, to a whole category, or to everything when both are NULL
+CREATE TABLE sale_events (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    name VARCHAR(255) NOT NULL,
+    percent_off INT NOT NULL,
+    service_id INT NULL,
+    category VARCHAR(255) NULL,
+    starts_at TIMESTAMP NULL,
+    ends_at TIMESTAMP NULL,
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE
+);
+
+-- Either percent_off or amount_off is set
+CREATE TABLE coupons (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    code VARCHAR(64) NOT NULL UNIQUE,
+    percent_off INT NULL,
+    amount_off INT NULL,
+    service_id INT NULL,
+    max_uses INT NULL,
+    max_uses_per_user INT NOT NULL DEFAULT 1,
+    uses INT NOT NULL DEFAULT 0,
+    valid_from TIMESTAMP NULL,
+    valid_until TIMESTAMP NULL,
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE
+);
+
+CREATE TABLE coupon_redemptions (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    coupon_id INT NOT NULL,
+    user_id INT NOT NULL,
+    redeemed_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (coupon_id) REFERENCES coupons(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+-- One row per service bought; bundle purchases split the paid price across their services
+CREATE TABLE service_purchases (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    service_id INT NOT NULL,
+    bundle_id INT NULL,
+    transaction_id INT NOT NULL,
+    base_price DECIMAL(10, 2) NOT NULL,
+    discount DECIMAL(10, 2) NOT NULL DEFAULT 0.00,
+    price_paid DECIMAL(10, 2) NOT NULL,
+    purchased_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    refunded_at TIMESTAMP NULL,
+    refund_amount DECIMAL(10, 2) NULL,
+    refund_transaction_id INT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (service_id) REFERENCES services(id) ON DELETE CASCADE,
+    FOREIGN KEY (bundle_id) REFERENCES service_bundles(id) ON DELETE SET NULL,
+    FOREIGN KEY (transaction_id) REFERENCES bank_transactions(id) ON DELETE CASCADE,
+    FOREIGN KEY (refund_transaction_id) REFERENCES bank_transactions(id) ON DELETE SET NULL
+);
+
+-- Single-row store refund policy. Purchases get a full refund inside window_hours and
+-- partial_refund_percent until partial_window_hours; used services only if refund_if_used.
+CREATE TABLE refund_policy (
+    id INT PRIMARY KEY DEFAULT 1,
+    window_hours INT NOT NULL DEFAULT 24,
+    partial_window_hours INT NOT NULL DEFAULT 72,
+    partial_refund_percent INT NOT NULL DEFAULT 50,
+    refund_if_used BOOLEAN NOT NULL DEFAULT false,
+    CHECK (id = 1)
+);
+
+INSERT INTO refund_policy (id) VALUES (1);
+
+INSERT INTO service_bundles (id, name, description, price) VALUES
+(1, 'Analyst Toolkit', 'MD5 Cracker, SecureBrowse and Privacy Shield at a bundle price.', 80);
+
+INSERT INTO service_bundle_items (bundle_id, service_id)
+SELECT 1, id FROM services WHERE name IN ('MD5 Cracker', 'SecureBrowse', 'Privacy Shield');
+
+INSERT INTO coupons (code, percent_off, max_uses, max_uses_per_user) VALUES
+('CYBOX25', 25, 50, 1);
 
 CREATE TABLE universal_emails (
     id INT AUTO_INCREMENT PRIMARY KEY,
     from_user VARCHAR(255) NOT NULL,
     subject VARCHAR(255) NOT NULL,
     body TEXT NOT NULL,
-    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP
+    headers TEXT NULL, -- JSON transport headers: received hops, SPF/DKIM/DMARC results, reply-to
+    label VARCHAR(20) NULL DEFAULT 'legitimate', -- ground truth: legitimate, spam, phishing; NULL is ungraded
+    indicators TEXT NULL, -- JSON list of the red flags that justify the label
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FULLTEXT universal_emails_search (from_user, subject, body)
 );
 
+-- Rows either point at a universal email or carry their own per-user content
 CREATE TABLE user_emails (
     id INT AUTO_INCREMENT PRIMARY KEY,
     user_id INT NOT NULL,
-    universal_email_id INT NOT NULL,
+    universal_email_id INT NULL,
+    from_user VARCHAR(255) NULL,
+    subject VARCHAR(255) NULL,
+    body TEXT NULL,
+    headers TEXT NULL,
+    label VARCHAR(20) NULL, -- ground truth for per-user content, as on universal_emails
+    indicators TEXT NULL,
     is_read BOOLEAN NOT NULL DEFAULT false,
     classification VARCHAR(50) NOT NULL DEFAULT 'none', -- none, spam,   phishing
+    folder VARCHAR(20) NOT NULL DEFAULT 'inbox', -- inbox, sent, archive, quarantine, trash
+    trashed_from VARCHAR(20) NULL, -- folder a trashed email is restored to
+    filter_checked BOOLEAN NOT NULL DEFAULT false, -- the spam filter only judges each email once
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
     FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
     FOREIGN KEY (universal_email_id) REFERENCES universal_emails(id) ON DELETE CASCADE,
-    UNIQUE (user_id, universal_email_id)
+    UNIQUE (user_id, universal_email_id),
+    FULLTEXT user_emails_search (from_user, subject, body)
+);
+
+CREATE TABLE mail_labels (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    name VARCHAR(50) NOT NULL,
+    color VARCHAR(20) NOT NULL DEFAULT '#60a5fa',
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    UNIQUE (user_id, name)
 );
 
-INSERT INTO universal_emails (from_user, subject, body) VALUES
-('admin@cyberbank.fake', 'Unusual login detected', 'We noticed a login from a new device. Click the link below to secure your account: <a href="cybox://bank/login?phishing=true" class="text-blue-400 underline">https://cyberbank.fake/secure</a>'),
-('support@securevpn.fake', 'Your subscription has expired', 'Renew your VPN today to stay protected. <a href="cybox://settings/network" class="text-blue-400 underline">Renew Now</a><!-- Fragment 2/2: X3NfZV9jX3VfcmlfdHk= -->'),
-('friend@trustme.fake', 'Check out this awesome game!', 'It’s a new hacking simulator. Totally legit ;) Download here: <a href="cybox://filemanager/download/malware.exe" class="text-blue-400 underline">malware.exe</a>'),
-('admin@cybox.app', 'Level 4 Challenge Hashes', '<p>Hello,</p><p>Here are the hashes for the Level 4 challenge. One of these sets is the correct one. Good luck.</p><pre>261e672695a654895fed24313e5246de
+CREATE TABLE user_email_labels (
+    user_email_id INT NOT NULL,
+    label_id INT NOT NULL,
+    PRIMARY KEY (user_email_id, label_id),
+    FOREIGN KEY (user_email_id) REFERENCES user_emails(id) ON DELETE CASCADE,
+    FOREIGN KEY (label_id) REFERENCES mail_labels(id) ON DELETE CASCADE
+);
 
-c1572d06420ea7890535785dd4815fe6
+INSERT INTO universal_emails (id, from_user, subject, body, headers) VALUES
+(1, 'admin@cyberbank.fake', 'Unusual login detected', 'We noticed a login from a new device. Click the link below to secure your account: <a href="cybox://bank/login?phishing=true" class="text-blue-400 underline">https://cyberbank.fake/secure</a>', '{"reply_to":"support@cyberbank-secure.xyz","return_path":"bounce@cyberbank-secure.xyz","message_id":"<5f2c9a01e7@cyberbank-secure.xyz>","x_mailer":"PHPMailer 5.2.1","received":[{"from_host":"localhost","from_ip":"127.0.0.1","by_host":"cyberbank-secure.xyz","delay_seconds":94},{"from_host":"cyberbank-secure.xyz","from_ip":"45.153.160.12","by_host":"mx.cybox.app","delay_seconds":3}],"spf":"fail","dkim":"none","dmarc":"fail"}'),
+(2, 'support@securevpn.fake', 'Your subscription has expired', 'Renew your VPN today to stay protected. <a href="cybox://settings/network" class="text-blue-400 underline">Renew Now</a><!-- cybox:hidden Fragment 2/2: {{flag:fragment_2}} -->', '{"return_path":"support@securevpn.fake","message_id":"<renewal-20931@securevpn.fake>","received":[{"from_host":"mail.securevpn.fake","from_ip":"198.51.100.24","by_host":"mx.cybox.app","delay_seconds":2}],"spf":"pass","dkim":"pass","dmarc":"pass"}'),
+(3, 'friend@trustme.fake', 'Check out this awesome game!', 'It’s a new hacking simulator. Totally legit ;) Download here: <a href="cybox://filemanager/download/malware.exe" class="text-blue-400 underline">malware.exe</a>', '{"return_path":"friend@trustme.fake","message_id":"<a81d7c@trustme.fake>","x_mailer":"MassMail Pro 3.1","received":[{"from_host":"trustme.fake","from_ip":"203.0.113.77","by_host":"mx.cybox.app","delay_seconds":5}],"spf":"softfail","dkim":"none","dmarc":"none"}'),
+(4, 'admin@cybox.app', 'Level 4 Challenge Hashes', '<p>Hello,</p><p>Here are the hashes for the Level 4 challenge. One of these sets is the correct one. Good luck.</p><pre>{{flag:md5_1}}
 
-9594de8de31942437651eb4162554253
+{{flag:md5_2}}
 
-2572057493948a74218569b549041897
+{{flag:md5_3}}
 
-59a84b33a4aa9e6953051510396308b1
+{{flag:md5_4}}
+
+{{flag:md5_5}}
 
 
 d41d8cd98f00b204e9800998ecf8427e
 
 098f6bcd4621d373cade4e832627b4f6
 
-5f4dcc3b5aa765d61d8327deb882cf99</pre>'),
-('intel@cybox.app', 'Fragment 1/2', 'Here is the first part of the key, encoded in Base64: Y195X2JfZV9y')
+5f4dcc3b5aa765d61d8327deb882cf99</pre>', '{"return_path":"admin@cybox.app","message_id":"<level4-hashes@cybox.app>","received":[{"from_host":"mail.cybox.app","from_ip":"10.0.0.5","by_host":"mx.cybox.app","delay_seconds":1}],"spf":"pass","dkim":"pass","dmarc":"pass"}'),
+(5, 'intel@cybox.app', 'Fragment 1/2', 'Here is the first part of the key, encoded in Base64: {{flag:fragment_1}}', '{"return_path":"intel@cybox.app","message_id":"<fragment-1@cybox.app>","received":[{"from_host":"mail.cybox.app","from_ip":"10.0.0.5","by_host":"mx.cybox.app","delay_seconds":1}],"spf":"pass","dkim":"pass","dmarc":"pass"}');
+
+INSERT INTO universal_emails (id, from_user, subject, body, headers) VALUES
+(6, 'accounts@payflow-billing.fake', 'Overdue invoice #0423', '<p>Dear customer,</p><p>Your invoice is 14 days overdue. Please review the attached invoice and arrange payment today to avoid a late fee.</p>', '{"reply_to":"accounts@payflow-billing.fake","return_path":"bounce@payflow-billing.fake","message_id":"<inv0423@payflow-billing.fake>","x_mailer":"PHPMailer 5.2.1","received":[{"from_host":"payflow-billing.fake","from_ip":"91.215.85.9","by_host":"mx.cybox.app","delay_seconds":4}],"spf":"fail","dkim":"none","dmarc":"fail"}'),
+(7, 'hr@securenet-payroll.fake', 'Updated payroll schedule', '<p>Hi,</p><p>The payroll schedule has changed. Open the attached document and click <b>Enable Content</b> to view your new pay dates.</p>', '{"return_path":"hr@securenet-payroll.fake","message_id":"<payroll-77@securenet-payroll.fake>","received":[{"from_host":"securenet-payroll.fake","from_ip":"45.95.147.20","by_host":"mx.cybox.app","delay_seconds":6}],"spf":"softfail","dkim":"none","dmarc":"fail"}'),
+(8, 'scanner@parcelpoint.fake', 'Scanned delivery documents', '<p>Your delivery documents are attached as a protected archive.</p><p>Archive password: <b>2231</b></p>', '{"return_path":"scanner@parcelpoint.fake","message_id":"<scan0231@parcelpoint.fake>","received":[{"from_host":"mail.parcelpoint.fake","from_ip":"203.0.113.140","by_host":"mx.cybox.app","delay_seconds":3}],"spf":"pass","dkim":"none","dmarc":"none"}');
+
+UPDATE universal_emails
+SET
+    label = CASE id
+        WHEN 1 THEN 'phishing'
+        WHEN 3 THEN 'spam'
+        WHEN 6 THEN 'phishing'
+        WHEN 7 THEN 'phishing'
+        WHEN 8 THEN 'phishing'
+    END,
+    indicators = CASE id
+        WHEN 1 THEN '["spoofed_sender", "mismatched_link", "credential_request"]'
+        WHEN 3 THEN '["unknown_sender", "too_good_to_be_true", "suspicious_attachment"]'
+        WHEN 6 THEN '["lookalike_domain", "spoofed_sender", "generic_greeting", "urgent_language", "suspicious_attachment"]'
+        WHEN 7 THEN '["lookalike_domain", "generic_greeting", "suspicious_attachment"]'
+        WHEN 8 THEN '["generic_greeting", "suspicious_attachment"]'
+    END
+WHERE id IN (1, 3, 6, 7, 8);
+
+-- Universal emails without rules are delivered straight away; otherwise the first rule to fire delivers them
+CREATE TABLE email_delivery_rules (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    universal_email_id INT NOT NULL,
+    delivery_trigger TEXT NOT NULL, -- JSON: {"type": "account_created|task_started|task_completed|service_purchased|setting_changed", ...}
+    delay_seconds INT NOT NULL DEFAULT 0, -- game-clock delay after the trigger fires
+    FOREIGN KEY (universal_email_id) REFERENCES universal_emails(id) ON DELETE CASCADE
+);
+
+-- One row per universal email a user is due; it outlives the user_emails copy so deleted mail stays deleted
+CREATE TABLE email_deliveries (
+    user_id INT NOT NULL,
+    universal_email_id INT NOT NULL,
+    rule_id INT NULL, -- NULL when the email had no rules
+    deliver_at TIMESTAMP NOT NULL,
+    delivered_at TIMESTAMP NULL,
+    PRIMARY KEY (user_id, universal_email_id),
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (universal_email_id) REFERENCES universal_emails(id) ON DELETE CASCADE,
+    FOREIGN KEY (rule_id) REFERENCES email_delivery_rules(id) ON DELETE SET NULL
+);
+
+INSERT INTO email_delivery_rules (universal_email_id, delivery_trigger, delay_seconds) VALUES
+(4, '{"type": "task_started", "task_id": 5}', 0),
+(5, '{"type": "task_started", "task_id": 6}', 0),
+(6, '{"type": "task_completed", "task_id": 3}', 120),
+(7, '{"type": "task_completed", "task_id": 3}', 600),
+(8, '{"type": "task_completed", "task_id": 3}', 1200);
+
+-- Emails users reported to the simulated security desk, which answers once reply_at passes
+CREATE TABLE email_reports (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    user_email_id INT NULL, -- NULL once the reported email is deleted for good
+    subject VARCHAR(255) NOT NULL,
+    label VARCHAR(20) NOT NULL, -- ground truth at the time of the report
+    correct BOOLEAN NOT NULL, -- spam and phishing are worth reporting, legitimate mail is not
+    note TEXT NULL,
+    reply_body TEXT NOT NULL,
+    reported_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    reply_at TIMESTAMP NOT NULL,
+    reply_email_id INT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_email_id) REFERENCES user_emails(id) ON DELETE SET NULL,
+    FOREIGN KEY (reply_email_id) REFERENCES user_emails(id) ON DELETE SET NULL,
+    UNIQUE (user_email_id)
+);
+
+-- Simulated phishing campaigns an instructor sends to their class
+CREATE TABLE phishing_campaigns (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    instructor_id INT NOT NULL,
+    name VARCHAR(255) NOT NULL,
+    from_user VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    body TEXT NOT NULL, -- template: {{name}} and {{link}} are filled in per recipient
+    label VARCHAR(20) NOT NULL DEFAULT 'phishing',
+    indicators TEXT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'scheduled', -- scheduled, cancelled
+    send_at TIMESTAMP NOT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (instructor_id) REFERENCES users(id) ON DELETE CASCADE
+);
+
+CREATE TABLE campaign_recipients (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    campaign_id INT NOT NULL,
+    user_id INT NOT NULL,
+    token VARCHAR(32) NOT NULL UNIQUE, -- identifies the recipient in their tracked link
+    user_email_id INT NULL,
+    delivered_at TIMESTAMP NULL,
+    opened_at TIMESTAMP NULL,
+    clicked_at TIMESTAMP NULL,
+    reported_at TIMESTAMP NULL,
+    credentials_submitted_at TIMESTAMP NULL,
+    FOREIGN KEY (campaign_id) REFERENCES phishing_campaigns(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_email_id) REFERENCES user_emails(id) ON DELETE SET NULL,
+    UNIQUE (campaign_id, user_id)
+);
+
+-- Every cybox:// link a user followed, and what it led to
+CREATE TABLE link_clicks (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    user_email_id INT NULL, -- the email the link was in, if any
+    url VARCHAR(2048) NOT NULL,
+    outcome VARCHAR(20) NOT NULL, -- navigate, login, download
+    phishing BOOLEAN NOT NULL DEFAULT false,
+    credentials_submitted_at TIMESTAMP NULL, -- set when the user signed in on a phishing login page
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_email_id) REFERENCES user_emails(id) ON DELETE SET NULL
+);
+
+-- Attachments hang off either a universal email or a single user's email
+CREATE TABLE email_attachments (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    universal_email_id INT NULL,
+    user_email_id INT NULL,
+    file_name VARCHAR(255) NOT NULL,
+    mime_type VARCHAR(100) NOT NULL, -- as declared by the sender, which may lie
+    size_bytes INT NOT NULL,
+    content MEDIUMBLOB NOT NULL,
+    archive_password VARCHAR(100) NULL,
+    behaviour TEXT NULL, -- JSON list of actions the sandbox observes when the file runs
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (universal_email_id) REFERENCES universal_emails(id) ON DELETE CASCADE,
+    FOREIGN KEY (user_email_id) REFERENCES user_emails(id) ON DELETE CASCADE,
+    CHECK ((universal_email_id IS NULL) <> (user_email_id IS NULL))
+);
+
+INSERT INTO email_attachments (universal_email_id, file_name, mime_type, size_bytes, content, archive_password, behaviour) VALUES
+(2, 'SecureVPN_Receipt.pdf', 'application/pdf', 70, '%PDF-1.7\nSecureVPN receipt\nPlan: Annual\nAmount: $59.99\nStatus: Expired', NULL, NULL),
+(3, 'malware.exe', 'application/x-msdownload', 56, 'MZ This program cannot be run in DOS mode. HackSim Setup', NULL, '["Copies itself to C:\\\\Users\\\\user\\\\AppData\\\\Roaming\\\\svchost.exe","Adds a Run registry key so it starts at login","Connects to 185.220.101.47:443 and waits for commands","Reads saved browser passwords"]'),
+(6, 'Invoice_0423.pdf.exe', 'application/pdf', 56, 'MZ This program cannot be run in DOS mode. InvoiceViewer', NULL, '["Shows a blank PDF window as a decoy","Encrypts files in Documents and appends .locked","Drops README_RESTORE.txt demanding payment"]'),
+(7, 'Payroll_Schedule.docm', 'application/vnd.ms-word.document.macroEnabled.12', 53, 'PK word/vbaProject.bin AutoOpen Shell powershell -enc', NULL, '["Runs an AutoOpen macro when content is enabled","Starts a hidden PowerShell process","Downloads a second-stage payload from 91.215.85.9"]'),
+(8, 'Scan_0231.zip', 'application/zip', 31, 'PK [encrypted] Scan_0231.pdf.js', '2231', '["Extracts Scan_0231.pdf.js from the archive","Runs the script with Windows Script Host","Downloads and starts a banking trojan"]');
+
+-- Files users have saved into their virtual home directory
+CREATE TABLE user_files (
+    id INT AUTO_INCREMENT PRIMARY KEY,
+    user_id INT NOT NULL,
+    path VARCHAR(255) NOT NULL,
+    mime_type VARCHAR(100) NOT NULL,
+    size_bytes INT NOT NULL,
+    content MEDIUMBLOB NOT NULL,
+    source_attachment_id INT NULL,
+    created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users(id) ON DELETE CASCADE,
+    FOREIGN KEY (source_attachment_id) REFERENCES email_attachments(id) ON DELETE SET NULL,
+    UNIQUE (user_id, path)
+);
 
 
 
@@ -217,7 +657,7 @@ CREATE TABLE firewall_rules (
         WHEN 5 THEN 'Level 5: The Multi-Hash Challenge'
     END,
     description = CASE id
-         WHEN 4 THEN 'A sensitive file is located at ''/home/user/sensitive_data.txt''. Encrypt it with the password ''TopSecret#9900'' to protect 
+         WHEN 4 THEN 'A sensitive file is located at ''/home/user/sensitive_data.txt''. Encrypt it with the password ''{{flag:file_password}}'' to protect 
       it.'
          WHEN 5 THEN 'Your account is protected by a multi-hash authentication. You need to crack 5 MD5 hashes to get the passwords. Check your 
       email from admin@cybox.app for the hashes.'
@@ -227,8 +667,8 @@ CREATE TABLE firewall_rules (
          WHEN 5 THEN 150
     END,
      task_data = CASE id
-        WHEN 4 THEN '{\"file_path\": \"/home/user/sensitive_data.txt\", \"password\": \"TopSecret#9900\"}'
-         WHEN 5 THEN '{\"passwords\": [\"User321\", \"adminIam230\", \"mod78mod\", \"Klein679\", \"Roco89080\"]}'
+        WHEN 4 THEN '{\"file_path\": \"/home/user/sensitive_data.txt\", \"password\": \"{{flag:file_password}}\"}'
+         WHEN 5 THEN '{\"md5_digests\": [\"{{flag:md5_1}}\", \"{{flag:md5_2}}\", \"{{flag:md5_3}}\", \"{{flag:md5_4}}\", \"{{flag:md5_5}}\"]}'
      END,
      level = CASE id
          WHEN 4 THEN 4
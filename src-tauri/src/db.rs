@@ -17,3 +17,10 @@ static POOL: Lazy<Pool> = Lazy::new(|| {
 pub fn get_db_connection() -> Result<PooledConn, mysql::Error> {
     POOL.get_conn()
 }
+
+/// Takes a named column out of a row, for queries too wide for the tuple `FromRow` impls.
+pub fn column<T: mysql::prelude::FromValue>(row: &mut mysql::Row, name: &str) -> Result<T, String> {
+    row.take_opt(name)
+        .ok_or_else(|| format!("Missing column {}", name))?
+        .map_err(|e| format!("Column {}: {}", name, e))
+}
@@ -4,27 +4,45 @@ use crate::utils::{generators, crypto};
 use mysql::prelude::*;
 use tauri::command;
 use mysql::params;
+use std::env;
 
 
+/// Accounts listed in the comma-separated `INSTRUCTOR_NAMES` variable sign up as instructors.
+fn initial_role(name: &str) -> &'static str {
+    let instructors = env::var("INSTRUCTOR_NAMES").unwrap_or_default();
+    if instructors.split(',').any(|instructor| instructor.trim() == name) {
+        "instructor"
+    } else {
+        "student"
+    }
+}
+
 #[command]
 pub fn create_account(request: CreateUserRequest) -> Result<String, String> {
+    // The user's name is their mailbox, and system senders must not be impersonated
+    if super::mail::is_reserved_mailbox(&request.name) {
+        return Err(format!("The name {} is reserved", request.name));
+    }
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
     let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
 
+    // The connection has loaded .env by now
+    let role = initial_role(&request.name);
     let hashed_password = crypto::hash_password(&request.password);
     let ip_address = generators::generate_ip_address();
     let mac_address = generators::generate_mac_address();
 
     let transaction_result: Result<(), mysql::Error> = (|| {
         tx.exec_drop(
-            r"INSERT INTO users (device_name, name, password, ip, mac)
-              VALUES (:device_name, :name, :password, :ip, :mac)",
+            r"INSERT INTO users (device_name, name, password, ip, mac, role)
+              VALUES (:device_name, :name, :password, :ip, :mac, :role)",
             params! {
                 "device_name" => &request.device_name,
                 "name" => &request.name,
                 "password" => &hashed_password,
                 "ip" => &ip_address,
-                "mac" => &mac_address
+                "mac" => &mac_address,
+                "role" => role
             }
         )?;
 
@@ -103,6 +121,42 @@ pub fn create_account(request: CreateUserRequest) -> Result<String, String> {
     }
 }
 
+/// Fails unless `user_id` is an instructor. Guards commands that act on other users or on
+/// content every user receives.
+pub fn require_instructor<Q: Queryable>(conn: &mut Q, user_id: i32) -> Result<(), String> {
+    let role: Option<String> = conn
+        .exec_first("SELECT role FROM users WHERE id = ?", (user_id,))
+        .map_err(|e| e.to_string())?;
+    match role.as_deref() {
+        Some("instructor") => Ok(()),
+        Some(_) => Err("This needs an instructor account".to_string()),
+        None => Err("User not found".to_string()),
+    }
+}
+
+/// Makes another user an instructor or a student. Instructors cannot change their own role,
+/// so there is always someone left who can.
+#[command]
+pub fn set_user_role(instructor_id: i32, user_id: i32, role: String) -> Result<(), String> {
+    if !matches!(role.as_str(), "student" | "instructor") {
+        return Err(format!("Unknown role {}", role));
+    }
+    if instructor_id == user_id {
+        return Err("You cannot change your own role".to_string());
+    }
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    require_instructor(&mut conn, instructor_id)?;
+    conn.exec_drop("UPDATE users SET role = ? WHERE id = ?", (&role, user_id))
+        .map_err(|e| e.to_string())?;
+    if conn.affected_rows() == 0 {
+        let exists: Option<i32> = conn
+            .exec_first("SELECT id FROM users WHERE id = ?", (user_id,))
+            .map_err(|e| e.to_string())?;
+        exists.ok_or_else(|| "User not found".to_string())?;
+    }
+    Ok(())
+}
+
 #[command]
 pub fn get_user_details(name: String) -> Result<Option<UserDetails>, String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
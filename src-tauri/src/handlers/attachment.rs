@@ -0,0 +1,189 @@
+use crate::db;
+use crate::models::mail::{Attachment, AttachmentPreview, SandboxReport, SandboxVerdict};
+use crate::utils::attachments;
+use mysql::{params, prelude::*};
+use tauri::command;
+
+/// Where saved attachments land in the user's virtual home directory.
+const DOWNLOADS_DIR: &str = "/home/user/Downloads";
+
+// Universal attachments are shared by every copy of their email
+const ATTACHMENT_SELECT: &str = r#"
+    SELECT a.id, ue.id, a.file_name, a.mime_type, a.size_bytes, a.content, a.archive_password, a.behaviour
+    FROM email_attachments a
+    JOIN user_emails ue ON a.user_email_id = ue.id OR a.universal_email_id = ue.universal_email_id
+    WHERE ue.id = :email_id
+"#;
+
+type AttachmentRow = (i32, i32, String, String, i64, Vec<u8>, Option<String>, Option<String>);
+
+struct StoredAttachment {
+    attachment: Attachment,
+    content: Vec<u8>,
+    archive_password: Option<String>,
+    behaviour: Vec<String>,
+}
+
+fn to_stored(row: AttachmentRow) -> Result<StoredAttachment, String> {
+    let (id, email_id, file_name, mime_type, size_bytes, content, archive_password, behaviour) = row;
+    let behaviour = match behaviour {
+        Some(json) => serde_json::from_str(&json).map_err(|e| format!("Invalid behaviour on attachment {}: {}", id, e))?,
+        None => Vec::new(),
+    };
+    let flags = attachments::flags(&file_name, &mime_type, &content, archive_password.is_some());
+
+    Ok(StoredAttachment {
+        attachment: Attachment {
+            id,
+            email_id,
+            file_name,
+            mime_type,
+            size_bytes,
+            password_protected: archive_password.is_some(),
+            flags,
+        },
+        content,
+        archive_password,
+        behaviour,
+    })
+}
+
+fn load_attachments<Q: Queryable>(conn: &mut Q, email_id: i32) -> Result<Vec<StoredAttachment>, String> {
+    let rows: Vec<AttachmentRow> = conn
+        .exec(format!("{} ORDER BY a.id", ATTACHMENT_SELECT), params! { "email_id" => email_id })
+        .map_err(|e| e.to_string())?;
+    rows.into_iter().map(to_stored).collect()
+}
+
+fn load_attachment<Q: Queryable>(conn: &mut Q, email_id: i32, attachment_id: i32) -> Result<StoredAttachment, String> {
+    let row: Option<AttachmentRow> = conn
+        .exec_first(
+            format!("{} AND a.id = :attachment_id", ATTACHMENT_SELECT),
+            params! { "email_id" => email_id, "attachment_id" => attachment_id },
+        )
+        .map_err(|e| e.to_string())?;
+    to_stored(row.ok_or_else(|| "Attachment not found".to_string())?)
+}
+
+/// Picks a free name in the downloads folder, numbering copies like a browser does.
+fn unused_download_path<Q: Queryable>(conn: &mut Q, user_id: i32, file_name: &str) -> Result<String, String> {
+    let (stem, ext) = match file_name.split_once('.') {
+        Some((stem, ext)) => (stem, format!(".{}", ext)),
+        None => (file_name, String::new()),
+    };
+
+    let mut copy = 0;
+    loop {
+        let name = if copy == 0 { file_name.to_string() } else { format!("{} ({}){}", stem, copy, ext) };
+        let path = format!("{}/{}", DOWNLOADS_DIR, name);
+        let taken: Option<i32> = conn
+            .exec_first("SELECT id FROM user_files WHERE user_id = ? AND path = ?", (user_id, &path))
+            .map_err(|e| e.to_string())?;
+        if taken.is_none() {
+            return Ok(path);
+        }
+        copy += 1;
+    }
+}
+
+#[command]
+pub fn get_email_attachments(email_id: i32) -> Result<Vec<Attachment>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    Ok(load_attachments(&mut conn, email_id)?.into_iter().map(|stored| stored.attachment).collect())
+}
+
+/// Static inspection: file name, magic bytes and readable strings, without running anything.
+#[command]
+pub fn preview_attachment(email_id: i32, attachment_id: i32) -> Result<AttachmentPreview, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let stored = load_attachment(&mut conn, email_id, attachment_id)?;
+
+    Ok(AttachmentPreview {
+        extensions: attachments::extensions(&stored.attachment.file_name),
+        detected_type: attachments::detect_type(&stored.content, &stored.attachment.file_name).to_string(),
+        header_hex: attachments::header_hex(&stored.content),
+        text_preview: attachments::text_preview(&stored.content),
+        attachment: stored.attachment,
+    })
+}
+
+/// Detonates the attachment in the simulated sandbox and reports what it did.
+/// Encrypted archives need their password before the sandbox can look inside.
+#[command]
+pub fn open_attachment_in_sandbox(email_id: i32, attachment_id: i32, password: Option<String>) -> Result<SandboxReport, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let stored = load_attachment(&mut conn, email_id, attachment_id)?;
+    let mut notes: Vec<String> = stored.attachment.flags.iter().map(|flag| flag.description().to_string()).collect();
+
+    let behaviour = match (&stored.archive_password, password) {
+        (None, _) => stored.behaviour,
+        (Some(expected), Some(given)) if *expected == given => stored.behaviour,
+        (Some(_), Some(_)) => {
+            notes.push("The password is wrong, so the archive could not be extracted.".to_string());
+            Vec::new()
+        }
+        (Some(_), None) => {
+            notes.push("The archive is encrypted. Supply the password from the email to extract it.".to_string());
+            Vec::new()
+        }
+    };
+
+    let verdict = if !behaviour.is_empty() {
+        SandboxVerdict::Malicious
+    } else if !stored.attachment.flags.is_empty() {
+        SandboxVerdict::Suspicious
+    } else {
+        SandboxVerdict::Clean
+    };
+
+    Ok(SandboxReport {
+        attachment_id: stored.attachment.id,
+        file_name: stored.attachment.file_name,
+        verdict,
+        behaviour,
+        notes,
+    })
+}
+
+/// Copies an attachment into the user's downloads folder and returns its path.
+fn save_to_downloads<Q: Queryable>(conn: &mut Q, user_id: i32, stored: &StoredAttachment) -> Result<String, String> {
+    let path = unused_download_path(conn, user_id, &stored.attachment.file_name)?;
+    conn.exec_drop(
+        r"INSERT INTO user_files (user_id, path, mime_type, size_bytes, content, source_attachment_id)
+          VALUES (:user_id, :path, :mime_type, :size_bytes, :content, :source_attachment_id)",
+        params! {
+            "user_id" => user_id,
+            "path" => &path,
+            "mime_type" => &stored.attachment.mime_type,
+            "size_bytes" => stored.attachment.size_bytes,
+            "content" => &stored.content,
+            "source_attachment_id" => stored.attachment.id,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+/// Saves the attachment of `email_id` called `file_name`, for download links in the body.
+/// Returns the saved path and the attachment.
+pub fn download_by_name<Q: Queryable>(conn: &mut Q, user_id: i32, email_id: i32, file_name: &str) -> Result<(String, Attachment), String> {
+    let stored = load_attachments(conn, email_id)?
+        .into_iter()
+        .find(|stored| stored.attachment.file_name == file_name)
+        .ok_or_else(|| format!("{} is not attached to this email", file_name))?;
+    let path = save_to_downloads(conn, user_id, &stored)?;
+    Ok((path, stored.attachment))
+}
+
+/// Saves the attachment into the user's downloads folder and returns its path.
+#[command]
+pub fn save_attachment(email_id: i32, attachment_id: i32) -> Result<String, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let stored = load_attachment(&mut conn, email_id, attachment_id)?;
+    let user_id: i32 = conn
+        .exec_first("SELECT user_id FROM user_emails WHERE id = ?", (email_id,))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Email not found".to_string())?;
+
+    save_to_downloads(&mut conn, user_id, &stored)
+}
@@ -1,4 +1,4 @@
-use crate::{db, handlers::task::complete_task};
+use crate::{db, handlers::task::submit_task};
 use crate::models::user::User;
 use crate::utils::crypto;
 use mysql::{params, prelude::*};
@@ -114,8 +114,8 @@ pub fn reset_password(name: String) -> Result<String, String> {
         params! { "name" => &name }
     ).map_err(|e| e.to_string())?.ok_or_else(|| "User not found.".to_string())?;
 
-    // Mark Level 2 as complete
-    complete_task(2, user_id)?;
+    // Counts towards Level 2 once its prerequisites are done
+    submit_task(user_id, 2, None)?;
 
     Ok(format!(
         "Password reset successfully. New password: {}",
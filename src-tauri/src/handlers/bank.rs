@@ -4,20 +4,25 @@ use crate::utils::generators;
 use mysql::{params, prelude::*};
 use tauri::command;
 
+type TransactionRow = (u64, u64, String, String, String, Option<String>, bool, chrono::NaiveDateTime);
+
 #[command]
 pub fn get_transactions(user_id: u64) -> Result<Vec<Transaction>, String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-    let query = "SELECT id, user_id, description, amount, created_at FROM bank_transactions WHERE user_id = :user_id ORDER BY created_at DESC";
+    let query = "SELECT id, user_id, description, amount, discount, discount_source, reversed_at IS NOT NULL, created_at FROM bank_transactions WHERE user_id = :user_id ORDER BY created_at DESC";
     
     let transactions = conn.exec_map(
         query,
         params! { "user_id" => user_id },
-        |(id, user_id, description, amount, created_at): (u64, u64, String, String, chrono::NaiveDateTime)| {
+        |(id, user_id, description, amount, discount, discount_source, reversed, created_at): TransactionRow| {
             Transaction {
                 id,
                 user_id,
                 description,
                 amount: amount.parse::<f64>().unwrap_or(0.0),
+                discount: discount.parse::<f64>().unwrap_or(0.0),
+                discount_source,
+                reversed,
                 created_at: created_at.format("%Y-%m-%d %H:%M:%S").to_string(),
             }
         },
@@ -30,20 +35,23 @@ pub fn get_transactions(user_id: u64) -> Result<Vec<Transaction>, String> {
 pub fn award_points(user_id: u64, amount: i32, description: String) -> Result<(), String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
     let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+    credit_points(&mut tx, user_id, amount, &description)?;
+    tx.commit().map_err(|e| e.to_string())?;
 
-    tx.exec_drop(
+    Ok(())
+}
+
+/// Credits points as part of the caller's transaction.
+pub fn credit_points<Q: Queryable>(conn: &mut Q, user_id: u64, amount: i32, description: &str) -> Result<(), String> {
+    conn.exec_drop(
         "UPDATE bank_accounts SET balance = balance + :amount WHERE user_id = :user_id",
         params! { "amount" => amount, "user_id" => user_id },
     ).map_err(|e| e.to_string())?;
 
-    tx.exec_drop(
+    conn.exec_drop(
         "INSERT INTO bank_transactions (user_id, description, amount) VALUES (:user_id, :description, :amount)",
         params! { "user_id" => user_id, "description" => description, "amount" => amount },
-    ).map_err(|e| e.to_string())?;
-
-    tx.commit().map_err(|e| e.to_string())?;
-
-    Ok(())
+    ).map_err(|e| e.to_string())
 }
 
 #[command]
@@ -132,4 +140,52 @@ pub fn generate_new_card(user_id: u64) -> Result<String, String> {
         user_id,
         generate_new_card: true,
     })
+}
+
+/// Reverses a fraudulent charge. Charges the user made themselves cannot be disputed.
+#[command]
+pub fn dispute_transaction(user_id: u64, transaction_id: u64) -> Result<String, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+
+    let transaction: Option<(String, rust_decimal::Decimal, bool, bool)> = tx.exec_first(
+        r"SELECT description, amount, is_fraudulent, reversed_at IS NOT NULL
+          FROM bank_transactions WHERE id = :transaction_id AND user_id = :user_id FOR UPDATE",
+        params! { "transaction_id" => transaction_id, "user_id" => user_id },
+    ).map_err(|e| e.to_string())?;
+
+    let (description, amount, is_fraudulent, is_reversed) = match transaction {
+        Some(transaction) => transaction,
+        None => {
+            tx.rollback().map_err(|e| e.to_string())?;
+            return Err("Transaction not found".to_string());
+        }
+    };
+    if is_reversed {
+        tx.rollback().map_err(|e| e.to_string())?;
+        return Err("This transaction has already been reversed".to_string());
+    }
+    if !is_fraudulent {
+        tx.rollback().map_err(|e| e.to_string())?;
+        return Err("Dispute rejected: this transaction was authorised by you".to_string());
+    }
+
+    tx.exec_drop(
+        "UPDATE bank_accounts SET balance = balance - :amount WHERE user_id = :user_id",
+        params! { "amount" => amount, "user_id" => user_id },
+    ).map_err(|e| e.to_string())?;
+
+    tx.exec_drop(
+        "INSERT INTO bank_transactions (user_id, description, amount) VALUES (:user_id, :description, :amount)",
+        params! { "user_id" => user_id, "description" => format!("Reversal: {}", description), "amount" => -amount },
+    ).map_err(|e| e.to_string())?;
+
+    tx.exec_drop(
+        "UPDATE bank_transactions SET reversed_at = NOW() WHERE id = :transaction_id",
+        params! { "transaction_id" => transaction_id },
+    ).map_err(|e| e.to_string())?;
+
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok("Dispute accepted. The charge has been reversed.".to_string())
 }
\ No newline at end of file
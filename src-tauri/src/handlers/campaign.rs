@@ -0,0 +1,227 @@
+use crate::db;
+use crate::handlers::account::require_instructor;
+use crate::models::campaign::{Campaign, CampaignEvent, CampaignRecipient, CampaignReport, NewCampaign};
+use crate::models::mail::EmailHeaders;
+use crate::utils::{campaign as campaign_utils, html};
+use chrono::NaiveDateTime;
+use mysql::{params, prelude::*, PooledConn};
+use tauri::command;
+
+const CAMPAIGN_SELECT: &str = r"
+    SELECT id, instructor_id, name, from_user, subject, status, send_at, created_at
+    FROM phishing_campaigns
+";
+
+type CampaignRow = (i32, i32, String, String, String, String, NaiveDateTime, NaiveDateTime);
+
+// Recipient id and token, then the campaign's id, sender, subject, body, label and indicators, then the recipient's name
+type DueRow = (i32, String, i32, String, String, String, String, Option<String>, String);
+
+fn to_campaign(row: CampaignRow) -> Campaign {
+    let (id, instructor_id, name, from_user, subject, status, send_at, created_at) = row;
+    Campaign { id, instructor_id, name, from_user, subject, status, send_at, created_at }
+}
+
+fn load_campaign<Q: Queryable>(conn: &mut Q, instructor_id: i32, campaign_id: i32) -> Result<Campaign, String> {
+    require_instructor(conn, instructor_id)?;
+    let row: Option<CampaignRow> = conn
+        .exec_first(format!("{} WHERE id = ? AND instructor_id = ?", CAMPAIGN_SELECT), (campaign_id, instructor_id))
+        .map_err(|e| e.to_string())?;
+    row.map(to_campaign).ok_or_else(|| "Campaign not found".to_string())
+}
+
+/// Stamps the first time a recipient did something with their campaign email.
+pub fn record_event<Q: Queryable>(conn: &mut Q, user_email_id: i32, event: CampaignEvent) -> Result<(), String> {
+    conn.exec_drop(
+        format!(
+            "UPDATE campaign_recipients SET {0} = COALESCE({0}, NOW()) WHERE user_email_id = ?",
+            event.column()
+        ),
+        (user_email_id,),
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Stamps events for the recipient behind a tracked link.
+pub fn record_link_event<Q: Queryable>(conn: &mut Q, token: &str, events: &[CampaignEvent]) -> Result<(), String> {
+    let found: Option<i32> = conn
+        .exec_first("SELECT id FROM campaign_recipients WHERE token = ? AND delivered_at IS NOT NULL", (token,))
+        .map_err(|e| e.to_string())?;
+    let id = found.ok_or_else(|| "Unknown campaign link".to_string())?;
+    for event in events {
+        conn.exec_drop(
+            format!("UPDATE campaign_recipients SET {0} = COALESCE({0}, NOW()) WHERE id = ?", event.column()),
+            (id,),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// Delivers campaign emails that are due to this user. Returns how many arrived.
+pub fn deliver_campaign_emails(conn: &mut PooledConn, user_id: i32) -> Result<usize, String> {
+    let due: Vec<DueRow> = conn
+        .exec(
+            r"SELECT r.id, r.token, c.id, c.from_user, c.subject, c.body, c.label, c.indicators, u.name
+              FROM campaign_recipients r
+              JOIN phishing_campaigns c ON c.id = r.campaign_id
+              JOIN users u ON u.id = r.user_id
+              WHERE r.user_id = :user_id AND r.delivered_at IS NULL
+                AND c.status = 'scheduled' AND c.send_at <= NOW()
+              ORDER BY c.send_at, r.id",
+            params! { "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut delivered = 0;
+    for (recipient_id, token, campaign_id, from_user, subject, body, label, indicators, name) in due {
+        let headers = EmailHeaders::authenticated(&from_user, "10.0.0.25", &format!("campaign-{}-{}", campaign_id, recipient_id));
+        let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+
+        // Claim the row first so a concurrent poll cannot deliver it twice
+        tx.exec_drop(
+            "UPDATE campaign_recipients SET delivered_at = NOW() WHERE id = :id AND delivered_at IS NULL",
+            params! { "id" => recipient_id },
+        )
+        .map_err(|e| e.to_string())?;
+        if tx.affected_rows() == 0 {
+            tx.rollback().map_err(|e| e.to_string())?;
+            continue;
+        }
+
+        // Like a real simulation, campaign mail is allow-listed past the spam filter
+        tx.exec_drop(
+            r"INSERT INTO user_emails (user_id, from_user, subject, body, headers, label, indicators, folder, filter_checked, is_read, classification)
+              VALUES (:user_id, :from_user, :subject, :body, :headers, :label, :indicators, 'inbox', true, false, 'none')",
+            params! {
+                "user_id" => user_id,
+                "from_user" => &from_user,
+                "subject" => &subject,
+                "body" => html::sanitize_html(&campaign_utils::render_body(&body, &name, &token)),
+                "headers" => serde_json::to_string(&headers).map_err(|e| e.to_string())?,
+                "label" => &label,
+                "indicators" => &indicators,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+        let email_id = tx.last_insert_id().ok_or_else(|| "Failed to deliver campaign email".to_string())?;
+        tx.exec_drop(
+            "UPDATE campaign_recipients SET user_email_id = :email_id WHERE id = :id",
+            params! { "email_id" => email_id, "id" => recipient_id },
+        )
+        .map_err(|e| e.to_string())?;
+        tx.commit().map_err(|e| e.to_string())?;
+        delivered += 1;
+    }
+    Ok(delivered)
+}
+
+/// Schedules a campaign against the given users. Each recipient gets their own tracked link.
+#[command]
+pub fn create_campaign(campaign: NewCampaign) -> Result<Campaign, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    require_instructor(&mut conn, campaign.instructor_id)?;
+    if campaign.name.trim().is_empty() || campaign.subject.trim().is_empty() {
+        return Err("A campaign needs a name and a subject".to_string());
+    }
+    if campaign.targets.is_empty() {
+        return Err("Add at least one target".to_string());
+    }
+
+    let mut targets: Vec<i32> = Vec::new();
+    for address in &campaign.targets {
+        let (user_id, _) = super::mail::resolve_address(&mut conn, address)?;
+        if !targets.contains(&user_id) {
+            targets.push(user_id);
+        }
+    }
+    let indicators = serde_json::to_string(&campaign.indicators).map_err(|e| e.to_string())?;
+
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+    tx.exec_drop(
+        r"INSERT INTO phishing_campaigns (instructor_id, name, from_user, subject, body, label, indicators, send_at)
+          VALUES (:instructor_id, :name, :from_user, :subject, :body, :label, :indicators, COALESCE(:send_at, NOW()))",
+        params! {
+            "instructor_id" => campaign.instructor_id,
+            "name" => campaign.name.trim(),
+            "from_user" => campaign.from_user.trim(),
+            "subject" => campaign.subject.trim(),
+            "body" => &campaign.body,
+            "label" => campaign.label.as_str(),
+            "indicators" => indicators,
+            "send_at" => campaign.send_at,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    let campaign_id = tx.last_insert_id().ok_or_else(|| "Failed to create campaign".to_string())? as i32;
+    for user_id in targets {
+        tx.exec_drop(
+            "INSERT INTO campaign_recipients (campaign_id, user_id, token) VALUES (:campaign_id, :user_id, :token)",
+            params! {
+                "campaign_id" => campaign_id,
+                "user_id" => user_id,
+                "token" => format!("{:032x}", rand::random::<u128>()),
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    load_campaign(&mut conn, campaign.instructor_id, campaign_id)
+}
+
+#[command]
+pub fn get_campaigns(instructor_id: i32) -> Result<Vec<Campaign>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    require_instructor(&mut conn, instructor_id)?;
+    let rows: Vec<CampaignRow> = conn
+        .exec(format!("{} WHERE instructor_id = ? ORDER BY send_at DESC, id DESC", CAMPAIGN_SELECT), (instructor_id,))
+        .map_err(|e| e.to_string())?;
+    Ok(rows.into_iter().map(to_campaign).collect())
+}
+
+/// Stops any emails that have not gone out yet. Ones already delivered stay.
+#[command]
+pub fn cancel_campaign(instructor_id: i32, campaign_id: i32) -> Result<(), String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    load_campaign(&mut conn, instructor_id, campaign_id)?;
+    conn.exec_drop("UPDATE phishing_campaigns SET status = 'cancelled' WHERE id = ?", (campaign_id,))
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn get_campaign_report(instructor_id: i32, campaign_id: i32) -> Result<CampaignReport, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let campaign = load_campaign(&mut conn, instructor_id, campaign_id)?;
+    let recipients: Vec<CampaignRecipient> = conn
+        .exec_map(
+            r"SELECT r.user_id, u.name, r.delivered_at, r.opened_at, r.clicked_at, r.reported_at, r.credentials_submitted_at
+              FROM campaign_recipients r
+              JOIN users u ON u.id = r.user_id
+              WHERE r.campaign_id = ?
+              ORDER BY u.name",
+            (campaign_id,),
+            |(user_id, name, delivered_at, opened_at, clicked_at, reported_at, credentials_submitted_at)| CampaignRecipient {
+                user_id,
+                name,
+                delivered_at,
+                opened_at,
+                clicked_at,
+                reported_at,
+                credentials_submitted_at,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+
+    Ok(CampaignReport {
+        campaign,
+        totals: campaign_utils::totals(&recipients),
+        recipients,
+    })
+}
+
+/// The campaign report as CSV, one row per recipient plus totals.
+#[command]
+pub fn export_campaign_report(instructor_id: i32, campaign_id: i32) -> Result<String, String> {
+    Ok(campaign_utils::report_csv(&get_campaign_report(instructor_id, campaign_id)?))
+}
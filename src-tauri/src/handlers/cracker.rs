@@ -0,0 +1,32 @@
+use crate::db;
+use crate::handlers::entitlement::require_capability;
+use crate::models::service::Capability;
+use crate::utils::flags::CRACKABLE_PASSWORDS;
+use tauri::command;
+
+// Wordlist shipped with the MD5 Cracker service, on top of the passwords behind the Level 5 hashes
+const COMMON_PASSWORDS: &[&str] = &[
+    "123456",
+    "password",
+    "test",
+    "qwerty",
+    "letmein",
+    "admin",
+];
+
+#[command]
+pub fn crack_md5_hash(user_id: u64, hash: String) -> Result<Option<String>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    require_capability(&mut conn, user_id, Capability::HashCracking)?;
+
+    let target = hash.trim().to_lowercase();
+    if target.len() != 32 || !target.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("Not a valid MD5 hash".to_string());
+    }
+
+    Ok(COMMON_PASSWORDS
+        .iter()
+        .chain(CRACKABLE_PASSWORDS)
+        .find(|password| format!("{:x}", md5::compute(password.as_bytes())) == target)
+        .map(|password| password.to_string()))
+}
@@ -0,0 +1,431 @@
+use crate::db;
+use crate::models::effects::{Effect, RemediationResult, RemediationTask, SecurityAlert};
+use crate::models::mail::{EmailHeaders, PhishingIndicator};
+use mysql::{params, prelude::*, PooledConn};
+use rust_decimal::Decimal;
+use serde_json::{json, Value};
+use tauri::command;
+
+// user_settings columns a malicious service is allowed to tamper with
+const DEGRADABLE_SETTINGS: &[&str] = &[
+    "firewall",
+    "vpn",
+    "usb_protection",
+    "email_filter",
+    "dns",
+    "proxy_server",
+    "kernel_protection",
+    "network_monitoring",
+    "keylogger_detection",
+    "traffic_analysis",
+];
+
+fn degradable_setting(setting: &str) -> Result<&'static str, String> {
+    DEGRADABLE_SETTINGS
+        .iter()
+        .find(|s| **s == setting)
+        .copied()
+        .ok_or_else(|| format!("Setting {} cannot be changed by an effect", setting))
+}
+
+/// Settings are compared in their `CAST(... AS CHAR)` form, so booleans become "1"/"0".
+pub fn setting_value(value: &Value) -> String {
+    match value {
+        Value::Bool(b) => if *b { "1".to_string() } else { "0".to_string() },
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Queues every effect of `service_id` for the user and opens a remediation task, unless the
+/// user already had one for this service. Called when the service is installed.
+pub fn schedule_service_effects<Q: Queryable>(conn: &mut Q, user_id: u64, service_id: i32) -> Result<(), String> {
+    let scheduled = conn
+        .exec_iter(
+            r"INSERT INTO scheduled_effects (user_id, service_id, effect, fire_at)
+              SELECT :user_id, service_id, effect, NOW() + INTERVAL delay_seconds SECOND
+              FROM service_effects WHERE service_id = :service_id",
+            params! { "user_id" => user_id, "service_id" => service_id },
+        )
+        .map_err(|e| e.to_string())?
+        .affected_rows();
+    if scheduled == 0 {
+        return Ok(());
+    }
+
+    let service_name: String = conn
+        .exec_first("SELECT name FROM services WHERE id = :service_id", params! { "service_id" => service_id })
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Service not found".to_string())?;
+
+    conn.exec_drop(
+        r"INSERT IGNORE INTO remediation_tasks (user_id, service_id, title, description)
+          VALUES (:user_id, :service_id, :title, :description)",
+        params! {
+            "user_id" => user_id,
+            "service_id" => service_id,
+            "title" => format!("Clean up after {}", service_name),
+            "description" => format!(
+                "{} is behaving suspiciously. Uninstall it, then find and undo everything it did: check your security alerts, bank transactions, inbox and settings.",
+                service_name
+            ),
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Effects that have not fired yet are dropped when the service is uninstalled.
+pub fn cancel_pending_effects<Q: Queryable>(conn: &mut Q, user_id: u64, service_id: i32) -> Result<(), String> {
+    conn.exec_drop(
+        "UPDATE scheduled_effects SET status = 'cancelled' WHERE user_id = :user_id AND service_id = :service_id AND status = 'pending'",
+        params! { "user_id" => user_id, "service_id" => service_id },
+    )
+    .map_err(|e| e.to_string())
+}
+
+pub fn insert_alert<Q: Queryable>(
+    conn: &mut Q,
+    user_id: u64,
+    service_id: Option<u64>,
+    severity: &str,
+    title: &str,
+    message: &str,
+) -> Result<u64, String> {
+    conn.exec_iter(
+        r"INSERT INTO security_alerts (user_id, service_id, severity, title, message)
+          VALUES (:user_id, :service_id, :severity, :title, :message)",
+        params! {
+            "user_id" => user_id,
+            "service_id" => service_id,
+            "severity" => severity,
+            "title" => title,
+            "message" => message,
+        },
+    )
+    .map_err(|e| e.to_string())?
+    .last_insert_id()
+    .ok_or_else(|| "Failed to record alert".to_string())
+}
+
+/// Applies one effect and returns what it changed, so remediation can check it was undone.
+fn apply_effect(tx: &mut mysql::Transaction, user_id: u64, service_id: u64, effect: &Effect) -> Result<Value, String> {
+    match effect {
+        Effect::ExfiltrationAlert { severity, title, message } => {
+            let alert_id = insert_alert(tx, user_id, Some(service_id), severity, title, message)?;
+            Ok(json!({ "alert_id": alert_id }))
+        }
+        Effect::UnauthorizedDebit { amount, description } => {
+            let amount = Decimal::try_from(*amount).map_err(|e| e.to_string())?.round_dp(2);
+            tx.exec_drop(
+                "UPDATE bank_accounts SET balance = balance - :amount WHERE user_id = :user_id",
+                params! { "amount" => amount, "user_id" => user_id },
+            )
+            .map_err(|e| e.to_string())?;
+            tx.exec_drop(
+                r"INSERT INTO bank_transactions (user_id, description, amount, is_fraudulent)
+                  VALUES (:user_id, :description, :amount, true)",
+                params! { "user_id" => user_id, "description" => description, "amount" => -amount },
+            )
+            .map_err(|e| e.to_string())?;
+            let transaction_id = tx.last_insert_id().ok_or_else(|| "Failed to record transaction".to_string())?;
+            Ok(json!({ "transaction_id": transaction_id }))
+        }
+        Effect::SpoofedEmail { from_user, subject, body } => {
+            // Sent through the compromised service's own relay, so it fails authentication
+            let headers = EmailHeaders::spoofed(
+                &format!("relay{}.svc-mailer.xyz", service_id),
+                "185.220.101.47",
+                &format!("{}.{}", user_id, chrono::Utc::now().timestamp()),
+            );
+            tx.exec_drop(
+                r"INSERT INTO user_emails (user_id, from_user, subject, body, headers, label, indicators, is_read, classification)
+                  VALUES (:user_id, :from_user, :subject, :body, :headers, 'phishing', :indicators, false, 'none')",
+                params! {
+                    "user_id" => user_id,
+                    "from_user" => from_user,
+                    "subject" => subject,
+                    "body" => body,
+                    "headers" => serde_json::to_string(&headers).map_err(|e| e.to_string())?,
+                    "indicators" => serde_json::to_string(&[PhishingIndicator::SpoofedSender]).map_err(|e| e.to_string())?,
+                },
+            )
+            .map_err(|e| e.to_string())?;
+            let email_id = tx.last_insert_id().ok_or_else(|| "Failed to deliver email".to_string())?;
+            Ok(json!({ "email_id": email_id }))
+        }
+        Effect::DegradeSetting { setting, value, message } => {
+            let column = degradable_setting(setting)?;
+            let original: Option<Option<String>> = tx
+                .exec_first(
+                    format!("SELECT CAST({} AS CHAR) FROM user_settings WHERE user_id = :user_id", column),
+                    params! { "user_id" => user_id },
+                )
+                .map_err(|e| e.to_string())?;
+            let original = original.ok_or_else(|| "Settings not found".to_string())?;
+
+            tx.exec_drop(
+                format!("UPDATE user_settings SET {} = :value WHERE user_id = :user_id", column),
+                params! { "value" => setting_value(value), "user_id" => user_id },
+            )
+            .map_err(|e| e.to_string())?;
+            insert_alert(tx, user_id, Some(service_id), "medium", &format!("Setting changed: {}", column), message)?;
+
+            Ok(json!({ "setting": column, "original": original }))
+        }
+    }
+}
+
+/// Fires every effect whose time has come. Each effect commits on its own, and one that fails
+/// is logged and marked failed so it neither holds back the rest nor retries on every poll.
+pub fn run_due_effects(conn: &mut PooledConn, user_id: u64) -> Result<usize, String> {
+    let due: Vec<(u64, u64, String)> = conn
+        .exec(
+            r"SELECT id, service_id, effect FROM scheduled_effects
+              WHERE user_id = :user_id AND status = 'pending' AND fire_at <= NOW()
+              ORDER BY fire_at",
+            params! { "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut fired = 0;
+    for (id, service_id, effect) in due {
+        match fire_effect(conn, id, user_id, service_id, &effect) {
+            Ok(true) => fired += 1,
+            Ok(false) => {}
+            Err(e) => {
+                log::warn!("Scheduled effect {} for user {} failed: {}", id, user_id, e);
+                conn.exec_drop(
+                    "UPDATE scheduled_effects SET status = 'failed' WHERE id = :id AND status = 'pending'",
+                    params! { "id" => id },
+                )
+                .map_err(|e| e.to_string())?;
+            }
+        }
+    }
+
+    Ok(fired)
+}
+
+/// Fires one effect in its own transaction. Returns false if a concurrent poll got there first.
+fn fire_effect(conn: &mut PooledConn, id: u64, user_id: u64, service_id: u64, effect: &str) -> Result<bool, String> {
+    let effect: Effect = serde_json::from_str(effect).map_err(|e| format!("Invalid effect {}: {}", id, e))?;
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+
+    // Claim the row first so a concurrent poll cannot fire it twice
+    tx.exec_drop(
+        "UPDATE scheduled_effects SET status = 'fired', fired_at = NOW() WHERE id = :id AND status = 'pending'",
+        params! { "id" => id },
+    )
+    .map_err(|e| e.to_string())?;
+    if tx.affected_rows() == 0 {
+        tx.rollback().map_err(|e| e.to_string())?;
+        return Ok(false);
+    }
+
+    match apply_effect(&mut tx, user_id, service_id, &effect) {
+        Ok(undo_state) => {
+            tx.exec_drop(
+                "UPDATE scheduled_effects SET undo_state = :undo_state WHERE id = :id",
+                params! { "undo_state" => undo_state.to_string(), "id" => id },
+            )
+            .map_err(|e| e.to_string())?;
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(true)
+        }
+        Err(e) => {
+            tx.rollback().map_err(|e| e.to_string())?;
+            Err(e)
+        }
+    }
+}
+
+/// Lists what is still left to undo for a remediation task; empty means it is done.
+fn outstanding_damage(conn: &mut PooledConn, user_id: u64, service_id: u64) -> Result<Vec<String>, String> {
+    let mut outstanding = Vec::new();
+
+    let (service_name, status): (String, Option<String>) = conn
+        .exec_first(
+            r"SELECT s.name, us.status FROM services s
+              LEFT JOIN user_services us ON us.service_id = s.id AND us.user_id = :user_id
+              WHERE s.id = :service_id",
+            params! { "user_id" => user_id, "service_id" => service_id },
+        )
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Service not found".to_string())?;
+    if !matches!(status.as_deref(), None | Some("uninstalled")) {
+        outstanding.push(format!("Uninstall {}", service_name));
+    }
+
+    let fired: Vec<(String, Option<String>)> = conn
+        .exec(
+            "SELECT effect, undo_state FROM scheduled_effects WHERE user_id = :user_id AND service_id = :service_id AND status = 'fired'",
+            params! { "user_id" => user_id, "service_id" => service_id },
+        )
+        .map_err(|e| e.to_string())?;
+
+    for (effect, undo_state) in fired {
+        let effect: Effect = serde_json::from_str(&effect).map_err(|e| e.to_string())?;
+        let undo: Value = undo_state
+            .as_deref()
+            .map(serde_json::from_str)
+            .transpose()
+            .map_err(|e| e.to_string())?
+            .unwrap_or(Value::Null);
+
+        match effect {
+            Effect::ExfiltrationAlert { title, .. } => {
+                let acknowledged: Option<bool> = conn
+                    .exec_first(
+                        "SELECT is_acknowledged FROM security_alerts WHERE id = :id",
+                        params! { "id" => undo["alert_id"].as_u64() },
+                    )
+                    .map_err(|e| e.to_string())?;
+                if acknowledged == Some(false) {
+                    outstanding.push(format!("Acknowledge the security alert \"{}\"", title));
+                }
+            }
+            Effect::UnauthorizedDebit { description, .. } => {
+                let reversed: Option<bool> = conn
+                    .exec_first(
+                        "SELECT reversed_at IS NOT NULL FROM bank_transactions WHERE id = :id",
+                        params! { "id" => undo["transaction_id"].as_u64() },
+                    )
+                    .map_err(|e| e.to_string())?;
+                if reversed == Some(false) {
+                    outstanding.push(format!("Dispute the \"{}\" charge in the bank app", description));
+                }
+            }
+            Effect::SpoofedEmail { subject, .. } => {
+                // Deleting the email or flagging it both count
+                let classification: Option<String> = conn
+                    .exec_first(
+                        "SELECT classification FROM user_emails WHERE id = :id AND folder <> 'trash'",
+                        params! { "id" => undo["email_id"].as_u64() },
+                    )
+                    .map_err(|e| e.to_string())?;
+                if classification.as_deref() == Some("none") {
+                    outstanding.push(format!("Report or delete the spoofed email \"{}\"", subject));
+                }
+            }
+            Effect::DegradeSetting { setting, .. } => {
+                let column = degradable_setting(&setting)?;
+                let current: Option<Option<String>> = conn
+                    .exec_first(
+                        format!("SELECT CAST({} AS CHAR) FROM user_settings WHERE user_id = :user_id", column),
+                        params! { "user_id" => user_id },
+                    )
+                    .map_err(|e| e.to_string())?;
+                let original = undo["original"].as_str().map(str::to_string);
+                if current.flatten() != original {
+                    outstanding.push(format!("Restore the {} setting", column));
+                }
+            }
+        }
+    }
+
+    Ok(outstanding)
+}
+
+#[command]
+pub fn process_service_effects(user_id: u64) -> Result<usize, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    run_due_effects(&mut conn, user_id)
+}
+
+#[command]
+pub fn get_security_alerts(user_id: u64) -> Result<Vec<SecurityAlert>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    run_due_effects(&mut conn, user_id)?;
+
+    conn.exec_map(
+        r"SELECT id, user_id, service_id, severity, title, message, is_acknowledged, created_at
+          FROM security_alerts WHERE user_id = :user_id ORDER BY created_at DESC",
+        params! { "user_id" => user_id },
+        |(id, user_id, service_id, severity, title, message, is_acknowledged, created_at)| SecurityAlert {
+            id,
+            user_id,
+            service_id,
+            severity,
+            title,
+            message,
+            is_acknowledged,
+            created_at,
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn acknowledge_alert(user_id: u64, alert_id: u64) -> Result<(), String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    conn.exec_drop(
+        "UPDATE security_alerts SET is_acknowledged = true WHERE id = :alert_id AND user_id = :user_id",
+        params! { "alert_id" => alert_id, "user_id" => user_id },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn get_remediation_tasks(user_id: u64) -> Result<Vec<RemediationTask>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    conn.exec_map(
+        r"SELECT id, user_id, service_id, title, description, points, status, created_at, resolved_at
+          FROM remediation_tasks WHERE user_id = :user_id ORDER BY created_at DESC",
+        params! { "user_id" => user_id },
+        |(id, user_id, service_id, title, description, points, status, created_at, resolved_at)| RemediationTask {
+            id,
+            user_id,
+            service_id,
+            title,
+            description,
+            points,
+            status,
+            created_at,
+            resolved_at,
+        },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[command]
+pub fn verify_remediation(user_id: u64, remediation_id: u64) -> Result<RemediationResult, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    run_due_effects(&mut conn, user_id)?;
+
+    let (service_id, title, points, status): (u64, String, i32, String) = conn
+        .exec_first(
+            "SELECT service_id, title, points, status FROM remediation_tasks WHERE id = :id AND user_id = :user_id",
+            params! { "id" => remediation_id, "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Remediation task not found".to_string())?;
+
+    if status == "resolved" {
+        return Ok(RemediationResult { resolved: true, outstanding: Vec::new() });
+    }
+
+    let outstanding = outstanding_damage(&mut conn, user_id, service_id)?;
+    if !outstanding.is_empty() {
+        return Ok(RemediationResult { resolved: false, outstanding });
+    }
+
+    // Uninstalling before anything fired leaves nothing to clean up, so it resolves without a reward
+    let fired: u64 = conn
+        .exec_first(
+            "SELECT COUNT(*) FROM scheduled_effects WHERE user_id = :user_id AND service_id = :service_id AND status = 'fired'",
+            params! { "user_id" => user_id, "service_id" => service_id },
+        )
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+
+    conn.exec_drop(
+        "UPDATE remediation_tasks SET status = 'resolved', resolved_at = NOW() WHERE id = :id AND status = 'open'",
+        params! { "id" => remediation_id },
+    )
+    .map_err(|e| e.to_string())?;
+    if conn.affected_rows() > 0 && fired > 0 {
+        super::bank::award_points(user_id, points, format!("Reward: {}", title))?;
+    }
+
+    Ok(RemediationResult { resolved: true, outstanding })
+}
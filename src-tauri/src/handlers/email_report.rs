@@ -0,0 +1,187 @@
+use crate::db;
+use crate::models::campaign::CampaignEvent;
+use crate::models::mail::{AwarenessScore, EmailHeaders, EmailLabel, EmailReport};
+use crate::utils::email_grading;
+use chrono::NaiveDateTime;
+use mysql::{params, prelude::*, PooledConn};
+use tauri::command;
+
+/// Game-clock seconds the security desk takes to answer a report.
+const DESK_REPLY_DELAY_SECONDS: i64 = 90;
+
+type ReportRow = (i32, i32, Option<i32>, String, String, bool, Option<String>, NaiveDateTime, NaiveDateTime, Option<i32>);
+
+// The verdict stays hidden until the desk replies, or reporting would be a free oracle
+fn to_report(row: ReportRow) -> Result<EmailReport, String> {
+    let (id, user_id, email_id, subject, label, correct, note, reported_at, reply_at, reply_email_id) = row;
+    let answered = reply_email_id.is_some();
+    Ok(EmailReport {
+        id,
+        user_id,
+        email_id,
+        subject,
+        label: if answered { Some(EmailLabel::parse(&label)?) } else { None },
+        correct: answered.then_some(correct),
+        note,
+        reported_at,
+        reply_at,
+        reply_email_id,
+    })
+}
+
+fn desk_address() -> String {
+    format!("security@{}", super::mail::USER_MAIL_DOMAIN)
+}
+
+/// Sends the security desk's answers to reports whose reply is due. Returns how many were sent.
+pub fn send_desk_replies(conn: &mut PooledConn, user_id: i32) -> Result<usize, String> {
+    let due: Vec<(i32, Option<i32>, String, String)> = conn
+        .exec(
+            r"SELECT id, user_email_id, subject, reply_body FROM email_reports
+              WHERE user_id = :user_id AND reply_email_id IS NULL AND reply_at <= NOW()
+              ORDER BY reply_at",
+            params! { "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let from_user = desk_address();
+    let mut sent = 0;
+    for (report_id, email_id, subject, body) in due {
+        let mut headers = EmailHeaders::authenticated(&from_user, "10.0.0.5", &format!("report-{}", report_id));
+        // Threads under the reported email unless it has since been deleted
+        if let Some(email_id) = email_id {
+            headers.in_reply_to = super::mail::load_email(conn, email_id)?.headers.message_id;
+        }
+
+        let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+        // The desk is trusted, so its replies skip the spam filter
+        tx.exec_drop(
+            r"INSERT INTO user_emails (user_id, from_user, subject, body, headers, label, folder, filter_checked, is_read, classification)
+              VALUES (:user_id, :from_user, :subject, :body, :headers, 'legitimate', 'inbox', true, false, 'none')",
+            params! {
+                "user_id" => user_id,
+                "from_user" => &from_user,
+                "subject" => super::mail::prefixed_subject("Re:", &format!("Reported: {}", subject)),
+                "body" => body,
+                "headers" => serde_json::to_string(&headers).map_err(|e| e.to_string())?,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+        let reply_id = tx.last_insert_id().ok_or_else(|| "Failed to deliver the desk's reply".to_string())?;
+
+        // Claim the report last so a concurrent poll that got here first wins
+        tx.exec_drop(
+            "UPDATE email_reports SET reply_email_id = :reply_id WHERE id = :id AND reply_email_id IS NULL",
+            params! { "reply_id" => reply_id, "id" => report_id },
+        )
+        .map_err(|e| e.to_string())?;
+        if tx.affected_rows() == 0 {
+            tx.rollback().map_err(|e| e.to_string())?;
+            continue;
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        sent += 1;
+    }
+    Ok(sent)
+}
+
+/// Reports an email to the security desk. The desk checks it against the ground truth and
+/// replies after a short delay.
+#[command]
+pub fn report_email(user_id: i32, email_id: i32, note: Option<String>) -> Result<EmailReport, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let email = super::mail::load_own_email(&mut conn, user_id, email_id)?;
+
+    let reported: Option<i32> = conn
+        .exec_first("SELECT id FROM email_reports WHERE user_email_id = ?", (email_id,))
+        .map_err(|e| e.to_string())?;
+    if reported.is_some() {
+        return Err("You have already reported this email".to_string());
+    }
+    let grade = super::mail::load_email_grades(&mut conn, "ue.id = ?", (email_id,))?
+        .pop()
+        .ok_or_else(|| "The security desk has no verdict for this email".to_string())?;
+
+    let note = note.map(|note| note.trim().to_string()).filter(|note| !note.is_empty());
+    let reply_body = email_grading::desk_reply(&email.subject, grade.label, &grade.indicators);
+    conn.exec_drop(
+        r"INSERT INTO email_reports (user_id, user_email_id, subject, label, correct, note, reply_body, reply_at)
+          VALUES (:user_id, :email_id, :subject, :label, :correct, :note, :reply_body, NOW() + INTERVAL :delay SECOND)",
+        params! {
+            "user_id" => user_id,
+            "email_id" => email_id,
+            "subject" => &email.subject,
+            "label" => grade.label.as_str(),
+            "correct" => grade.label != EmailLabel::Legitimate,
+            "note" => note,
+            "reply_body" => reply_body,
+            "delay" => DESK_REPLY_DELAY_SECONDS,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    let report_id = conn.last_insert_id() as i32;
+    super::campaign::record_event(&mut conn, email_id, CampaignEvent::Reported)?;
+
+    let row: Option<ReportRow> = conn
+        .exec_first(
+            r"SELECT id, user_id, user_email_id, subject, label, correct, note, reported_at, reply_at, reply_email_id
+              FROM email_reports WHERE id = ?",
+            (report_id,),
+        )
+        .map_err(|e| e.to_string())?;
+    to_report(row.ok_or_else(|| "Report not found".to_string())?)
+}
+
+#[command]
+pub fn get_email_reports(user_id: i32) -> Result<Vec<EmailReport>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    send_desk_replies(&mut conn, user_id)?;
+
+    let rows: Vec<ReportRow> = conn
+        .exec(
+            r"SELECT id, user_id, user_email_id, subject, label, correct, note, reported_at, reply_at, reply_email_id
+              FROM email_reports WHERE user_id = ? ORDER BY reported_at DESC, id DESC",
+            (user_id,),
+        )
+        .map_err(|e| e.to_string())?;
+    rows.into_iter().map(to_report).collect()
+}
+
+/// The user's awareness score from their reports, the phishing they read but let through and
+/// the phishing links they followed.
+#[command]
+pub fn get_awareness_score(user_id: i32) -> Result<AwarenessScore, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    send_desk_replies(&mut conn, user_id)?;
+    // Reports only count once the desk has answered them
+    let (correct, false_reports): (Option<u32>, Option<u32>) = conn
+        .exec_first(
+            r"SELECT CAST(SUM(correct) AS UNSIGNED), CAST(SUM(NOT correct) AS UNSIGNED) FROM email_reports
+              WHERE user_id = ? AND reply_email_id IS NOT NULL",
+            (user_id,),
+        )
+        .map_err(|e| e.to_string())?
+        .unwrap_or((None, None));
+
+    // Flagging it as phishing shows the user spotted it, even without a report
+    let missed: u32 = conn
+        .exec_first(
+            r"SELECT COUNT(*)
+              FROM user_emails ue
+              LEFT JOIN universal_emails ue_universal ON ue.universal_email_id = ue_universal.id
+              WHERE ue.user_id = ? AND ue.is_read AND ue.folder <> 'sent'
+                AND COALESCE(ue.label, ue_universal.label) = 'phishing'
+                AND ue.classification <> 'phishing'
+                AND NOT EXISTS (SELECT 1 FROM email_reports r WHERE r.user_email_id = ue.id)",
+            (user_id,),
+        )
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+
+    let clicks: u32 = conn
+        .exec_first("SELECT COUNT(*) FROM link_clicks WHERE user_id = ? AND phishing", (user_id,))
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+
+    Ok(email_grading::awareness(correct.unwrap_or(0), false_reports.unwrap_or(0), missed, clicks))
+}
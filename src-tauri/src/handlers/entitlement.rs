@@ -0,0 +1,55 @@
+use crate::db;
+use crate::models::service::Capability;
+use mysql::{params, prelude::*};
+use tauri::command;
+
+/// Fails with a "requires service X" error unless one of the user's enabled services grants `capability`.
+pub fn require_capability<Q: Queryable>(conn: &mut Q, user_id: u64, capability: Capability) -> Result<(), String> {
+    let providers: Vec<(String, Option<String>)> = conn
+        .exec(
+            r"SELECT s.name, us.status
+              FROM service_capabilities sc
+              JOIN services s ON s.id = sc.service_id
+              LEFT JOIN user_services us ON us.service_id = s.id AND us.user_id = :user_id
+              WHERE sc.capability = :capability
+              ORDER BY s.name",
+            params! { "user_id" => user_id, "capability" => capability.as_str() },
+        )
+        .map_err(|e| e.to_string())?;
+
+    if providers.iter().any(|(_, status)| status.as_deref() == Some("enabled")) {
+        return Ok(());
+    }
+
+    let names: Vec<&str> = providers.iter().map(|(name, _)| name.as_str()).collect();
+    if names.is_empty() {
+        return Err(format!("No service provides {}", capability.as_str()));
+    }
+
+    let owned: Vec<&str> = providers
+        .iter()
+        .filter(|(_, status)| status.is_some())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if owned.is_empty() {
+        Err(format!("Requires service {}", names.join(" or ")))
+    } else {
+        Err(format!("Requires service {} to be installed and enabled in Settings > Security", owned.join(" or ")))
+    }
+}
+
+#[command]
+pub fn get_entitlements(user_id: u64) -> Result<Vec<Capability>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let capabilities: Vec<String> = conn
+        .exec(
+            r"SELECT DISTINCT sc.capability
+              FROM service_capabilities sc
+              JOIN user_services us ON us.service_id = sc.service_id
+              WHERE us.user_id = :user_id AND us.status = 'enabled'",
+            params! { "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?;
+
+    capabilities.iter().map(|c| Capability::parse(c)).collect()
+}
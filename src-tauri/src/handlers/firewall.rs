@@ -1,5 +1,7 @@
 use crate::db::get_db_connection;
+use crate::handlers::entitlement::require_capability;
 use crate::models::firewall::FirewallRule;
+use crate::models::service::Capability;
 use mysql::prelude::Queryable;
 
 #[tauri::command]
@@ -18,6 +20,7 @@ pub fn list_firewall_rules(user_id: u32) -> Result<Vec<FirewallRule>, String> {
 #[tauri::command]
 pub fn add_firewall_rule(user_id: u32, rule: String) -> Result<String, String> {
     let mut conn = get_db_connection().map_err(|e| e.to_string())?;
+    require_capability(&mut conn, user_id.into(), Capability::Firewall)?;
     conn.exec_drop(
         "INSERT INTO firewall_rules (user_id, rule) VALUES (?, ?)",
         (user_id, rule),
@@ -29,6 +32,7 @@ pub fn add_firewall_rule(user_id: u32, rule: String) -> Result<String, String> {
 #[tauri::command]
 pub fn remove_firewall_rule(user_id: u32, rule: String) -> Result<String, String> {
     let mut conn = get_db_connection().map_err(|e| e.to_string())?;
+    require_capability(&mut conn, user_id.into(), Capability::Firewall)?;
     conn.exec_drop(
         "DELETE FROM firewall_rules WHERE user_id = ? AND rule = ?",
         (user_id, rule),
@@ -0,0 +1,76 @@
+use crate::db;
+use crate::models::flag::UserFlags;
+use crate::models::mail::Email;
+use crate::utils::flags;
+use mysql::{params, prelude::*};
+use tauri::command;
+
+/// The user's flags, giving them a random seed the first time they are asked for.
+pub fn user_flags<Q: Queryable>(conn: &mut Q, user_id: u64) -> Result<UserFlags, String> {
+    let seed: Option<u64> = conn
+        .exec_first("SELECT seed FROM user_flag_seeds WHERE user_id = ?", (user_id,))
+        .map_err(|e| e.to_string())?;
+    if let Some(seed) = seed {
+        return Ok(flags::generate(seed));
+    }
+
+    // A concurrent call may have seeded the user already, in which case its seed wins
+    conn.exec_drop(
+        "INSERT IGNORE INTO user_flag_seeds (user_id, seed) VALUES (?, ?)",
+        (user_id, flags::new_seed()),
+    )
+    .map_err(|e| e.to_string())?;
+    let seed: u64 = conn
+        .exec_first("SELECT seed FROM user_flag_seeds WHERE user_id = ?", (user_id,))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "User not found".to_string())?;
+    Ok(flags::generate(seed))
+}
+
+/// Fills the flag placeholders in seeded content with the user's own flags.
+pub fn personalize<Q: Queryable>(conn: &mut Q, user_id: u64, text: &str) -> Result<String, String> {
+    if !text.contains("{{flag:") {
+        return Ok(text.to_string());
+    }
+    Ok(flags::render(text, &user_flags(conn, user_id)?))
+}
+
+/// Personalizes the bodies of universal emails, which are shared by every user.
+pub fn personalize_emails<Q: Queryable>(conn: &mut Q, emails: &mut [Email]) -> Result<(), String> {
+    for email in emails.iter_mut().filter(|email| email.universal_email_id.is_some()) {
+        email.body = personalize(conn, email.user_id as u64, &email.body)?;
+    }
+    Ok(())
+}
+
+#[command]
+pub fn get_user_flags(instructor_id: i32, user_id: u64) -> Result<UserFlags, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    super::account::require_instructor(&mut conn, instructor_id)?;
+    user_flags(&mut conn, user_id)
+}
+
+/// Rebuilds a user's instance from `seed`, or from a fresh one when none is given. Passing a
+/// seed the user had before gives them the same flags again. Progress made against the old
+/// flags is cleared, but completed tasks stay completed.
+#[command]
+pub fn regenerate_user_flags(instructor_id: i32, user_id: u64, seed: Option<u64>) -> Result<UserFlags, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    super::account::require_instructor(&mut conn, instructor_id)?;
+    let seed = seed.unwrap_or_else(flags::new_seed);
+
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+    tx.exec_drop(
+        r"INSERT INTO user_flag_seeds (user_id, seed) VALUES (:user_id, :seed)
+          ON DUPLICATE KEY UPDATE seed = :seed, generated_at = NOW()",
+        params! { "user_id" => user_id, "seed" => seed },
+    )
+    .map_err(|e| e.to_string())?;
+    tx.exec_drop("DELETE FROM cracked_hashes WHERE user_id = ?", (user_id,))
+        .map_err(|e| e.to_string())?;
+    tx.exec_drop("DELETE FROM encrypted_files WHERE user_id = ?", (user_id,))
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(flags::generate(seed))
+}
@@ -1,25 +1,55 @@
-use crate::{db, handlers::task::complete_task};
+use crate::{db, handlers::flags, handlers::task::{submit_task, user_task_spec}};
+use crate::models::task::TaskSpec;
+use crate::models::fs::UserFile;
 use mysql::params;
 use mysql::prelude::*;
 use tauri::command;
 
+// Files seeded into every home directory. Flag placeholders are filled in per user.
+const READABLE_FILES: &[(&str, &str)] = &[("/home/user/code.txt", "Fragment 2/2: 2nd key \nEncoded in Base64: {{flag:fragment_2}}")];
+
+// Seeded files whose content is only shown once they are decrypted
+const ENCRYPTED_FILES: &[(&str, &str)] = &[(
+    "/home/user/sensitive_data.txt",
+    "Hash Passwords:\n\n261e672695a654895fed24313e5246de\n\n{{flag:md5_1}}\n\n9594de8de31942437651eb4162554253\n\n\
+     {{flag:md5_2}}\n\n59a84b33a4aa9e6953051510396308b1\n\n{{flag:md5_3}}\n\n{{flag:md5_4}}\n\n\
+     5f4dcc3b5aa765d61d8327deb882cf99\n\n{{flag:md5_5}}",
+)];
+
+fn seeded_file<Q: Queryable>(conn: &mut Q, user_id: u64, files: &[(&str, &str)], file_path: &str) -> Result<String, String> {
+    let (_, content) = files
+        .iter()
+        .find(|(path, _)| *path == file_path)
+        .ok_or_else(|| "File not found.".to_string())?;
+    flags::personalize(conn, user_id, content)
+}
+
+/// Encrypts a file that a `file_encrypted` task asks for, then submits that task.
 #[command]
 pub fn encrypt_file(file_path: String, password: String, user_id: u64) -> Result<String, String> {
-    if file_path == "/home/user/sensitive_data.txt" && password == "TopSecret#9900" {
-        let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-        conn.exec_drop(
-            "INSERT IGNORE INTO encrypted_files (file_path, user_id) VALUES (:file_path, :user_id)",
-            params! { "file_path" => &file_path, "user_id" => user_id },
-        )
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let tasks: Vec<(u64, String, Option<String>)> = conn
+        .exec("SELECT id, task_type, task_data FROM universal_tasks WHERE task_type = 'file_encrypted'", ())
         .map_err(|e| e.to_string())?;
 
-        // Mark Level 4 as complete
-        complete_task(4, user_id)?;
-
-        Ok("File encrypted successfully.".to_string())
-    } else {
-        Err("Invalid file path or password.".to_string())
+    let mut matching_task = None;
+    for (task_id, task_type, task_data) in tasks {
+        if let TaskSpec::FileEncrypted(data) = user_task_spec(&mut conn, user_id, &task_type, task_data.as_deref())? {
+            if data.file_path == file_path && data.password == password {
+                matching_task = Some(task_id);
+            }
+        }
     }
+    let task_id = matching_task.ok_or_else(|| "Invalid file path or password.".to_string())?;
+
+    conn.exec_drop(
+        "INSERT IGNORE INTO encrypted_files (file_path, user_id) VALUES (:file_path, :user_id)",
+        params! { "file_path" => &file_path, "user_id" => user_id },
+    )
+    .map_err(|e| e.to_string())?;
+    submit_task(user_id, task_id, None)?;
+
+    Ok("File encrypted successfully.".to_string())
 }
 
 #[command]
@@ -48,30 +78,45 @@ pub fn is_file_encrypted(file_path: String, user_id: u64) -> Result<bool, String
 //     Ok("Encrypted file entry deleted successfully.".to_string())
 // }
 
+/// Reads a seeded file that is never encrypted.
 #[command]
-pub fn decrypt_file_content(file_path: String, password: String) -> Result<String, String> {
-    if file_path == "/home/user/sensitive_data.txt" && password == "TopSecret#9900" {
-        Ok("Hash Passwords:
-
-261e672695a654895fed24313e5246de
-
-81888ab915f5e5363bc4ee7220805c74
-
-9594de8de31942437651eb4162554253
-
-007587d6d21505c9f73c140a4518c580
-
-59a84b33a4aa9e6953051510396308b1
-
-27313a3e077d07b1eb97c745509313bf
-
-80a871b60516a8724d628c129d91d618
-
-5f4dcc3b5aa765d61d8327deb882cf99
+pub fn read_seeded_file(file_path: String, user_id: u64) -> Result<String, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    seeded_file(&mut conn, user_id, READABLE_FILES, &file_path)
+}
 
-850e217e5a9fe749cb9aea7e050bdb88"
-            .to_string())
-    } else {
-        Err("Invalid password.".to_string())
+/// Decrypts a file with the password its `file_encrypted` task gave this user.
+#[command]
+pub fn decrypt_file_content(file_path: String, password: String, user_id: u64) -> Result<String, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let tasks: Vec<(String, Option<String>)> = conn
+        .exec("SELECT task_type, task_data FROM universal_tasks WHERE task_type = 'file_encrypted'", ())
+        .map_err(|e| e.to_string())?;
+    for (task_type, task_data) in tasks {
+        if let TaskSpec::FileEncrypted(data) = user_task_spec(&mut conn, user_id, &task_type, task_data.as_deref())? {
+            if data.file_path == file_path && data.password == password {
+                return seeded_file(&mut conn, user_id, ENCRYPTED_FILES, &file_path);
+            }
+        }
     }
+    Err("Invalid password.".to_string())
+}
+
+/// Files the user has saved into their virtual home directory, such as email attachments.
+#[command]
+pub fn list_user_files(user_id: u64) -> Result<Vec<UserFile>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    conn.exec_map(
+        "SELECT id, path, mime_type, size_bytes, source_attachment_id, created_at FROM user_files WHERE user_id = :user_id ORDER BY path",
+        params! { "user_id" => user_id },
+        |(id, path, mime_type, size_bytes, source_attachment_id, created_at)| UserFile {
+            id,
+            path,
+            mime_type,
+            size_bytes,
+            source_attachment_id,
+            created_at,
+        },
+    )
+    .map_err(|e| e.to_string())
 }
@@ -0,0 +1,113 @@
+use crate::db;
+use crate::models::campaign::CampaignEvent;
+use crate::models::link::{LinkClick, LinkOutcome};
+use crate::models::mail::EmailLabel;
+use crate::utils::deep_link::{self, Route};
+use mysql::{params, prelude::*};
+use tauri::command;
+
+/// Routes a `cybox://` link the user followed inside the app and records the click. Only
+/// links clicked in Cybox's own windows are handled; the scheme is not registered with the
+/// operating system. The response never says whether the link was a trap: a login page
+/// looks the same either way, and the damage from an unsafe link shows up as security
+/// alerts once the user acts on it.
+#[command]
+pub fn open_cybox_link(user_id: i32, url: String, email_id: Option<i32>) -> Result<LinkClick, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let link = deep_link::parse(&url)?;
+
+    // Links in spam and phishing are unsafe whatever they point at
+    let email_label = match email_id {
+        Some(email_id) => {
+            super::mail::load_own_email(&mut conn, user_id, email_id)?;
+            super::mail::load_email_grades(&mut conn, "ue.id = ?", (email_id,))?
+                .pop()
+                .map(|grade| grade.label)
+        }
+        None => None,
+    };
+    let from_bad_email = matches!(email_label, Some(EmailLabel::Phishing) | Some(EmailLabel::Spam));
+    let unsafe_link = link.flag("phishing") || from_bad_email;
+
+    let (outcome, phishing) = match deep_link::route(&link) {
+        Route::Campaign { token } => {
+            super::campaign::record_link_event(&mut conn, &token, &[CampaignEvent::Clicked])?;
+            (LinkOutcome::Login { app: "campaign".to_string() }, true)
+        }
+        Route::Login { app } => (LinkOutcome::Login { app }, unsafe_link),
+        Route::Download { file_name } => {
+            let email_id = email_id.ok_or_else(|| format!("Nothing to download at {}", url))?;
+            let (path, attachment) = super::attachment::download_by_name(&mut conn, user_id, email_id, &file_name)?;
+            let phishing = unsafe_link || !attachment.flags.is_empty();
+            (LinkOutcome::Download { path, file_name, flags: attachment.flags }, phishing)
+        }
+        Route::App { app, path } => (LinkOutcome::Navigate { app, path }, unsafe_link),
+    };
+
+    conn.exec_drop(
+        r"INSERT INTO link_clicks (user_id, user_email_id, url, outcome, phishing)
+          VALUES (:user_id, :email_id, :url, :outcome, :phishing)",
+        params! {
+            "user_id" => user_id,
+            "email_id" => email_id,
+            "url" => &url,
+            "outcome" => outcome.kind(),
+            "phishing" => phishing,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_id() as i32;
+
+    // The saved file's own red flags are visible to the user anyway, so warning about them
+    // gives nothing away
+    if let LinkOutcome::Download { path, file_name, flags } = &outcome {
+        if !flags.is_empty() {
+            super::effects::insert_alert(
+                &mut conn,
+                user_id as u64,
+                None,
+                "high",
+                "Suspicious file downloaded",
+                &format!("{} was saved to {}. Delete it without opening it.", file_name, path),
+            )?;
+        }
+    }
+
+    Ok(LinkClick { id, url, outcome })
+}
+
+/// Records that the user signed in on a login page reached through a link. Only the fact is
+/// kept, never what was typed. Signing in on a phishing page raises a critical alert.
+#[command]
+pub fn submit_link_credentials(user_id: i32, click_id: i32) -> Result<(), String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let (url, phishing): (String, bool) = conn
+        .exec_first(
+            "SELECT url, phishing FROM link_clicks WHERE id = ? AND user_id = ? AND outcome = 'login'",
+            (click_id, user_id),
+        )
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Login page not found".to_string())?;
+    if !phishing {
+        return Ok(());
+    }
+
+    conn.exec_drop(
+        "UPDATE link_clicks SET credentials_submitted_at = COALESCE(credentials_submitted_at, NOW()) WHERE id = ?",
+        (click_id,),
+    )
+    .map_err(|e| e.to_string())?;
+    if let Route::Campaign { token } = deep_link::route(&deep_link::parse(&url)?) {
+        super::campaign::record_link_event(&mut conn, &token, &[CampaignEvent::CredentialsSubmitted])?;
+    }
+
+    super::effects::insert_alert(
+        &mut conn,
+        user_id as u64,
+        None,
+        "critical",
+        "Credentials submitted to a phishing page",
+        &format!("You signed in on a fake page at {}. Change your password now.", url),
+    )?;
+    Ok(())
+}
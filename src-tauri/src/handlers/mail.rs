@@ -1,68 +1,181 @@
 use crate::db;
-use crate::models::mail::Email;
+use crate::models::campaign::CampaignEvent;
+use crate::models::mail::{
+    ClassificationGrade, Email, EmailGrade, EmailHeaders, EmailLabel, EmailPage, EmailSearch, EmailThread, FilterScore, InspectedLink,
+    MailFolder, MailLabel, PhishingIndicator,
+};
+use crate::utils::spam_filter::{self, SpamFilter};
+use crate::utils::{email_generator, email_grading, html, links, rfc5322, threading};
+use mysql::params;
 use mysql::prelude::*;
 
+// Per-user content on user_emails wins over the universal email it was copied from
+const EMAIL_SELECT: &str = r#"
+    SELECT
+        ue.id,
+        ue.user_id,
+        ue.universal_email_id,
+        ue.is_read,
+        ue.classification,
+        ue.created_at,
+        COALESCE(ue.from_user, ue_universal.from_user) AS from_user,
+        COALESCE(ue.subject, ue_universal.subject) AS subject,
+        COALESCE(ue.body, ue_universal.body) AS body,
+        COALESCE(ue.headers, ue_universal.headers) AS headers,
+        ue.folder,
+        (SELECT GROUP_CONCAT(ml.name ORDER BY ml.name SEPARATOR '\n')
+         FROM user_email_labels uel
+         JOIN mail_labels ml ON ml.id = uel.label_id
+         WHERE uel.user_email_id = ue.id) AS labels
+    FROM user_emails ue
+    LEFT JOIN universal_emails ue_universal ON ue.universal_email_id = ue_universal.id
+"#;
+
+fn to_email(mut row: mysql::Row) -> Result<Email, String> {
+    let id: i32 = db::column(&mut row, "id")?;
+    let headers: Option<String> = db::column(&mut row, "headers")?;
+    let headers = match headers {
+        Some(json) => serde_json::from_str(&json).map_err(|e| format!("Invalid headers on email {}: {}", id, e))?,
+        None => EmailHeaders::default(),
+    };
+    let folder: String = db::column(&mut row, "folder")?;
+    let labels: Option<String> = db::column(&mut row, "labels")?;
+
+    Ok(Email {
+        id,
+        user_id: db::column(&mut row, "user_id")?,
+        universal_email_id: db::column(&mut row, "universal_email_id")?,
+        is_read: db::column(&mut row, "is_read")?,
+        classification: db::column(&mut row, "classification")?,
+        created_at: db::column(&mut row, "created_at")?,
+        from_user: db::column(&mut row, "from_user")?,
+        subject: db::column(&mut row, "subject")?,
+        // Stored HTML may come from content packs or other users, so it is never trusted
+        body: html::sanitize_html(&db::column::<String>(&mut row, "body")?),
+        headers,
+        folder: MailFolder::parse(&folder)?,
+        labels: labels.map(|labels| labels.split('\n').map(str::to_string).collect()).unwrap_or_default(),
+    })
+}
+
+/// Loads emails matching `condition` (a SQL expression over `ue`), newest first.
+/// `tail` is appended after the ordering, for `LIMIT` clauses.
+fn select_emails<Q: Queryable, P: Into<mysql::Params>>(conn: &mut Q, condition: &str, tail: &str, params: P) -> Result<Vec<Email>, String> {
+    let query = format!("{} WHERE {} ORDER BY ue.created_at DESC, ue.id DESC {}", EMAIL_SELECT, condition, tail);
+    let rows: Vec<mysql::Row> = conn.exec(query, params).map_err(|e| e.to_string())?;
+    let mut emails = rows.into_iter().map(to_email).collect::<Result<Vec<_>, _>>()?;
+    super::flags::personalize_emails(conn, &mut emails)?;
+    Ok(emails)
+}
+
+pub fn load_emails<Q: Queryable, P: Into<mysql::Params>>(conn: &mut Q, condition: &str, params: P) -> Result<Vec<Email>, String> {
+    select_emails(conn, condition, "", params)
+}
+
+pub fn load_email<Q: Queryable>(conn: &mut Q, email_id: i32) -> Result<Email, String> {
+    load_emails(conn, "ue.id = ?", (email_id,))?
+        .pop()
+        .ok_or_else(|| "Email not found".to_string())
+}
+
+/// Grades the student's classification of every labelled email matching `condition`.
+/// Emails with no ground truth are left out.
+pub fn load_email_grades<Q: Queryable, P: Into<mysql::Params> + Clone>(conn: &mut Q, condition: &str, params: P) -> Result<Vec<EmailGrade>, String> {
+    let labels: std::collections::HashMap<i32, (Option<String>, Option<String>)> = conn
+        .exec_map(
+            format!(
+                r"SELECT ue.id, COALESCE(ue.label, ue_universal.label), COALESCE(ue.indicators, ue_universal.indicators)
+                  FROM user_emails ue
+                  LEFT JOIN universal_emails ue_universal ON ue.universal_email_id = ue_universal.id
+                  WHERE {}",
+                condition
+            ),
+            params.clone(),
+            |(id, label, indicators)| (id, (label, indicators)),
+        )
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .collect();
+
+    let mut grades = Vec::new();
+    for email in load_emails(conn, condition, params)? {
+        let Some((Some(label), indicators)) = labels.get(&email.id) else {
+            continue;
+        };
+        let indicators: Vec<PhishingIndicator> = match indicators {
+            Some(json) => serde_json::from_str(json).map_err(|e| format!("Invalid indicators on email {}: {}", email.id, e))?,
+            None => Vec::new(),
+        };
+        grades.push(email_grading::grade_email(&email, EmailLabel::parse(label)?, indicators)?);
+    }
+    Ok(grades)
+}
+
+/// Emails at least this likely to be spam or phishing are quarantined.
+const QUARANTINE_THRESHOLD: f64 = 0.9;
+
+/// The user's filter: the bundled corpus plus every email they have classified. Mail they
+/// only read says nothing about what they think of it, so it is not trained on.
+fn train_filter<Q: Queryable>(conn: &mut Q, user_id: i32) -> Result<SpamFilter, String> {
+    let mut filter = SpamFilter::with_base_corpus();
+    let decided = load_emails(conn, "ue.user_id = ? AND ue.classification <> 'none'", (user_id,))?;
+    for email in decided {
+        let label = EmailLabel::from_classification(&email.classification)?;
+        filter.train(label, &spam_filter::tokenize(&email), spam_filter::USER_EXAMPLE_WEIGHT);
+    }
+    Ok(filter)
+}
+
+/// When the user's `email_filter` setting is on, judges inbox mail the filter has not seen yet
+/// and moves likely spam and phishing to quarantine. Returns how many were quarantined.
+pub fn apply_email_filter<Q: Queryable>(conn: &mut Q, user_id: i32) -> Result<usize, String> {
+    let enabled: Option<bool> = conn
+        .exec_first("SELECT email_filter FROM user_settings WHERE user_id = ?", (user_id,))
+        .map_err(|e| e.to_string())?;
+    if !enabled.unwrap_or(false) {
+        return Ok(0);
+    }
+
+    let unchecked = load_emails(
+        conn,
+        "ue.user_id = ? AND ue.folder = 'inbox' AND NOT ue.filter_checked AND ue.classification = 'none'",
+        (user_id,),
+    )?;
+    if unchecked.is_empty() {
+        return Ok(0);
+    }
+
+    let filter = train_filter(conn, user_id)?;
+    let mut quarantined = 0;
+    for email in unchecked {
+        let outcome = filter.classify(&spam_filter::tokenize(&email));
+        let folder = if outcome.unwanted() >= QUARANTINE_THRESHOLD {
+            quarantined += 1;
+            "quarantine"
+        } else {
+            "inbox"
+        };
+        conn.exec_drop(
+            "UPDATE user_emails SET filter_checked = true, folder = ? WHERE id = ?",
+            (folder, email.id),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(quarantined)
+}
+
 #[tauri::command]
 pub fn get_emails(user_id: i32) -> Result<Vec<Email>, String> {
     println!("Fetching emails for user: {}", user_id);
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
     
-    let insert_query = r#"
-        INSERT INTO user_emails (user_id, universal_email_id, is_read, classification)
-        SELECT ?, ue.id, false, 'none'
-        FROM universal_emails ue
-        WHERE NOT EXISTS (
-            SELECT 1
-            FROM user_emails
-            WHERE user_id = ? AND universal_email_id = ue.id
-        )
-    "#;
-    conn.exec_drop(insert_query, (user_id, user_id)).map_err(|e| e.to_string())?;
-
-    let query = r#"
-        SELECT
-            ue.id,
-            ue.user_id,
-            ue.universal_email_id,
-            ue.is_read,
-            ue.classification,
-            ue.created_at,
-            ue_universal.from_user,
-            ue_universal.subject,
-            ue_universal.body
-        FROM user_emails ue
-        JOIN universal_emails ue_universal ON ue.universal_email_id = ue_universal.id
-        WHERE ue.user_id = ?
-        ORDER BY ue.created_at DESC
-    "#;
-
-    let emails = conn
-        .exec_map(
-            query,
-            (user_id,),
-            |(id, user_id, universal_email_id, is_read, classification, created_at, from_user, subject, body): (
-                i32,
-                i32,
-                i32,
-                bool,
-                String,
-                chrono::NaiveDateTime,
-                String,
-                String,
-                String,
-            )| Email {
-                id,
-                user_id,
-                universal_email_id,
-                is_read,
-                classification,
-                created_at,
-                from_user,
-                subject,
-                body,
-            },
-        )
-        .map_err(|e| e.to_string())?;
+    super::mail_delivery::dispatch_emails(&mut conn, user_id)?;
+    super::email_report::send_desk_replies(&mut conn, user_id)?;
+    super::campaign::deliver_campaign_emails(&mut conn, user_id)?;
+    super::effects::run_due_effects(&mut conn, user_id as u64)?;
+    apply_email_filter(&mut conn, user_id)?;
+
+    let emails = load_emails(&mut conn, "ue.user_id = ?", (user_id,))?;
 
     println!("Found {} emails for user: {}", emails.len(), user_id);
     Ok(emails)
@@ -76,17 +189,225 @@ pub fn mark_email_as_read(email_id: i32) -> Result<(), String> {
         (email_id,),
     )
     .map_err(|e| e.to_string())?;
+    super::campaign::record_event(&mut conn, email_id, CampaignEvent::Opened)?;
     Ok(())
 }
 
+/// Moves an email to the trash, or deletes it for good if it is already there.
 #[tauri::command]
 pub fn delete_email(email_id: i32) -> Result<(), String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-    conn.exec_drop("DELETE FROM user_emails WHERE id = ?", (email_id,))
+    let folder = load_email(&mut conn, email_id)?.folder;
+    if folder == MailFolder::Trash {
+        conn.exec_drop("DELETE FROM user_emails WHERE id = ?", (email_id,))
+            .map_err(|e| e.to_string())?;
+    } else {
+        conn.exec_drop(
+            "UPDATE user_emails SET folder = 'trash', trashed_from = ? WHERE id = ?",
+            (folder.as_str(), email_id),
+        )
         .map_err(|e| e.to_string())?;
+    }
     Ok(())
 }
 
+/// Puts a trashed email back in the folder it was deleted from.
+#[tauri::command]
+pub fn restore_email(email_id: i32) -> Result<MailFolder, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let trashed_from: Option<Option<String>> = conn
+        .exec_first("SELECT trashed_from FROM user_emails WHERE id = ? AND folder = 'trash'", (email_id,))
+        .map_err(|e| e.to_string())?;
+    let folder = match trashed_from.ok_or_else(|| "Email is not in the trash".to_string())? {
+        Some(folder) => MailFolder::parse(&folder)?,
+        None => MailFolder::Inbox,
+    };
+    conn.exec_drop(
+        "UPDATE user_emails SET folder = ?, trashed_from = NULL WHERE id = ?",
+        (folder.as_str(), email_id),
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(folder)
+}
+
+#[tauri::command]
+pub fn empty_trash(user_id: i32) -> Result<u64, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let deleted = conn
+        .exec_iter("DELETE FROM user_emails WHERE user_id = ? AND folder = 'trash'", (user_id,))
+        .map_err(|e| e.to_string())?
+        .affected_rows();
+    Ok(deleted)
+}
+
+/// Files an email in the inbox or the archive. Trash and quarantine have their own commands.
+#[tauri::command]
+pub fn move_email(email_id: i32, folder: MailFolder) -> Result<(), String> {
+    if !matches!(folder, MailFolder::Inbox | MailFolder::Archive) {
+        return Err(format!("Emails cannot be moved to {}", folder.as_str()));
+    }
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let current = load_email(&mut conn, email_id)?.folder;
+    if matches!(current, MailFolder::Sent | MailFolder::Trash) {
+        return Err(format!("Emails in {} cannot be moved", current.as_str()));
+    }
+    conn.exec_drop("UPDATE user_emails SET folder = ? WHERE id = ?", (folder.as_str(), email_id))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_mail_labels(user_id: i32) -> Result<Vec<MailLabel>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    conn.exec_map(
+        "SELECT id, name, color FROM mail_labels WHERE user_id = ? ORDER BY name",
+        (user_id,),
+        |(id, name, color)| MailLabel { id, name, color },
+    )
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn create_mail_label(user_id: i32, name: String, color: Option<String>) -> Result<MailLabel, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() || name.len() > 50 || name.contains('\n') {
+        return Err("Label names must be 1 to 50 characters on one line".to_string());
+    }
+    let color = color.unwrap_or_else(|| "#60a5fa".to_string());
+
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let existing: Option<i32> = conn
+        .exec_first("SELECT id FROM mail_labels WHERE user_id = ? AND name = ?", (user_id, &name))
+        .map_err(|e| e.to_string())?;
+    if existing.is_some() {
+        return Err(format!("You already have a label called {}", name));
+    }
+    let id = conn
+        .exec_iter(
+            "INSERT INTO mail_labels (user_id, name, color) VALUES (?, ?, ?)",
+            (user_id, &name, &color),
+        )
+        .map_err(|e| e.to_string())?
+        .last_insert_id()
+        .ok_or_else(|| "Failed to create label".to_string())?;
+    Ok(MailLabel { id: id as i32, name, color })
+}
+
+#[tauri::command]
+pub fn delete_mail_label(user_id: i32, label_id: i32) -> Result<(), String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    conn.exec_drop("DELETE FROM mail_labels WHERE id = ? AND user_id = ?", (label_id, user_id))
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Adds or removes one of the user's labels on one of their emails.
+#[tauri::command]
+pub fn set_email_label(email_id: i32, label_id: i32, applied: bool) -> Result<(), String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let owners: Option<(i32, i32)> = conn
+        .exec_first(
+            r"SELECT ue.user_id, ml.user_id FROM user_emails ue, mail_labels ml WHERE ue.id = ? AND ml.id = ?",
+            (email_id, label_id),
+        )
+        .map_err(|e| e.to_string())?;
+    match owners {
+        Some((email_owner, label_owner)) if email_owner == label_owner => {}
+        _ => return Err("Email or label not found".to_string()),
+    }
+
+    let query = if applied {
+        "INSERT IGNORE INTO user_email_labels (user_email_id, label_id) VALUES (?, ?)"
+    } else {
+        "DELETE FROM user_email_labels WHERE user_email_id = ? AND label_id = ?"
+    };
+    conn.exec_drop(query, (email_id, label_id)).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Conversations with at least one email in `folder`, most recently active first. Threads are
+/// built across folders, so an inbox thread includes the user's own replies from sent. Trashed
+/// mail only appears when viewing the trash.
+#[tauri::command]
+pub fn get_email_threads(user_id: i32, folder: MailFolder) -> Result<Vec<EmailThread>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let emails = if folder == MailFolder::Trash {
+        load_emails(&mut conn, "ue.user_id = ?", (user_id,))?
+    } else {
+        load_emails(&mut conn, "ue.user_id = ? AND ue.folder <> 'trash'", (user_id,))?
+    };
+    Ok(threading::thread_emails(emails)
+        .into_iter()
+        .filter(|thread| thread.emails.iter().any(|email| email.folder == folder))
+        .collect())
+}
+
+const DEFAULT_PAGE_SIZE: u32 = 20;
+const MAX_PAGE_SIZE: u32 = 100;
+
+/// Full-text search over sender, subject and body, filtered and paginated.
+#[tauri::command]
+pub fn search_emails(query: EmailSearch) -> Result<EmailPage, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+
+    let mut conditions: Vec<String> = vec!["ue.user_id = ?".to_string()];
+    let mut params_vec: Vec<mysql::Value> = vec![query.user_id.into()];
+
+    if let Some(terms) = query.text.as_deref().and_then(super::service::fulltext_terms) {
+        conditions.push(
+            r"(MATCH(ue.from_user, ue.subject, ue.body) AGAINST (? IN BOOLEAN MODE)
+               OR MATCH(ue_universal.from_user, ue_universal.subject, ue_universal.body) AGAINST (? IN BOOLEAN MODE))"
+                .to_string(),
+        );
+        params_vec.push(terms.clone().into());
+        params_vec.push(terms.into());
+    }
+    match query.folder {
+        Some(folder) => {
+            conditions.push("ue.folder = ?".to_string());
+            params_vec.push(folder.as_str().into());
+        }
+        // Trash only shows up when asked for
+        None => conditions.push("ue.folder <> 'trash'".to_string()),
+    }
+    if let Some(is_read) = query.is_read {
+        conditions.push("ue.is_read = ?".to_string());
+        params_vec.push(is_read.into());
+    }
+    if let Some(classification) = &query.classification {
+        EmailLabel::from_classification(classification)?;
+        conditions.push("ue.classification = ?".to_string());
+        params_vec.push(classification.as_str().into());
+    }
+    if let Some(label) = &query.label {
+        conditions.push(
+            r"EXISTS (SELECT 1 FROM user_email_labels uel JOIN mail_labels ml ON ml.id = uel.label_id
+                      WHERE uel.user_email_id = ue.id AND ml.name = ?)"
+                .to_string(),
+        );
+        params_vec.push(label.as_str().into());
+    }
+
+    let condition = conditions.join(" AND ");
+    let total: u64 = conn
+        .exec_first(
+            format!(
+                "SELECT COUNT(*) FROM user_emails ue LEFT JOIN universal_emails ue_universal ON ue.universal_email_id = ue_universal.id WHERE {}",
+                condition
+            ),
+            params_vec.clone(),
+        )
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+    let tail = format!("LIMIT {} OFFSET {}", page_size, (page as u64 - 1) * page_size as u64);
+    let emails = select_emails(&mut conn, &condition, &tail, params_vec)?;
+
+    Ok(EmailPage { emails, total, page, page_size })
+}
+
 #[tauri::command]
 pub fn classify_email(email_id: i32, classification: String) -> Result<String, String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
@@ -109,14 +430,352 @@ pub fn classify_email(email_id: i32, classification: String) -> Result<String, S
     Ok(new_classification)
 }
 
-// #[tauri::command]
-// pub fn send_email(from_user: String, to_user: String, subject: String, body: String) -> Result<(), String> {
-//     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-//     conn.exec_drop(
-//         "INSERT INTO emails (from_user, to_user, subject, body, is_read, classification) VALUES (?, ?, ?, ?, false, 'none')",
-//         (from_user, to_user, subject, body),
-//     )
-//     .map_err(|e| e.to_string())?;
-//     Ok(())
-// }
+/// Marks every labelled email the user has classified, explains each mistake and scores
+/// phishing and spam detection with precision and recall. Emails the user has not classified
+/// yet are left out, so grading cannot be used to read off the answers.
+#[tauri::command]
+pub fn grade_email_classification(user_id: i32) -> Result<ClassificationGrade, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let grades = load_email_grades(&mut conn, "ue.user_id = ? AND ue.classification <> 'none'", (user_id,))?;
+    Ok(email_grading::summarise(grades))
+}
+
+/// The filter's probabilities for every email, so students can compare its judgement
+/// with their own.
+#[tauri::command]
+pub fn get_filter_scores(user_id: i32) -> Result<Vec<FilterScore>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let filter = train_filter(&mut conn, user_id)?;
+
+    load_emails(&mut conn, "ue.user_id = ?", (user_id,))?
+        .into_iter()
+        .map(|email| {
+            let outcome = filter.classify(&spam_filter::tokenize(&email));
+            let classification = EmailLabel::from_classification(&email.classification)?;
+            Ok(FilterScore {
+                email_id: email.id,
+                filter_label: outcome.label,
+                legitimate: outcome.probabilities[&EmailLabel::Legitimate],
+                spam: outcome.probabilities[&EmailLabel::Spam],
+                phishing: outcome.probabilities[&EmailLabel::Phishing],
+                evidence: outcome.evidence,
+                classification,
+                agrees: outcome.label == classification,
+                quarantined: email.folder == MailFolder::Quarantine,
+                subject: email.subject,
+                from_user: email.from_user,
+            })
+        })
+        .collect()
+}
+
+/// Moves a wrongly quarantined email back to the inbox. The filter will not quarantine it again.
+#[tauri::command]
+pub fn release_from_quarantine(email_id: i32) -> Result<(), String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let released = conn
+        .exec_iter(
+            "UPDATE user_emails SET folder = 'inbox' WHERE id = ? AND folder = 'quarantine'",
+            (email_id,),
+        )
+        .map_err(|e| e.to_string())?
+        .affected_rows();
+    if released == 0 {
+        return Err("Email is not in quarantine".to_string());
+    }
+    Ok(())
+}
+
+/// Address users receive mail at inside Cybox.
+pub const USER_MAIL_DOMAIN: &str = "cybox.app";
+
+/// The full RFC 5322 source of an email, for header-analysis lessons.
+#[tauri::command]
+pub fn get_raw_email(email_id: i32) -> Result<String, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let email = load_email(&mut conn, email_id)?;
+    let recipient: String = conn
+        .exec_first("SELECT name FROM users WHERE id = ?", (email.user_id,))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "User not found".to_string())?;
+
+    Ok(rfc5322::render_message(&email, &format!("{}@{}", recipient, USER_MAIL_DOMAIN)))
+}
+
+/// Every link in an email with its real target and red flags, for hover-to-inspect.
+#[tauri::command]
+pub fn inspect_email_links(email_id: i32) -> Result<Vec<InspectedLink>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let email = load_email(&mut conn, email_id)?;
+    Ok(links::inspect_links(&email.body))
+}
+
+/// Adds template-generated training emails to the user's inbox, storing the ground-truth
+/// label and planted indicators alongside each one. Without a seed, the seed is derived from
+/// the user and how many emails they already have, so regenerating is reproducible.
+#[tauri::command]
+pub fn generate_training_emails(user_id: i32, count: usize, seed: Option<u64>) -> Result<usize, String> {
+    if count == 0 || count > 50 {
+        return Err("Choose between 1 and 50 emails".to_string());
+    }
+
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let recipient: String = conn
+        .exec_first("SELECT name FROM users WHERE id = ?", (user_id,))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "User not found".to_string())?;
+
+    let seed = match seed {
+        Some(seed) => seed,
+        None => {
+            let generated: u64 = conn
+                .exec_first("SELECT COUNT(*) FROM user_emails WHERE user_id = ? AND label IS NOT NULL", (user_id,))
+                .map_err(|e| e.to_string())?
+                .unwrap_or(0);
+            (user_id as u64).wrapping_mul(1_000_003).wrapping_add(generated)
+        }
+    };
+
+    let emails = email_generator::generate_emails(seed, &recipient, count);
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+    for email in &emails {
+        let indicators = serde_json::to_string(&email.indicators).map_err(|e| e.to_string())?;
+        tx.exec_drop(
+            r"INSERT INTO user_emails (user_id, from_user, subject, body, headers, label, indicators, is_read, classification)
+              VALUES (:user_id, :from_user, :subject, :body, :headers, :label, :indicators, false, 'none')",
+            params! {
+                "user_id" => user_id,
+                "from_user" => &email.from_user,
+                "subject" => &email.subject,
+                "body" => &email.body,
+                "headers" => serde_json::to_string(&email.headers).map_err(|e| e.to_string())?,
+                "label" => email.label.as_str(),
+                "indicators" => indicators,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(emails.len())
+}
 
+/// Mailboxes the game itself sends from, which no user may own or receive mail for.
+pub const RESERVED_MAILBOXES: &[&str] = &["admin", "intel", "security"];
+
+pub fn is_reserved_mailbox(name: &str) -> bool {
+    RESERVED_MAILBOXES.iter().any(|reserved| reserved.eq_ignore_ascii_case(name.trim()))
+}
+
+/// Resolves `name@cybox.app` to the user it belongs to, returning their id and canonical address.
+pub fn resolve_address<Q: Queryable>(conn: &mut Q, address: &str) -> Result<(i32, String), String> {
+    let address = address.trim();
+    let (name, domain) = address
+        .rsplit_once('@')
+        .ok_or_else(|| format!("\"{}\" is not an email address", address))?;
+    if !domain.eq_ignore_ascii_case(USER_MAIL_DOMAIN) {
+        return Err(format!("Mail can only be sent to @{} addresses", USER_MAIL_DOMAIN));
+    }
+    if is_reserved_mailbox(name) {
+        return Err(format!("{} is a system address and does not accept mail", address));
+    }
+    let user: Option<(i32, String)> = conn
+        .exec_first("SELECT id, name FROM users WHERE name = ?", (name,))
+        .map_err(|e| e.to_string())?;
+    let (id, name) = user.ok_or_else(|| format!("No Cybox user is called {}", name))?;
+    Ok((id, format!("{}@{}", name, USER_MAIL_DOMAIN)))
+}
+
+/// What a user is sending, already rendered as HTML.
+struct Outgoing<'a> {
+    subject: &'a str,
+    body: &'a str,
+    in_reply_to: Option<String>,
+    // Ground truth for recipients when the mail is a classification exercise
+    label: Option<(EmailLabel, Vec<PhishingIndicator>)>,
+    // Replies may go to outside or system senders; those are only filed in the sent folder
+    keep_unresolved: bool,
+}
+
+/// Delivers a copy to each recipient's inbox and files one in the sender's sent folder.
+/// Returns the id of the sent copy.
+fn deliver(conn: &mut mysql::PooledConn, sender_id: i32, to: &[String], outgoing: Outgoing) -> Result<i32, String> {
+    if to.is_empty() {
+        return Err("Add at least one recipient".to_string());
+    }
+    if outgoing.subject.trim().is_empty() {
+        return Err("Subject cannot be empty".to_string());
+    }
+
+    let sender: String = conn
+        .exec_first("SELECT name FROM users WHERE id = ?", (sender_id,))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "User not found".to_string())?;
+    let from_user = format!("{}@{}", sender, USER_MAIL_DOMAIN);
+
+    let mut recipients: Vec<(i32, String)> = Vec::new();
+    let mut unresolved: Vec<String> = Vec::new();
+    for address in to {
+        match resolve_address(conn, address) {
+            Ok(recipient) if !recipients.contains(&recipient) => recipients.push(recipient),
+            Ok(_) => {}
+            Err(_) if outgoing.keep_unresolved && address.contains('@') => {
+                if !unresolved.iter().any(|other| other.eq_ignore_ascii_case(address.trim())) {
+                    unresolved.push(address.trim().to_string());
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    let mut headers = EmailHeaders::authenticated(&from_user, "10.0.0.5", &format!("{:016x}", rand::random::<u64>()));
+    let addresses: Vec<&str> = recipients.iter().map(|(_, address)| address.as_str()).chain(unresolved.iter().map(String::as_str)).collect();
+    headers.to = Some(addresses.join(", "));
+    headers.in_reply_to = outgoing.in_reply_to;
+    let headers = serde_json::to_string(&headers).map_err(|e| e.to_string())?;
+    let body = html::sanitize_html(outgoing.body);
+    let (label, indicators) = match &outgoing.label {
+        Some((label, indicators)) => (
+            Some(label.as_str()),
+            Some(serde_json::to_string(indicators).map_err(|e| e.to_string())?),
+        ),
+        None => (None, None),
+    };
+
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+    for (recipient_id, _) in &recipients {
+        tx.exec_drop(
+            r"INSERT INTO user_emails (user_id, from_user, subject, body, headers, label, indicators, folder, is_read, classification)
+              VALUES (:user_id, :from_user, :subject, :body, :headers, :label, :indicators, 'inbox', false, 'none')",
+            params! {
+                "user_id" => recipient_id,
+                "from_user" => &from_user,
+                "subject" => outgoing.subject,
+                "body" => &body,
+                "headers" => &headers,
+                "label" => label,
+                "indicators" => &indicators,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    tx.exec_drop(
+        r"INSERT INTO user_emails (user_id, from_user, subject, body, headers, folder, filter_checked, is_read, classification)
+          VALUES (:user_id, :from_user, :subject, :body, :headers, 'sent', true, true, 'none')",
+        params! {
+            "user_id" => sender_id,
+            "from_user" => &from_user,
+            "subject" => outgoing.subject,
+            "body" => &body,
+            "headers" => &headers,
+        },
+    )
+    .map_err(|e| e.to_string())?;
+    let sent_id = tx.last_insert_id().ok_or_else(|| "Failed to send email".to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(sent_id as i32)
+}
+
+/// Loads one of the user's own emails, refusing anyone else's.
+pub fn load_own_email<Q: Queryable>(conn: &mut Q, user_id: i32, email_id: i32) -> Result<Email, String> {
+    let email = load_email(conn, email_id)?;
+    if email.user_id != user_id {
+        return Err("Email not found".to_string());
+    }
+    Ok(email)
+}
+
+pub fn prefixed_subject(prefix: &str, subject: &str) -> String {
+    if subject.to_lowercase().starts_with(&prefix.to_lowercase()) {
+        subject.to_string()
+    } else {
+        format!("{} {}", prefix, subject)
+    }
+}
+
+/// Sends mail to other Cybox users. Setting `exercise_label` turns the message into a
+/// classification exercise that recipients are graded on.
+#[tauri::command]
+pub fn send_email(user_id: i32, to: Vec<String>, subject: String, body: String, exercise_label: Option<String>) -> Result<i32, String> {
+    let label = exercise_label.map(|label| EmailLabel::parse(&label)).transpose()?;
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    deliver(
+        &mut conn,
+        user_id,
+        &to,
+        Outgoing {
+            subject: &subject,
+            body: &body,
+            in_reply_to: None,
+            label: label.map(|label| (label, Vec::new())),
+            keep_unresolved: false,
+        },
+    )
+}
+
+/// Replies to the sender, quoting the original. Replying from the sent folder goes to the
+/// original recipients instead. Replies to senders outside Cybox are only kept in the sent folder.
+#[tauri::command]
+pub fn reply_to_email(user_id: i32, email_id: i32, body: String) -> Result<i32, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let original = load_own_email(&mut conn, user_id, email_id)?;
+
+    let to: Vec<String> = match (original.folder, &original.headers.to) {
+        (MailFolder::Sent, Some(to)) => to.split(',').map(|address| address.trim().to_string()).collect(),
+        _ => vec![original.from_user.clone()],
+    };
+    let quoted = format!(
+        "{}<blockquote>On {}, {} wrote:<br>{}</blockquote>",
+        body,
+        original.created_at.format("%d %b %Y %H:%M"),
+        html::escape(&original.from_user),
+        original.body
+    );
+
+    deliver(
+        &mut conn,
+        user_id,
+        &to,
+        Outgoing {
+            subject: &prefixed_subject("Re:", &original.subject),
+            body: &quoted,
+            in_reply_to: original.headers.message_id.clone(),
+            label: None,
+            keep_unresolved: true,
+        },
+    )
+}
+
+/// Forwards an email with an optional note. The forwarded copy keeps the original's ground
+/// truth, so forwarding a phishing email to a classmate makes a ready-made exercise.
+#[tauri::command]
+pub fn forward_email(user_id: i32, email_id: i32, to: Vec<String>, note: Option<String>) -> Result<i32, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let original = load_own_email(&mut conn, user_id, email_id)?;
+    let label = load_email_grades(&mut conn, "ue.id = ?", (email_id,))?
+        .pop()
+        .map(|grade| (grade.label, grade.indicators));
+
+    let body = format!(
+        "{}<p>---------- Forwarded message ----------<br>From: {}<br>Date: {}<br>Subject: {}</p>{}",
+        note.map(|note| format!("<p>{}</p>", html::escape(&note))).unwrap_or_default(),
+        html::escape(&original.from_user),
+        original.created_at.format("%d %b %Y %H:%M"),
+        html::escape(&original.subject),
+        original.body
+    );
+
+    deliver(
+        &mut conn,
+        user_id,
+        &to,
+        Outgoing {
+            subject: &prefixed_subject("Fwd:", &original.subject),
+            body: &body,
+            in_reply_to: None,
+            label,
+            keep_unresolved: false,
+        },
+    )
+}
@@ -0,0 +1,164 @@
+use crate::models::mail::DeliveryTrigger;
+use chrono::{Duration, NaiveDateTime};
+use mysql::{params, prelude::*, PooledConn};
+
+struct DeliveryRule {
+    id: i32,
+    universal_email_id: i32,
+    trigger: DeliveryTrigger,
+    delay_seconds: i64,
+}
+
+// Column names are spliced into SQL, so only plain identifiers get through
+fn setting_column(setting: &str) -> Result<&str, String> {
+    if !setting.is_empty() && setting.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+        Ok(setting)
+    } else {
+        Err(format!("Invalid setting in delivery rule: {}", setting))
+    }
+}
+
+/// When the trigger happened for this user, or `None` if it has not happened yet.
+fn fired_at(conn: &mut PooledConn, user_id: i32, trigger: &DeliveryTrigger) -> Result<Option<NaiveDateTime>, String> {
+    let fired: Option<Option<NaiveDateTime>> = match trigger {
+        DeliveryTrigger::AccountCreated => conn
+            .exec_first("SELECT created_at FROM users WHERE id = ?", (user_id,))
+            .map_err(|e| e.to_string())?,
+        DeliveryTrigger::TaskStarted { task_id } => {
+            // A task starts when its last prerequisite is completed, or with the account if it has none
+            let row: Option<(i64, Option<NaiveDateTime>)> = conn
+                .exec_first(
+                    r"SELECT
+                          COUNT(*) - COUNT(CASE WHEN ut.status = 'Completed' THEN 1 END),
+                          COALESCE(MAX(ut.completed_at), (SELECT created_at FROM users WHERE id = :user_id))
+                      FROM task_prerequisites p
+                      LEFT JOIN user_tasks ut ON ut.universal_task_id = p.prerequisite_id AND ut.user_id = :user_id
+                      WHERE p.task_id = :task_id",
+                    params! { "user_id" => user_id, "task_id" => task_id },
+                )
+                .map_err(|e| e.to_string())?;
+            row.and_then(|(outstanding, started_at)| (outstanding == 0).then_some(started_at))
+        }
+        DeliveryTrigger::TaskCompleted { task_id } => conn
+            .exec_first(
+                "SELECT completed_at FROM user_tasks WHERE user_id = ? AND universal_task_id = ? AND status = 'Completed'",
+                (user_id, task_id),
+            )
+            .map_err(|e| e.to_string())?,
+        DeliveryTrigger::ServicePurchased { service_id } => conn
+            .exec_first(
+                "SELECT MIN(purchased_at) FROM service_purchases WHERE user_id = ? AND service_id = ? AND refunded_at IS NULL",
+                (user_id, service_id),
+            )
+            .map_err(|e| e.to_string())?,
+        // Settings keep no history, so the change counts from when the dispatcher first sees it
+        DeliveryTrigger::SettingChanged { setting, value } => conn
+            .exec_first(
+                format!(
+                    "SELECT NOW() FROM user_settings WHERE user_id = ? AND CAST({} AS CHAR) = ?",
+                    setting_column(setting)?
+                ),
+                (user_id, super::effects::setting_value(value)),
+            )
+            .map_err(|e| e.to_string())?,
+    };
+    Ok(fired.flatten())
+}
+
+/// Schedules the universal emails whose rules have fired and delivers those that are due.
+/// Returns how many emails landed in the inbox.
+pub fn dispatch_emails(conn: &mut PooledConn, user_id: i32) -> Result<usize, String> {
+    // Emails without rules are due as soon as the user first looks
+    conn.exec_drop(
+        r"INSERT INTO email_deliveries (user_id, universal_email_id, deliver_at)
+          SELECT :user_id, ue.id, NOW()
+          FROM universal_emails ue
+          WHERE NOT EXISTS (SELECT 1 FROM email_delivery_rules r WHERE r.universal_email_id = ue.id)
+            AND NOT EXISTS (SELECT 1 FROM email_deliveries d WHERE d.user_id = :user_id AND d.universal_email_id = ue.id)",
+        params! { "user_id" => user_id },
+    )
+    .map_err(|e| e.to_string())?;
+
+    let rules: Vec<DeliveryRule> = conn
+        .exec::<(i32, i32, String, i64), _, _>(
+            r"SELECT r.id, r.universal_email_id, r.delivery_trigger, r.delay_seconds
+              FROM email_delivery_rules r
+              WHERE NOT EXISTS (
+                  SELECT 1 FROM email_deliveries d WHERE d.user_id = :user_id AND d.universal_email_id = r.universal_email_id
+              )
+              ORDER BY r.universal_email_id, r.id",
+            params! { "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|(id, universal_email_id, trigger, delay_seconds)| {
+            let trigger = serde_json::from_str(&trigger).map_err(|e| format!("Invalid delivery rule {}: {}", id, e))?;
+            Ok(DeliveryRule { id, universal_email_id, trigger, delay_seconds })
+        })
+        .collect::<Result<_, String>>()?;
+
+    // The earliest fired rule decides when each email is delivered
+    let mut scheduled: Vec<(i32, i32, NaiveDateTime)> = Vec::new();
+    for rule in rules {
+        let Some(fired_at) = fired_at(conn, user_id, &rule.trigger)? else {
+            continue;
+        };
+        let deliver_at = fired_at + Duration::seconds(rule.delay_seconds);
+        match scheduled.iter_mut().find(|(_, email_id, _)| *email_id == rule.universal_email_id) {
+            Some(entry) if deliver_at < entry.2 => *entry = (rule.id, rule.universal_email_id, deliver_at),
+            Some(_) => {}
+            None => scheduled.push((rule.id, rule.universal_email_id, deliver_at)),
+        }
+    }
+    for (rule_id, universal_email_id, deliver_at) in scheduled {
+        conn.exec_drop(
+            r"INSERT IGNORE INTO email_deliveries (user_id, universal_email_id, rule_id, deliver_at)
+              VALUES (:user_id, :universal_email_id, :rule_id, :deliver_at)",
+            params! {
+                "user_id" => user_id,
+                "universal_email_id" => universal_email_id,
+                "rule_id" => rule_id,
+                "deliver_at" => deliver_at,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    let due: Vec<i32> = conn
+        .exec(
+            r"SELECT universal_email_id FROM email_deliveries
+              WHERE user_id = :user_id AND delivered_at IS NULL AND deliver_at <= NOW()
+              ORDER BY deliver_at, universal_email_id",
+            params! { "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let mut delivered = 0;
+    for universal_email_id in due {
+        let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+
+        // Claim the row first so a concurrent poll cannot deliver it twice
+        tx.exec_drop(
+            r"UPDATE email_deliveries SET delivered_at = NOW()
+              WHERE user_id = :user_id AND universal_email_id = :universal_email_id AND delivered_at IS NULL",
+            params! { "user_id" => user_id, "universal_email_id" => universal_email_id },
+        )
+        .map_err(|e| e.to_string())?;
+        if tx.affected_rows() == 0 {
+            tx.rollback().map_err(|e| e.to_string())?;
+            continue;
+        }
+
+        // Users from before delivery rules may already hold a copy
+        tx.exec_drop(
+            r"INSERT IGNORE INTO user_emails (user_id, universal_email_id, is_read, classification)
+              VALUES (:user_id, :universal_email_id, false, 'none')",
+            params! { "user_id" => user_id, "universal_email_id" => universal_email_id },
+        )
+        .map_err(|e| e.to_string())?;
+        delivered += tx.affected_rows() as usize;
+        tx.commit().map_err(|e| e.to_string())?;
+    }
+
+    Ok(delivered)
+}
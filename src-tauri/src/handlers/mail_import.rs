@@ -0,0 +1,109 @@
+use crate::db;
+use crate::models::mail::{EmailLabel, ImportSummary, PhishingIndicator};
+use crate::utils::html;
+use crate::utils::mime::{self, ParsedMessage};
+use crate::utils::redact::Redactor;
+use mysql::{params, prelude::*};
+use tauri::command;
+
+/// The bare address from a `From` value such as `"Alice" <alice@example.com>`.
+fn bare_address(from: &str) -> String {
+    match (from.rfind('<'), from.rfind('>')) {
+        (Some(start), Some(end)) if start < end => from[start + 1..end].trim().to_string(),
+        _ => from.trim().to_string(),
+    }
+}
+
+fn redact_message(redactor: &mut Redactor, message: &mut ParsedMessage) {
+    message.from_user = redactor.redact(&message.from_user);
+    message.subject = redactor.redact(&message.subject);
+    message.body = redactor.redact(&message.body);
+
+    let headers = &mut message.headers;
+    headers.to = redactor.redact_option(&headers.to);
+    headers.reply_to = redactor.redact_option(&headers.reply_to);
+    headers.return_path = redactor.redact_option(&headers.return_path);
+    headers.message_id = redactor.redact_option(&headers.message_id);
+    headers.in_reply_to = redactor.redact_option(&headers.in_reply_to);
+    for hop in &mut headers.received {
+        hop.from_host = redactor.redact_host(&hop.from_host);
+        hop.by_host = redactor.redact_host(&hop.by_host);
+    }
+}
+
+/// Imports a .eml file or an mbox archive from disk into the shared inbox, so every user
+/// receives the samples. `label` and `indicators` become the ground truth for grading;
+/// with `redact` set, real addresses and domains are swapped for placeholders first.
+/// Only instructors may import.
+#[command]
+pub fn import_emails(
+    instructor_id: i32,
+    path: String,
+    label: Option<String>,
+    indicators: Option<Vec<PhishingIndicator>>,
+    redact: bool,
+) -> Result<ImportSummary, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    super::account::require_instructor(&mut conn, instructor_id)?;
+    let label = label.map(|label| EmailLabel::parse(&label)).transpose()?;
+    let indicators = serde_json::to_string(&indicators.unwrap_or_default()).map_err(|e| e.to_string())?;
+    let raw = std::fs::read(&path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+    let sources = if mime::is_mbox(&raw) { mime::split_mbox(&raw) } else { vec![raw] };
+
+    let mut redactor = Redactor::default();
+    let mut messages = Vec::new();
+    let mut skipped = Vec::new();
+    for (index, source) in sources.iter().enumerate() {
+        match mime::parse_message(source) {
+            Ok(mut message) => {
+                message.from_user = bare_address(&message.from_user);
+                if redact {
+                    redact_message(&mut redactor, &mut message);
+                }
+                messages.push(message);
+            }
+            Err(e) => skipped.push(format!("Message {}: {}", index + 1, e)),
+        }
+    }
+
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+    let mut imported = Vec::new();
+    let mut attachments = 0;
+    for message in &messages {
+        tx.exec_drop(
+            r"INSERT INTO universal_emails (from_user, subject, body, headers, label, indicators, created_at)
+              VALUES (:from_user, :subject, :body, :headers, :label, :indicators, COALESCE(:created_at, NOW()))",
+            params! {
+                "from_user" => &message.from_user,
+                "subject" => &message.subject,
+                "body" => html::sanitize_html(&message.body),
+                "headers" => serde_json::to_string(&message.headers).map_err(|e| e.to_string())?,
+                "label" => label.map(|label| label.as_str()),
+                "indicators" => &indicators,
+                "created_at" => message.date,
+            },
+        )
+        .map_err(|e| e.to_string())?;
+        let email_id = tx.last_insert_id().ok_or_else(|| "Failed to import email".to_string())? as i32;
+
+        for attachment in &message.attachments {
+            tx.exec_drop(
+                r"INSERT INTO email_attachments (universal_email_id, file_name, mime_type, size_bytes, content)
+                  VALUES (:email_id, :file_name, :mime_type, :size_bytes, :content)",
+                params! {
+                    "email_id" => email_id,
+                    "file_name" => &attachment.file_name,
+                    "mime_type" => &attachment.mime_type,
+                    "size_bytes" => attachment.content.len(),
+                    "content" => &attachment.content,
+                },
+            )
+            .map_err(|e| e.to_string())?;
+            attachments += 1;
+        }
+        imported.push(email_id);
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+
+    Ok(ImportSummary { imported, attachments, skipped })
+}
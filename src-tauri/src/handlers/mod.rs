@@ -6,4 +6,15 @@ pub mod task;
 pub mod bank;
 pub mod settings;
 pub mod fs;
-pub mod firewall;
\ No newline at end of file
+pub mod firewall;
+pub mod entitlement;
+pub mod cracker;
+pub mod effects;
+pub mod attachment;
+pub mod mail_import;
+pub mod mail_delivery;
+pub mod email_report;
+pub mod campaign;
+pub mod link;
+pub mod task_verifier;
+pub mod flags;
\ No newline at end of file
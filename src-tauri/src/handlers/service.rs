@@ -1,60 +1,199 @@
 use crate::db;
-use crate::models::service::Service;
+use crate::models::service::{
+    PriceQuote, RefundPolicy, RefundQuote, RiskRating, Service, ServiceBundle, ServiceCategory, ServiceQuery, ServiceSort,
+    ServiceStatus, ServiceUpdate, ServiceVersion, UserServiceState,
+};
+use crate::utils::pricing::{self, AppliedCoupon, Pricing};
 use mysql::{prelude::*, params};
 use tauri::command;
-use chrono::NaiveDate;
+use rust_decimal::prelude::ToPrimitive;
 use rust_decimal::Decimal;
 
+pub const SERVICE_COLUMNS: &str = "id, name, description, price, category, features, permissions, risk_rating, icon_path, version, developer, release_date, is_important";
+
+/// Parses the JSON and enum columns of a catalog row and validates the result.
+/// Taken from a `Row` since the column count exceeds the tuple `FromRow` impls.
+pub fn parse_service(mut row: mysql::Row) -> Result<Service, String> {
+    let name: String = db::column(&mut row, "name")?;
+    let context = |e: String| format!("Invalid service '{}': {}", name, e);
+
+    let category: String = db::column(&mut row, "category")?;
+    let features: Option<String> = db::column(&mut row, "features")?;
+    let permissions: Option<String> = db::column(&mut row, "permissions")?;
+    let risk_rating: String = db::column(&mut row, "risk_rating")?;
+
+    let service = Service {
+        id: db::column(&mut row, "id")?,
+        description: db::column(&mut row, "description")?,
+        price: db::column(&mut row, "price")?,
+        category: ServiceCategory::parse(&category).map_err(context)?,
+        features: serde_json::from_str(features.as_deref().unwrap_or("[]")).map_err(|e| context(format!("features: {}", e)))?,
+        permissions: serde_json::from_str(permissions.as_deref().unwrap_or("[]")).map_err(|e| context(format!("permissions: {}", e)))?,
+        risk_rating: RiskRating::parse(&risk_rating).map_err(context)?,
+        icon_path: db::column(&mut row, "icon_path")?,
+        version: db::column(&mut row, "version")?,
+        developer: db::column(&mut row, "developer")?,
+        release_date: db::column(&mut row, "release_date")?,
+        is_important: db::column(&mut row, "is_important")?,
+        name,
+    };
+    service.validate()?;
+    Ok(service)
+}
+
 #[command]
 pub fn get_all_services() -> Result<Vec<Service>, String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-    let query = "SELECT id, name, description, price, category, features, icon_path, version, developer, release_date, is_important FROM services";
+    let query = format!("SELECT {} FROM services", SERVICE_COLUMNS);
 
-    let services = conn
-        .exec_map(
-            query,
-            (),
-            |(
-                id,
-                name,
-                description,
-                price,
-                category,
-                features,
-                icon_path,
-                version,
-                developer,
-                release_date,
-                is_important,
-            ): (
-                i32,
-                String,
-                Option<String>,
-                Option<i32>,
-                Option<String>,
-                Option<String>,
-                Option<String>,
-                Option<String>,
-                Option<String>,
-                Option<NaiveDate>,
-                Option<bool>,
-            )| Service {
-                id,
-                name,
-                description,
-                price,
-                category,
-                features,
-                icon_path,
-                version,
-                developer,
-                release_date,
-                is_important,
-            },
-        )
+    let rows: Vec<mysql::Row> = conn.query(query).map_err(|e| e.to_string())?;
+    rows.into_iter().map(parse_service).collect()
+}
+
+#[command]
+pub fn get_service(service_id: i32) -> Result<Service, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let query = format!("SELECT {} FROM services WHERE id = :service_id", SERVICE_COLUMNS);
+
+    let row: Option<mysql::Row> = conn
+        .exec_first(query, params! { "service_id" => service_id })
         .map_err(|e| e.to_string())?;
+    parse_service(row.ok_or_else(|| "Service not found".to_string())?)
+}
+
+/// Turns free text into a boolean-mode FULLTEXT query where every word must match as a prefix.
+pub fn fulltext_terms(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.chars().filter(|c| c.is_alphanumeric()).collect::<String>())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("+{}*", word))
+        .collect();
+    if terms.is_empty() { None } else { Some(terms.join(" ")) }
+}
+
+#[command]
+pub fn search_services(query: ServiceQuery) -> Result<Vec<Service>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+
+    let mut conditions: Vec<String> = Vec::new();
+    let mut params_vec: Vec<mysql::Value> = Vec::new();
+    let mut relevance = "0".to_string();
+
+    if let Some(terms) = query.text.as_deref().and_then(fulltext_terms) {
+        conditions.push("MATCH(name, description, developer) AGAINST (? IN BOOLEAN MODE)".to_string());
+        relevance = "MATCH(name, description, developer) AGAINST (? IN BOOLEAN MODE)".to_string();
+        // The relevance expression comes first in the SELECT list, so its parameter does too
+        params_vec.insert(0, terms.clone().into());
+        params_vec.push(terms.into());
+    }
+
+    if !query.categories.is_empty() {
+        let placeholders = vec!["?"; query.categories.len()].join(", ");
+        conditions.push(format!("category IN ({})", placeholders));
+        params_vec.extend(query.categories.iter().map(|c| c.as_str().into()));
+    }
+
+    if let Some(min_price) = query.min_price {
+        conditions.push("price >= ?".to_string());
+        params_vec.push(min_price.into());
+    }
+    if let Some(max_price) = query.max_price {
+        conditions.push("price <= ?".to_string());
+        params_vec.push(max_price.into());
+    }
+
+    if let Some(owned) = query.owned {
+        let user_id = query.user_id.ok_or_else(|| "Filtering by ownership needs a user".to_string())?;
+        let negate = if owned { "" } else { "NOT " };
+        conditions.push(format!("{}EXISTS (SELECT 1 FROM user_services us WHERE us.service_id = services.id AND us.user_id = ?)", negate));
+        params_vec.push(user_id.into());
+    }
 
-    Ok(services)
+    let sort_by = query.sort_by.unwrap_or(if query.text.is_some() { ServiceSort::Relevance } else { ServiceSort::Name });
+    let order_column = match sort_by {
+        ServiceSort::Relevance => "relevance",
+        ServiceSort::Name => "name",
+        ServiceSort::Price => "price",
+        ServiceSort::ReleaseDate => "release_date",
+        ServiceSort::Risk => "FIELD(risk_rating, 'low', 'medium', 'high', 'critical')",
+    };
+    // Relevance reads naturally best-first; everything else ascending unless asked otherwise
+    let descending = query.descending ^ (sort_by == ServiceSort::Relevance);
+    let direction = if descending { "DESC" } else { "ASC" };
+
+    let where_clause = if conditions.is_empty() { String::new() } else { format!("WHERE {}", conditions.join(" AND ")) };
+    let sql = format!(
+        "SELECT {}, {} AS relevance FROM services {} ORDER BY {} {}, name ASC",
+        SERVICE_COLUMNS, relevance, where_clause, order_column, direction
+    );
+
+    let rows: Vec<mysql::Row> = conn.exec(sql, params_vec).map_err(|e| e.to_string())?;
+    rows.into_iter().map(parse_service).collect()
+}
+
+#[command]
+pub fn get_service_changelog(service_id: i32) -> Result<Vec<ServiceVersion>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    conn.exec_map(
+        "SELECT service_id, version, changelog, released_at FROM service_versions WHERE service_id = :service_id ORDER BY released_at DESC",
+        params! { "service_id" => service_id },
+        |(service_id, version, changelog, released_at)| ServiceVersion { service_id, version, changelog, released_at },
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Installed services whose catalog version has moved on, with the changelog entries since.
+#[command]
+pub fn get_available_updates(user_id: i32) -> Result<Vec<ServiceUpdate>, String> {
+    let states = get_service_states(user_id)?;
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+
+    let mut updates = Vec::new();
+    for state in states.into_iter().filter(|s| s.update_available) {
+        let changelog: Vec<ServiceVersion> = conn
+            .exec_map(
+                r"SELECT sv.service_id, sv.version, sv.changelog, sv.released_at
+                  FROM service_versions sv
+                  LEFT JOIN service_versions installed
+                    ON installed.service_id = sv.service_id AND installed.version = :installed_version
+                  WHERE sv.service_id = :service_id
+                    AND (installed.released_at IS NULL OR sv.released_at > installed.released_at)
+                  ORDER BY sv.released_at DESC",
+                params! { "service_id" => state.service_id, "installed_version" => &state.installed_version },
+                |(service_id, version, changelog, released_at)| ServiceVersion { service_id, version, changelog, released_at },
+            )
+            .map_err(|e| e.to_string())?;
+
+        updates.push(ServiceUpdate {
+            service_id: state.service_id,
+            name: state.name,
+            installed_version: state.installed_version,
+            latest_version: state.latest_version.unwrap_or_default(),
+            changelog,
+        });
+    }
+
+    Ok(updates)
+}
+
+#[command]
+pub fn apply_service_update(user_id: i32, service_id: i32) -> Result<UserServiceState, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let state = get_service_state(&mut conn, user_id, service_id)?;
+    if !state.update_available {
+        return Err(format!("{} is already up to date", state.name));
+    }
+
+    conn.exec_drop(
+        r"UPDATE user_services us JOIN services s ON s.id = us.service_id
+          SET us.installed_version = s.version
+          WHERE us.user_id = :user_id AND us.service_id = :service_id",
+        params! { "user_id" => user_id, "service_id" => service_id },
+    )
+    .map_err(|e| e.to_string())?;
+
+    get_service_state(&mut conn, user_id, service_id)
 }
 
 #[command]
@@ -69,51 +208,655 @@ pub fn get_user_services(user_id: i32) -> Result<Vec<i32>, String> {
     Ok(service_ids)
 }
 
-#[command]
-pub fn purchase_service(user_id: i32, service_id: i32, price: Decimal) -> Result<(), String> {
-    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+/// Best active sale for a service, as (sale name, percent off).
+fn find_active_sale<Q: Queryable>(conn: &mut Q, service_id: i32) -> Result<Option<(String, i32)>, String> {
+    conn.exec_first(
+        r"SELECT se.name, se.percent_off
+          FROM sale_events se
+          JOIN services s ON s.id = :service_id
+          WHERE (se.service_id IS NULL OR se.service_id = s.id)
+            AND (se.category IS NULL OR se.category = s.category)
+            AND (se.starts_at IS NULL OR se.starts_at <= NOW())
+            AND (se.ends_at IS NULL OR se.ends_at > NOW())
+          ORDER BY se.percent_off DESC
+          LIMIT 1",
+        params! { "service_id" => service_id },
+    )
+    .map_err(|e| e.to_string())
+}
+
+type CouponRow = (i32, String, Option<i32>, Option<i32>, Option<i32>, Option<i32>, i32, i32, bool);
+
+/// Validates a coupon code for this user and returns the discount it gives on `amount`.
+/// `service_ids` are the services being bought; a service-specific coupon must match one of them.
+fn find_coupon<Q: Queryable>(
+    conn: &mut Q,
+    user_id: i32,
+    code: &str,
+    service_ids: &[i32],
+    amount: Decimal,
+) -> Result<AppliedCoupon, String> {
+    let coupon: Option<CouponRow> = conn
+        .exec_first(
+            r"SELECT id, code, percent_off, amount_off, service_id, max_uses, max_uses_per_user, uses,
+                     (valid_from IS NULL OR valid_from <= NOW()) AND (valid_until IS NULL OR valid_until > NOW())
+              FROM coupons WHERE code = :code",
+            params! { "code" => code.trim().to_uppercase() },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let (id, code, percent_off, amount_off, service_id, max_uses, max_uses_per_user, uses, is_active) =
+        coupon.ok_or_else(|| "Invalid coupon code".to_string())?;
+
+    if !is_active {
+        return Err("This coupon has expired".to_string());
+    }
+    if max_uses.is_some_and(|max| uses >= max) {
+        return Err("This coupon has reached its usage limit".to_string());
+    }
+    if service_id.is_some_and(|required| !service_ids.contains(&required)) {
+        return Err("This coupon does not apply to this purchase".to_string());
+    }
+
+    let user_uses: i32 = conn
+        .exec_first(
+            "SELECT COUNT(*) FROM coupon_redemptions WHERE coupon_id = :coupon_id AND user_id = :user_id",
+            params! { "coupon_id" => id, "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+    if user_uses >= max_uses_per_user {
+        return Err("You have already used this coupon".to_string());
+    }
+
+    Ok(AppliedCoupon { id, code, discount: pricing::coupon_discount(amount, percent_off, amount_off) })
+}
+
+/// Sale is applied to the catalog price first, then the coupon to what remains.
+fn price_service<Q: Queryable>(
+    conn: &mut Q,
+    user_id: i32,
+    service_id: i32,
+    coupon_code: Option<&str>,
+) -> Result<Pricing, String> {
+    let price: Option<i32> = conn
+        .exec_first(
+            "SELECT price FROM services WHERE id = :service_id",
+            params! { "service_id" => service_id },
+        )
+        .map_err(|e| e.to_string())?;
+    let base_price = Decimal::from(price.ok_or_else(|| "Service not found".to_string())?);
+
+    let sale = find_active_sale(conn, service_id)?;
+    let sale_discount = sale.as_ref().map_or(Decimal::ZERO, |(_, percent)| pricing::percent_of(base_price, *percent));
+
+    let coupon = match coupon_code.filter(|code| !code.trim().is_empty()) {
+        Some(code) => Some(find_coupon(conn, user_id, code, &[service_id], base_price - sale_discount)?),
+        None => None,
+    };
+
+    Ok(Pricing { base_price, sale_discount, sale_name: sale.map(|(name, _)| name), coupon })
+}
+
+/// Bundles are already discounted, so only coupons apply to them.
+/// Returns the pricing and each service in the bundle with its catalog price.
+fn price_bundle<Q: Queryable>(
+    conn: &mut Q,
+    user_id: i32,
+    bundle_id: i32,
+    coupon_code: Option<&str>,
+) -> Result<(Pricing, Vec<(i32, Decimal)>), String> {
+    let price: Option<i32> = conn
+        .exec_first(
+            "SELECT price FROM service_bundles WHERE id = :bundle_id",
+            params! { "bundle_id" => bundle_id },
+        )
+        .map_err(|e| e.to_string())?;
+    let bundle_price = Decimal::from(price.ok_or_else(|| "Bundle not found".to_string())?);
+
+    let items: Vec<(i32, Decimal)> = conn
+        .exec_map(
+            r"SELECT s.id, s.price
+              FROM service_bundle_items bi
+              JOIN services s ON s.id = bi.service_id
+              WHERE bi.bundle_id = :bundle_id
+              ORDER BY s.id",
+            params! { "bundle_id" => bundle_id },
+            |(id, price): (i32, i32)| (id, Decimal::from(price)),
+        )
+        .map_err(|e| e.to_string())?;
+    if items.is_empty() {
+        return Err("Bundle has no services".to_string());
+    }
+
+    let service_ids: Vec<i32> = items.iter().map(|(id, _)| *id).collect();
+    let coupon = match coupon_code.filter(|code| !code.trim().is_empty()) {
+        Some(code) => Some(find_coupon(conn, user_id, code, &service_ids, bundle_price)?),
+        None => None,
+    };
+
+    let catalog_prices: Vec<Decimal> = items.iter().map(|(_, price)| *price).collect();
+    Ok((Pricing::bundle(&catalog_prices, bundle_price, coupon), items))
+}
 
-    // Get balance and service name
+fn redeem_coupon(tx: &mut mysql::Transaction, user_id: i32, coupon: &AppliedCoupon) -> Result<(), String> {
+    // Guarded update so two concurrent purchases cannot both take the last use
+    tx.exec_drop(
+        "UPDATE coupons SET uses = uses + 1 WHERE id = :id AND (max_uses IS NULL OR uses < max_uses)",
+        params! { "id" => coupon.id },
+    )
+    .map_err(|e| e.to_string())?;
+    if tx.affected_rows() == 0 {
+        return Err("This coupon has reached its usage limit".to_string());
+    }
+    tx.exec_drop(
+        "INSERT INTO coupon_redemptions (coupon_id, user_id) VALUES (:coupon_id, :user_id)",
+        params! { "coupon_id" => coupon.id, "user_id" => user_id },
+    )
+    .map_err(|e| e.to_string())
+}
+
+/// Locks the user's bank account, debits `price` and records the transaction. Returns the transaction id.
+fn charge(tx: &mut mysql::Transaction, user_id: i32, description: String, pricing: &Pricing) -> Result<u64, String> {
+    let price = pricing.final_price();
     let balance: Option<Decimal> = tx.exec_first(
         "SELECT balance FROM bank_accounts WHERE user_id = :user_id FOR UPDATE",
         params! { "user_id" => user_id }
     ).map_err(|e| e.to_string())?;
-    
-    let service_name: Option<String> = tx.exec_first(
-        "SELECT name FROM services WHERE id = :service_id",
-        params! { "service_id" => service_id }
-    ).map_err(|e| e.to_string())?;
-
     let balance = balance.ok_or_else(|| "Bank account not found".to_string())?;
-    let service_name = service_name.ok_or_else(|| "Service not found".to_string())?;
 
     if balance < price {
-        tx.rollback().map_err(|e| e.to_string())?;
         return Err("Not enough points".to_string());
     }
 
-    // Deduct price from balance
     tx.exec_drop(
         "UPDATE bank_accounts SET balance = balance - :price WHERE user_id = :user_id",
         params! { "price" => price, "user_id" => user_id }
     ).map_err(|e| e.to_string())?;
 
-    // Add service to user
+    tx.exec_drop(
+        r"INSERT INTO bank_transactions (user_id, description, amount, discount, discount_source)
+          VALUES (:user_id, :description, :amount, :discount, :discount_source)",
+        params! {
+            "user_id" => user_id,
+            "description" => description,
+            "amount" => -price,
+            "discount" => pricing.discount(),
+            "discount_source" => pricing.discount_source(),
+        }
+    ).map_err(|e| e.to_string())?;
+
+    tx.last_insert_id().ok_or_else(|| "Failed to record transaction".to_string())
+}
+
+fn grant_service(
+    tx: &mut mysql::Transaction,
+    user_id: i32,
+    service_id: i32,
+    bundle_id: Option<i32>,
+    transaction_id: u64,
+    base_price: Decimal,
+    price_paid: Decimal,
+) -> Result<(), String> {
     tx.exec_drop(
         "INSERT IGNORE INTO user_services (user_id, service_id) VALUES (:user_id, :service_id)",
         params! { "user_id" => user_id, "service_id" => service_id }
     ).map_err(|e| e.to_string())?;
 
-    // Record transaction
-    let description = format!("Purchase: {}", service_name);
-    let amount = -price;
     tx.exec_drop(
-        "INSERT INTO bank_transactions (user_id, description, amount) VALUES (:user_id, :description, :amount)",
-        params! { "user_id" => user_id, "description" => description, "amount" => amount }
+        r"INSERT INTO service_purchases (user_id, service_id, bundle_id, transaction_id, base_price, discount, price_paid)
+          VALUES (:user_id, :service_id, :bundle_id, :transaction_id, :base_price, :discount, :price_paid)",
+        params! {
+            "user_id" => user_id,
+            "service_id" => service_id,
+            "bundle_id" => bundle_id,
+            "transaction_id" => transaction_id,
+            "base_price" => base_price,
+            "discount" => base_price - price_paid,
+            "price_paid" => price_paid,
+        }
+    ).map_err(|e| e.to_string())
+}
+
+fn owns_service<Q: Queryable>(conn: &mut Q, user_id: i32, service_id: i32) -> Result<bool, String> {
+    let count: u64 = conn
+        .exec_first(
+            "SELECT COUNT(*) FROM user_services WHERE user_id = :user_id AND service_id = :service_id",
+            params! { "user_id" => user_id, "service_id" => service_id },
+        )
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+    Ok(count > 0)
+}
+
+#[command]
+pub fn quote_service_price(user_id: i32, service_id: i32, coupon_code: Option<String>) -> Result<PriceQuote, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let pricing = price_service(&mut conn, user_id, service_id, coupon_code.as_deref())?;
+    Ok(pricing.to_quote(Some(service_id), None))
+}
+
+#[command]
+pub fn purchase_service(user_id: i32, service_id: i32, coupon_code: Option<String>) -> Result<PriceQuote, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+
+    let result = (|| {
+        if owns_service(&mut tx, user_id, service_id)? {
+            return Err("You already own this service".to_string());
+        }
+
+        let pricing = price_service(&mut tx, user_id, service_id, coupon_code.as_deref())?;
+        let service_name: String = tx.exec_first(
+            "SELECT name FROM services WHERE id = :service_id",
+            params! { "service_id" => service_id }
+        ).map_err(|e| e.to_string())?.ok_or_else(|| "Service not found".to_string())?;
+
+        if let Some(coupon) = &pricing.coupon {
+            redeem_coupon(&mut tx, user_id, coupon)?;
+        }
+        let transaction_id = charge(&mut tx, user_id, format!("Purchase: {}", service_name), &pricing)?;
+        grant_service(&mut tx, user_id, service_id, None, transaction_id, pricing.base_price, pricing.final_price())?;
+
+        Ok(pricing.to_quote(Some(service_id), None))
+    })();
+
+    match result {
+        Ok(quote) => {
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(quote)
+        }
+        Err(e) => {
+            tx.rollback().map_err(|e| e.to_string())?;
+            Err(e)
+        }
+    }
+}
+
+#[command]
+pub fn get_bundles() -> Result<Vec<ServiceBundle>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+
+    let mut bundles: Vec<ServiceBundle> = conn
+        .query_map(
+            "SELECT id, name, description, price FROM service_bundles ORDER BY id",
+            |(id, name, description, price)| ServiceBundle { id, name, description, price, service_ids: Vec::new() },
+        )
+        .map_err(|e| e.to_string())?;
+
+    let items: Vec<(i32, i32)> = conn
+        .query("SELECT bundle_id, service_id FROM service_bundle_items")
+        .map_err(|e| e.to_string())?;
+    for (bundle_id, service_id) in items {
+        if let Some(bundle) = bundles.iter_mut().find(|b| b.id == bundle_id) {
+            bundle.service_ids.push(service_id);
+        }
+    }
+
+    Ok(bundles)
+}
+
+#[command]
+pub fn quote_bundle_price(user_id: i32, bundle_id: i32, coupon_code: Option<String>) -> Result<PriceQuote, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let (pricing, _) = price_bundle(&mut conn, user_id, bundle_id, coupon_code.as_deref())?;
+    Ok(pricing.to_quote(None, Some(bundle_id)))
+}
+
+#[command]
+pub fn purchase_bundle(user_id: i32, bundle_id: i32, coupon_code: Option<String>) -> Result<PriceQuote, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+
+    let result = (|| {
+        let (pricing, items) = price_bundle(&mut tx, user_id, bundle_id, coupon_code.as_deref())?;
+        for (service_id, _) in &items {
+            if owns_service(&mut tx, user_id, *service_id)? {
+                return Err("You already own a service in this bundle".to_string());
+            }
+        }
+
+        let bundle_name: String = tx.exec_first(
+            "SELECT name FROM service_bundles WHERE id = :bundle_id",
+            params! { "bundle_id" => bundle_id }
+        ).map_err(|e| e.to_string())?.ok_or_else(|| "Bundle not found".to_string())?;
+
+        if let Some(coupon) = &pricing.coupon {
+            redeem_coupon(&mut tx, user_id, coupon)?;
+        }
+        let transaction_id = charge(&mut tx, user_id, format!("Purchase: {} (bundle)", bundle_name), &pricing)?;
+
+        let catalog_prices: Vec<Decimal> = items.iter().map(|(_, price)| *price).collect();
+        let shares = pricing::split_bundle_price(pricing.final_price(), &catalog_prices);
+        for ((service_id, base_price), share) in items.iter().zip(shares) {
+            grant_service(&mut tx, user_id, *service_id, Some(bundle_id), transaction_id, *base_price, share)?;
+        }
+
+        Ok(pricing.to_quote(None, Some(bundle_id)))
+    })();
+
+    match result {
+        Ok(quote) => {
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(quote)
+        }
+        Err(e) => {
+            tx.rollback().map_err(|e| e.to_string())?;
+            Err(e)
+        }
+    }
+}
+
+const USER_SERVICE_STATE_COLUMNS: &str = r"us.service_id, s.name, us.status, us.purchased_at, us.installed_at,
+    us.enabled_at, us.disabled_at, us.uninstalled_at, us.updated_at, us.installed_version, s.version";
+
+type UserServiceStateRow = (
+    i32,
+    String,
+    String,
+    Option<chrono::NaiveDateTime>,
+    Option<chrono::NaiveDateTime>,
+    Option<chrono::NaiveDateTime>,
+    Option<chrono::NaiveDateTime>,
+    Option<chrono::NaiveDateTime>,
+    Option<chrono::NaiveDateTime>,
+    Option<String>,
+    Option<String>,
+);
+
+fn to_user_service_state(row: UserServiceStateRow) -> Result<UserServiceState, String> {
+    let (service_id, name, status, purchased_at, installed_at, enabled_at, disabled_at, uninstalled_at, updated_at, installed_version, latest_version) = row;
+    let status = ServiceStatus::parse(&status)?;
+    let is_installed = matches!(status, ServiceStatus::Installed | ServiceStatus::Enabled | ServiceStatus::Disabled);
+    let update_available = is_installed && installed_version.is_some() && installed_version != latest_version;
+    Ok(UserServiceState {
+        service_id,
+        name,
+        status,
+        purchased_at,
+        installed_at,
+        enabled_at,
+        disabled_at,
+        uninstalled_at,
+        updated_at,
+        installed_version,
+        latest_version,
+        update_available,
+    })
+}
+
+fn get_service_state<Q: Queryable>(conn: &mut Q, user_id: i32, service_id: i32) -> Result<UserServiceState, String> {
+    let query = format!(
+        "SELECT {} FROM user_services us JOIN services s ON s.id = us.service_id WHERE us.user_id = :user_id AND us.service_id = :service_id",
+        USER_SERVICE_STATE_COLUMNS
+    );
+    let row: Option<UserServiceStateRow> = conn
+        .exec_first(query, params! { "user_id" => user_id, "service_id" => service_id })
+        .map_err(|e| e.to_string())?;
+    to_user_service_state(row.ok_or_else(|| "You do not own this service".to_string())?)
+}
+
+/// The service's status, locking its row until the transaction ends.
+fn lock_service_status(tx: &mut mysql::Transaction, user_id: i32, service_id: i32) -> Result<ServiceStatus, String> {
+    let current: Option<String> = tx.exec_first(
+        "SELECT status FROM user_services WHERE user_id = :user_id AND service_id = :service_id FOR UPDATE",
+        params! { "user_id" => user_id, "service_id" => service_id },
+    ).map_err(|e| e.to_string())?;
+    ServiceStatus::parse(&current.ok_or_else(|| "You do not own this service".to_string())?)
+}
+
+/// Moves an owned service to `next`, stamping the matching timestamp column.
+fn apply_transition(tx: &mut mysql::Transaction, user_id: i32, service_id: i32, next: ServiceStatus) -> Result<(), String> {
+    let current = lock_service_status(tx, user_id, service_id)?;
+
+    if !current.can_transition_to(next) {
+        return Err(format!("Cannot change a service from {} to {}", current.as_str(), next.as_str()));
+    }
+
+    let timestamp_column = match next {
+        ServiceStatus::Owned => "purchased_at",
+        ServiceStatus::Installed => "installed_at",
+        ServiceStatus::Enabled => "enabled_at",
+        ServiceStatus::Disabled => "disabled_at",
+        ServiceStatus::Uninstalled => "uninstalled_at",
+    };
+    tx.exec_drop(
+        format!(
+            "UPDATE user_services SET status = :status, {} = NOW() WHERE user_id = :user_id AND service_id = :service_id",
+            timestamp_column
+        ),
+        params! { "status" => next.as_str(), "user_id" => user_id, "service_id" => service_id },
     ).map_err(|e| e.to_string())?;
 
-    tx.commit().map_err(|e| e.to_string())?;
+    match next {
+        ServiceStatus::Installed => {
+            // Installing always fetches the latest version
+            tx.exec_drop(
+                r"UPDATE user_services us JOIN services s ON s.id = us.service_id
+                  SET us.installed_version = s.version
+                  WHERE us.user_id = :user_id AND us.service_id = :service_id",
+                params! { "user_id" => user_id, "service_id" => service_id },
+            ).map_err(|e| e.to_string())?;
+            super::effects::schedule_service_effects(tx, user_id as u64, service_id)
+        }
+        ServiceStatus::Uninstalled => super::effects::cancel_pending_effects(tx, user_id as u64, service_id),
+        _ => Ok(()),
+    }
+}
+
+/// Moves an owned service through `steps` in one transaction, so either all of them apply or none do.
+fn transition_service(user_id: i32, service_id: i32, steps: &[ServiceStatus]) -> Result<UserServiceState, String> {
+    plan_transition(user_id, service_id, |_| steps)
+}
+
+/// Like `transition_service`, but picks the steps from the status read under the row lock, so
+/// a concurrent transition cannot change it in between.
+fn plan_transition<'a>(
+    user_id: i32,
+    service_id: i32,
+    plan: impl FnOnce(ServiceStatus) -> &'a [ServiceStatus],
+) -> Result<UserServiceState, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+
+    let result = (|| {
+        let steps = plan(lock_service_status(&mut tx, user_id, service_id)?);
+        for next in steps {
+            apply_transition(&mut tx, user_id, service_id, *next)?;
+        }
+        get_service_state(&mut tx, user_id, service_id)
+    })();
+
+    match result {
+        Ok(state) => {
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(state)
+        }
+        Err(e) => {
+            tx.rollback().map_err(|e| e.to_string())?;
+            Err(e)
+        }
+    }
+}
+
+#[command]
+pub fn install_service(user_id: i32, service_id: i32) -> Result<UserServiceState, String> {
+    transition_service(user_id, service_id, &[ServiceStatus::Installed])
+}
+
+#[command]
+pub fn enable_service(user_id: i32, service_id: i32) -> Result<UserServiceState, String> {
+    transition_service(user_id, service_id, &[ServiceStatus::Enabled])
+}
+
+#[command]
+pub fn disable_service(user_id: i32, service_id: i32) -> Result<UserServiceState, String> {
+    transition_service(user_id, service_id, &[ServiceStatus::Disabled])
+}
+
+#[command]
+pub fn uninstall_service(user_id: i32, service_id: i32) -> Result<UserServiceState, String> {
+    transition_service(user_id, service_id, &[ServiceStatus::Uninstalled])
+}
+
+/// Settings toggle: enabling a service that is not installed yet installs it first, and
+/// switching off a service that is not enabled leaves it as it is.
+#[command]
+pub fn set_service_status(user_id: i32, service_id: i32, is_enabled: bool) -> Result<UserServiceState, String> {
+    plan_transition(user_id, service_id, |current| match (is_enabled, current) {
+        (true, ServiceStatus::Enabled) => &[],
+        (true, ServiceStatus::Owned | ServiceStatus::Uninstalled) => &[ServiceStatus::Installed, ServiceStatus::Enabled],
+        (true, _) => &[ServiceStatus::Enabled],
+        (false, ServiceStatus::Enabled) => &[ServiceStatus::Disabled],
+        (false, _) => &[],
+    })
+}
+
+#[command]
+pub fn get_service_states(user_id: i32) -> Result<Vec<UserServiceState>, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let query = format!(
+        "SELECT {} FROM user_services us JOIN services s ON s.id = us.service_id WHERE us.user_id = :user_id ORDER BY s.name",
+        USER_SERVICE_STATE_COLUMNS
+    );
+    let rows: Vec<UserServiceStateRow> = conn
+        .exec(query, params! { "user_id" => user_id })
+        .map_err(|e| e.to_string())?;
+    rows.into_iter().map(to_user_service_state).collect()
+}
+
+struct RefundablePurchase {
+    id: u64,
+    service_name: String,
+    price_paid: Decimal,
+    minutes_since_purchase: i64,
+    used: bool,
+}
+
+fn load_refund_policy<Q: Queryable>(conn: &mut Q) -> Result<RefundPolicy, String> {
+    let policy: Option<(i32, i32, i32, bool)> = conn
+        .query_first("SELECT window_hours, partial_window_hours, partial_refund_percent, refund_if_used FROM refund_policy WHERE id = 1")
+        .map_err(|e| e.to_string())?;
+    let (window_hours, partial_window_hours, partial_refund_percent, refund_if_used) =
+        policy.ok_or_else(|| "Refund policy is not configured".to_string())?;
+    Ok(RefundPolicy { window_hours, partial_window_hours, partial_refund_percent, refund_if_used })
+}
+
+/// Latest purchase of the service that has not been refunded yet.
+fn find_refundable_purchase<Q: Queryable>(conn: &mut Q, user_id: i32, service_id: i32, for_update: bool) -> Result<RefundablePurchase, String> {
+    let query = format!(
+        r"SELECT sp.id, s.name, sp.price_paid, TIMESTAMPDIFF(MINUTE, sp.purchased_at, NOW()), us.enabled_at IS NOT NULL
+          FROM service_purchases sp
+          JOIN services s ON s.id = sp.service_id
+          LEFT JOIN user_services us ON us.user_id = sp.user_id AND us.service_id = sp.service_id
+          WHERE sp.user_id = :user_id AND sp.service_id = :service_id AND sp.refunded_at IS NULL
+          ORDER BY sp.purchased_at DESC, sp.id DESC
+          LIMIT 1{}",
+        if for_update { " FOR UPDATE" } else { "" }
+    );
+    let purchase: Option<(u64, String, Decimal, i64, Option<bool>)> = conn
+        .exec_first(query, params! { "user_id" => user_id, "service_id" => service_id })
+        .map_err(|e| e.to_string())?;
+
+    if let Some((id, service_name, price_paid, minutes_since_purchase, used)) = purchase {
+        return Ok(RefundablePurchase { id, service_name, price_paid, minutes_since_purchase, used: used.unwrap_or(false) });
+    }
+
+    let refunded: u64 = conn
+        .exec_first(
+            "SELECT COUNT(*) FROM service_purchases WHERE user_id = :user_id AND service_id = :service_id",
+            params! { "user_id" => user_id, "service_id" => service_id },
+        )
+        .map_err(|e| e.to_string())?
+        .unwrap_or(0);
+    if refunded > 0 {
+        Err("This purchase has already been refunded".to_string())
+    } else {
+        Err("You have not purchased this service".to_string())
+    }
+}
+
+fn refund_quote(service_id: i32, purchase: &RefundablePurchase, percent: i32, reason: String) -> (RefundQuote, Decimal) {
+    let amount = pricing::percent_of(purchase.price_paid, percent);
+    let quote = RefundQuote {
+        service_id,
+        price_paid: purchase.price_paid.to_f64().unwrap_or(0.0),
+        refund_percent: percent,
+        refund_amount: amount.to_f64().unwrap_or(0.0),
+        reason,
+    };
+    (quote, amount)
+}
+
+#[command]
+pub fn get_refund_policy() -> Result<RefundPolicy, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    load_refund_policy(&mut conn)
+}
+
+#[command]
+pub fn quote_refund(user_id: i32, service_id: i32) -> Result<RefundQuote, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let policy = load_refund_policy(&mut conn)?;
+    let purchase = find_refundable_purchase(&mut conn, user_id, service_id, false)?;
+    let (percent, reason) = pricing::refund_percent(&policy, purchase.used, purchase.minutes_since_purchase)?;
+    Ok(refund_quote(service_id, &purchase, percent, reason).0)
+}
+
+/// Refunds the latest purchase of a service: removes the entitlement and credits the
+/// refund back in one transaction. A purchase can only be refunded once.
+#[command]
+pub fn refund_service(user_id: i32, service_id: i32) -> Result<RefundQuote, String> {
+    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+
+    let result = (|| {
+        let policy = load_refund_policy(&mut tx)?;
+        let purchase = find_refundable_purchase(&mut tx, user_id, service_id, true)?;
+        let (percent, reason) = pricing::refund_percent(&policy, purchase.used, purchase.minutes_since_purchase)?;
+        let (quote, amount) = refund_quote(service_id, &purchase, percent, reason);
 
-    Ok(())
-}
\ No newline at end of file
+        tx.exec_drop(
+            "UPDATE bank_accounts SET balance = balance + :amount WHERE user_id = :user_id",
+            params! { "amount" => amount, "user_id" => user_id },
+        ).map_err(|e| e.to_string())?;
+        if tx.affected_rows() == 0 {
+            return Err("Bank account not found".to_string());
+        }
+
+        tx.exec_drop(
+            "INSERT INTO bank_transactions (user_id, description, amount) VALUES (:user_id, :description, :amount)",
+            params! { "user_id" => user_id, "description" => format!("Refund: {}", purchase.service_name), "amount" => amount },
+        ).map_err(|e| e.to_string())?;
+        let refund_transaction_id = tx.last_insert_id().ok_or_else(|| "Failed to record transaction".to_string())?;
+
+        tx.exec_drop(
+            r"UPDATE service_purchases
+              SET refunded_at = NOW(), refund_amount = :amount, refund_transaction_id = :refund_transaction_id
+              WHERE id = :id AND refunded_at IS NULL",
+            params! { "amount" => amount, "refund_transaction_id" => refund_transaction_id, "id" => purchase.id },
+        ).map_err(|e| e.to_string())?;
+        if tx.affected_rows() == 0 {
+            return Err("This purchase has already been refunded".to_string());
+        }
+
+        super::effects::cancel_pending_effects(&mut tx, user_id as u64, service_id)?;
+        tx.exec_drop(
+            "DELETE FROM user_services WHERE user_id = :user_id AND service_id = :service_id",
+            params! { "user_id" => user_id, "service_id" => service_id },
+        ).map_err(|e| e.to_string())?;
+
+        Ok(quote)
+    })();
+
+    match result {
+        Ok(quote) => {
+            tx.commit().map_err(|e| e.to_string())?;
+            Ok(quote)
+        }
+        Err(e) => {
+            tx.rollback().map_err(|e| e.to_string())?;
+            Err(e)
+        }
+    }
+}
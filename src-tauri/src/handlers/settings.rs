@@ -1,4 +1,6 @@
 use crate::db;
+use crate::handlers::entitlement::require_capability;
+use crate::models::service::Capability;
 use crate::models::settings::Settings;
 use mysql::prelude::*;
 use mysql::params;
@@ -28,6 +30,17 @@ pub fn get_settings(user_id: i32) -> Result<Option<Settings>, String> {
 #[allow(dead_code)]
 pub fn update_settings(settings: Settings) -> Result<(), String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+
+    // Turning the VPN on needs a VPN service; leaving it as it was does not
+    if settings.vpn {
+        let vpn_enabled: Option<bool> = conn
+            .exec_first("SELECT vpn FROM user_settings WHERE user_id = ?", (settings.user_id,))
+            .map_err(|e| e.to_string())?;
+        if vpn_enabled != Some(true) {
+            require_capability(&mut conn, settings.user_id as u64, Capability::Vpn)?;
+        }
+    }
+
     let query = r#"
         UPDATE user_settings
         SET
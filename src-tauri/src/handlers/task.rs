@@ -1,107 +1,152 @@
 use crate::{
     db,
-    models::task::{UniversalTask, UserTask},
-    utils::crypto,
+    handlers::{flags, task_verifier},
+    models::task::{HashProgress, TaskSpec, TaskSubmission, UniversalTask, UserTask},
+    utils::{html, task_graph::TaskGraph},
 };
+use chrono::NaiveDateTime;
 use mysql::prelude::*;
-use mysql::params;
+use mysql::{params, PooledConn};
+use std::collections::HashSet;
 
+type TaskRow = (u64, String, String, Option<String>, i32, String, Option<String>, i32);
 
+type UserTaskRow = (u64, u64, String, Option<NaiveDateTime>, NaiveDateTime);
 
+const LOCKED_MESSAGE: &str = "Complete this task's prerequisites to unlock it.";
+
+/// Every task, with its text filled in with the user's own flags. Task data is never sent.
 #[tauri::command]
-pub fn get_universal_tasks() -> Result<Vec<UniversalTask>, String> {
+pub fn get_universal_tasks(user_id: u64) -> Result<Vec<UniversalTask>, String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
     let query = "SELECT id, title, description, learning_module, points, task_type, task_data, level FROM universal_tasks ORDER BY id ASC";
 
-    let universal_tasks = conn
-        .exec_map(
-            query,
-            (), 
-            |(id, title, description, learning_module, points, task_type, task_data, level): (
-                u64,
-                String,
-                String,
-                Option<String>,
-                i32,
-                String,
-                Option<String>,
-                i32,
-            )| UniversalTask {
-                id,
-                title,
-                description,
-                learning_module,
-                points,
-                task_type,
-                task_data,
-                level,
-            },
-        )
-        .map_err(|e| e.to_string())?;
+    let rows: Vec<TaskRow> = conn.exec(query, ()).map_err(|e| e.to_string())?;
+
+    let mut universal_tasks = Vec::with_capacity(rows.len());
+    for (id, title, description, learning_module, points, task_type, task_data, level) in rows {
+        let learning_module = learning_module
+            .map(|module| flags::personalize(&mut conn, user_id, &module).map(|module| html::sanitize_html(&module)))
+            .transpose()?;
+        universal_tasks.push(UniversalTask {
+            id,
+            title,
+            description: flags::personalize(&mut conn, user_id, &description)?,
+            learning_module,
+            points,
+            task_type,
+            has_answer: task_data.is_some(),
+            level,
+        });
+    }
 
     Ok(universal_tasks)
 }
 
-/// Get task    to a  user
-#[tauri::command]
-pub fn get_user_tasks(user_id: u64) -> Result<Vec<UserTask>, String> {
-    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-    let query = "SELECT id, user_id, universal_task_id, status, completed_at, created_at FROM user_tasks WHERE user_id = ?";
-
-    let user_tasks = conn
-        .exec_map(
-            query,
-            (user_id,),
-            |(id, user_id, universal_task_id, status, completed_at, created_at): (u64, u64, u64, String, Option<chrono::NaiveDateTime>, chrono::NaiveDateTime)| UserTask {
-                id,
-                user_id,
-                universal_task_id,
-                status,
-                completed_at,
-                created_at,
-            },
-        )
-        .map_err(|e| e.to_string())?;
-
-    Ok(user_tasks)
+/// Parses a task's spec after filling in the user's flags, so verifiers check their own answers.
+pub fn user_task_spec<Q: Queryable>(conn: &mut Q, user_id: u64, task_type: &str, task_data: Option<&str>) -> Result<TaskSpec, String> {
+    let task_data = task_data.map(|data| flags::personalize(conn, user_id, data)).transpose()?;
+    TaskSpec::parse(task_type, task_data.as_deref())
 }
 
+/// Loads the prerequisite graph, failing if the prerequisites form a cycle.
+pub fn load_task_graph<Q: Queryable>(conn: &mut Q) -> Result<TaskGraph, String> {
+    let tasks: Vec<u64> = conn.exec("SELECT id FROM universal_tasks ORDER BY id", ()).map_err(|e| e.to_string())?;
+    let edges: Vec<(u64, u64)> = conn
+        .exec("SELECT task_id, prerequisite_id FROM task_prerequisites ORDER BY task_id, prerequisite_id", ())
+        .map_err(|e| e.to_string())?;
+    TaskGraph::new(&tasks, &edges)
+}
 
+fn completed_tasks<Q: Queryable>(conn: &mut Q, user_id: u64) -> Result<HashSet<u64>, String> {
+    let completed: Vec<u64> = conn
+        .exec("SELECT universal_task_id FROM user_tasks WHERE user_id = ? AND status = 'Completed'", (user_id,))
+        .map_err(|e| e.to_string())?;
+    Ok(completed.into_iter().collect())
+}
 
-
-
-
-
-
-
-
-
-/// task  complete    and award points
+/// Every task with the user's status on it, computed from the prerequisite graph.
 #[tauri::command]
-pub fn complete_task(task_id: u64, user_id: u64) -> Result<String, String> {
+pub fn get_user_tasks(user_id: u64) -> Result<Vec<UserTask>, String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
+    let graph = load_task_graph(&mut conn)?;
+    let rows: Vec<UserTaskRow> = conn
+        .exec("SELECT id, universal_task_id, status, completed_at, created_at FROM user_tasks WHERE user_id = ?", (user_id,))
+        .map_err(|e| e.to_string())?;
 
-    let task_details: Option<(i32, String)> = conn.exec_first(
-        "SELECT points, title FROM universal_tasks WHERE id = :universal_task_id",
-        params! { "universal_task_id" => task_id }
-    ).map_err(|e| e.to_string())?;
-
-    let (points_to_award, task_title) = task_details.ok_or_else(|| "Universal task not found.".to_string())?;
+    let ids_with_status = |wanted: &str| -> HashSet<u64> {
+        rows.iter().filter(|row| row.2 == wanted).map(|row| row.1).collect()
+    };
+    let completed = ids_with_status("Completed");
+    let started = ids_with_status("In Progress");
+
+    let user_tasks = graph
+        .order()
+        .iter()
+        .map(|task_id| {
+            let row = rows.iter().find(|row| row.1 == *task_id);
+            UserTask {
+                id: row.map(|row| row.0),
+                user_id,
+                universal_task_id: *task_id,
+                status: graph.status(*task_id, &completed, &started),
+                prerequisites: graph.prerequisites(*task_id).to_vec(),
+                completed_at: row.and_then(|row| row.3),
+                created_at: row.map(|row| row.4),
+            }
+        })
+        .collect();
 
-    let query = "INSERT INTO user_tasks (user_id, universal_task_id, status, completed_at) VALUES (:user_id, :universal_task_id, 'Completed', NOW()) ON DUPLICATE KEY UPDATE status = 'Completed', completed_at = NOW()";
-    conn.exec_drop(query, mysql::params! {
-        "user_id" => user_id,
-        "universal_task_id" => task_id,
-    }).map_err(|e| e.to_string())?;
+    Ok(user_tasks)
+}
 
-    let description = format!("Reward: {}", task_title);
-    super::bank::award_points(user_id, points_to_award, description)?;
+/// Marks the task complete and awards its points, in one transaction so concurrent submissions
+/// cannot both pay out. Points are paid once per user, even if the task is reset and completed
+/// again. Returns `None` if the task was already completed. Locked tasks are rejected.
+pub fn complete_task(conn: &mut PooledConn, task_id: u64, user_id: u64) -> Result<Option<String>, String> {
+    let mut tx = conn.start_transaction(mysql::TxOpts::default()).map_err(|e| e.to_string())?;
+    let graph = load_task_graph(&mut tx)?;
+    if !graph.is_unlocked(task_id, &completed_tasks(&mut tx, user_id)?) {
+        return Err(LOCKED_MESSAGE.to_string());
+    }
 
-    Ok("Task completed and points awarded.".to_string())
-}
+    let (points_to_award, task_title): (i32, String) = tx
+        .exec_first("SELECT points, title FROM universal_tasks WHERE id = ?", (task_id,))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Universal task not found.".to_string())?;
 
+    // The conditional update locks the row, so of two concurrent submissions only one completes it
+    tx.exec_drop(
+        "INSERT IGNORE INTO user_tasks (user_id, universal_task_id) VALUES (?, ?)",
+        (user_id, task_id),
+    )
+    .map_err(|e| e.to_string())?;
+    tx.exec_drop(
+        r"UPDATE user_tasks SET status = 'Completed', completed_at = NOW()
+          WHERE user_id = ? AND universal_task_id = ? AND status <> 'Completed'",
+        (user_id, task_id),
+    )
+    .map_err(|e| e.to_string())?;
+    if tx.affected_rows() == 0 {
+        tx.commit().map_err(|e| e.to_string())?;
+        return Ok(None);
+    }
 
+    tx.exec_drop(
+        "UPDATE user_tasks SET rewarded = true WHERE user_id = ? AND universal_task_id = ? AND NOT rewarded",
+        (user_id, task_id),
+    )
+    .map_err(|e| e.to_string())?;
+    let message = if tx.affected_rows() > 0 {
+        super::bank::credit_points(&mut tx, user_id, points_to_award, &format!("Reward: {}", task_title))?;
+        "Task completed and points awarded."
+    } else {
+        "Task completed. Its points were already awarded."
+    };
+    tx.commit().map_err(|e| e.to_string())?;
 
+    Ok(Some(message.to_string()))
+}
 
 #[tauri::command]
 pub fn reset_user_task(user_id: u64, universal_task_id: u64) -> Result<String, String> {
@@ -114,85 +159,82 @@ pub fn reset_user_task(user_id: u64, universal_task_id: u64) -> Result<String, S
         },
     )
     .map_err(|e| e.to_string())?;
+    conn.exec_drop(
+        "DELETE FROM cracked_hashes WHERE user_id = :user_id AND universal_task_id = :universal_task_id",
+        params! {
+            "user_id" => user_id,
+            "universal_task_id" => universal_task_id,
+        },
+    )
+    .map_err(|e| e.to_string())?;
     Ok("Task status reset to To Do.".to_string())
 }
 
-
-
-
-
-
-
-
-
-
-
-
-
-
-
+/// Checks a submission with the verifier for the task's type and completes the task if it
+/// passes. `answer` is whatever that type expects, e.g. a string or one cracked password.
 #[tauri::command]
-pub fn verify_file_encryption(file_path: String) -> Result<bool, String> {
+pub fn submit_task(user_id: u64, task_id: u64, answer: Option<serde_json::Value>) -> Result<TaskSubmission, String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-    let query = "SELECT COUNT(*) FROM encrypted_files WHERE file_path = :file_path";
-    let count: u64 = conn
-        .exec_first(query, params! { "file_path" => file_path })
+    let (task_type, task_data): (String, Option<String>) = conn
+        .exec_first("SELECT task_type, task_data FROM universal_tasks WHERE id = ?", (task_id,))
         .map_err(|e| e.to_string())?
-        .unwrap_or(0);
-    Ok(count > 0)
-}
-
-#[tauri::command]
-pub fn verify_hidden_file(content: String) -> Result<bool, String> {
-    Ok(content == "c_y_b_e_r_s_e_c_u_r_i_t_y")
-}
-
-#[tauri::command]
-pub fn verify_email_classification(user_id: u64) -> Result<bool, String> {
-    let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-
-    let required_classifications: std::collections::HashMap<u64, &str> = 
-        [(1, "spam"), (3, "phishing")].iter().cloned().collect();
-
-    let user_classifications: Vec<(u64, String)> = conn.exec_map(
-        "SELECT universal_email_id, classification FROM user_emails WHERE user_id = ?",
-        (user_id,),
-        |(universal_email_id, classification): (u64, String)| (universal_email_id, classification),
-    ).map_err(|e| e.to_string())?;
+        .ok_or_else(|| "Universal task not found.".to_string())?;
+    let spec = user_task_spec(&mut conn, user_id, &task_type, task_data.as_deref())?;
 
-    let user_classifications_map: std::collections::HashMap<u64, String> = 
-        user_classifications.into_iter().collect();
+    let graph = load_task_graph(&mut conn)?;
+    let mut completed = completed_tasks(&mut conn, user_id)?;
+    if completed.contains(&task_id) {
+        return Ok(TaskSubmission { task_id, completed: true, message: "Task already completed.".to_string(), unlocked: Vec::new() });
+    }
+    if !graph.is_unlocked(task_id, &completed) {
+        return Ok(TaskSubmission { task_id, completed: false, message: LOCKED_MESSAGE.to_string(), unlocked: Vec::new() });
+    }
 
-    for (universal_email_id, required_class) in required_classifications {
-        match user_classifications_map.get(&universal_email_id) {
-            Some(user_class) if user_class == required_class => continue,
-            _ => return Ok(false), 
+    match task_verifier::verify(&mut conn, user_id, task_id, &spec, answer)? {
+        Ok(()) => {
+            let Some(message) = complete_task(&mut conn, task_id, user_id)? else {
+                return Ok(TaskSubmission { task_id, completed: true, message: "Task already completed.".to_string(), unlocked: Vec::new() });
+            };
+            completed.insert(task_id);
+            let unlocked = graph
+                .dependents(task_id)
+                .into_iter()
+                .filter(|dependent| !completed.contains(dependent) && graph.is_unlocked(*dependent, &completed))
+                .collect();
+            Ok(TaskSubmission { task_id, completed: true, message, unlocked })
+        }
+        Err(reason) => {
+            conn.exec_drop(
+                r"INSERT INTO user_tasks (user_id, universal_task_id, status) VALUES (:user_id, :universal_task_id, 'In Progress')
+                  ON DUPLICATE KEY UPDATE status = IF(status = 'Completed', status, 'In Progress')",
+                params! { "user_id" => user_id, "universal_task_id" => task_id },
+            )
+            .map_err(|e| e.to_string())?;
+            Ok(TaskSubmission { task_id, completed: false, message: reason, unlocked: Vec::new() })
         }
     }
-
-    Ok(true)
 }
 
-
-
-
-
-
-
-
-
+/// Which hashes of a cracked_passwords task the user has solved. Only flags are returned,
+/// never the passwords.
 #[tauri::command]
-pub fn verify_strong_password_task(user_id: u64) -> Result<bool, String> {
+pub fn get_hash_progress(user_id: u64, task_id: u64) -> Result<HashProgress, String> {
     let mut conn = db::get_db_connection().map_err(|e| e.to_string())?;
-
-    let password_query = "SELECT password FROM users WHERE id = :user_id";
-    let password_hash: Option<String> = conn.exec_first(password_query, params! { "user_id" => user_id })
-        .map_err(|e| e.to_string())?;
-
-    if let Some(hash) = password_hash {
-        let default_password_hash = crypto::hash_password("password");
-        Ok(hash != default_password_hash)
-    } else {
-        Err("User not found".to_string())
+    let graph = load_task_graph(&mut conn)?;
+    if !graph.is_unlocked(task_id, &completed_tasks(&mut conn, user_id)?) {
+        return Err(LOCKED_MESSAGE.to_string());
     }
-}
\ No newline at end of file
+    let (task_type, task_data): (String, Option<String>) = conn
+        .exec_first("SELECT task_type, task_data FROM universal_tasks WHERE id = ?", (task_id,))
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "Universal task not found.".to_string())?;
+    let TaskSpec::CrackedPasswords(data) = user_task_spec(&mut conn, user_id, &task_type, task_data.as_deref())? else {
+        return Err("This task has no hashes to crack.".to_string());
+    };
+
+    let cracked = task_verifier::cracked_digests(&mut conn, user_id, task_id, &data)?;
+    let status: Option<String> = conn
+        .exec_first("SELECT status FROM user_tasks WHERE user_id = ? AND universal_task_id = ?", (user_id, task_id))
+        .map_err(|e| e.to_string())?;
+    Ok(HashProgress { task_id, cracked, completed: status.as_deref() == Some("Completed") })
+}
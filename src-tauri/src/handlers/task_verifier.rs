@@ -0,0 +1,120 @@
+use crate::models::task::{CrackedPasswordsData, EmailClassificationData, FileEncryptedData, HiddenMessageData, TaskSpec};
+use crate::utils::crypto;
+use mysql::{params, prelude::*};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// `Err` carries the reason shown to the user when a submission does not pass.
+pub type Verdict = Result<(), String>;
+
+fn parse_answer<T: DeserializeOwned>(answer: Option<Value>, expected: &str) -> Result<T, String> {
+    serde_json::from_value(answer.unwrap_or(Value::Null)).map_err(|_| format!("This task expects {} as the answer", expected))
+}
+
+fn verify_password_changed<Q: Queryable>(conn: &mut Q, user_id: u64) -> Result<Verdict, String> {
+    let hash: String = conn
+        .exec_first("SELECT password FROM users WHERE id = :user_id", params! { "user_id" => user_id })
+        .map_err(|e| e.to_string())?
+        .ok_or_else(|| "User not found".to_string())?;
+    if hash == crypto::hash_password("password") {
+        return Ok(Err("Your account still uses the default password.".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+fn verify_email_classification<Q: Queryable>(conn: &mut Q, user_id: u64, data: &EmailClassificationData) -> Result<Verdict, String> {
+    // With nothing to check every classification would trivially be right
+    if data.required_classifications.is_empty() {
+        return Err("This task has no emails to classify".to_string());
+    }
+    let mut wrong = 0;
+    for required in &data.required_classifications {
+        let classification: Option<String> = conn
+            .exec_first(
+                "SELECT classification FROM user_emails WHERE user_id = ? AND universal_email_id = ?",
+                (user_id, required.universal_email_id),
+            )
+            .map_err(|e| e.to_string())?;
+        match classification {
+            None => return Ok(Err("Some of the emails for this task have not arrived yet.".to_string())),
+            Some(classification) if classification != required.classification => wrong += 1,
+            Some(_) => {}
+        }
+    }
+    if wrong > 0 {
+        return Ok(Err(format!("{} of {} emails are not classified correctly.", wrong, data.required_classifications.len())));
+    }
+    Ok(Ok(()))
+}
+
+fn verify_file_encrypted<Q: Queryable>(conn: &mut Q, user_id: u64, data: &FileEncryptedData) -> Result<Verdict, String> {
+    let encrypted: Option<u64> = conn
+        .exec_first(
+            "SELECT COUNT(*) FROM encrypted_files WHERE file_path = :file_path AND user_id = :user_id",
+            params! { "file_path" => &data.file_path, "user_id" => user_id },
+        )
+        .map_err(|e| e.to_string())?;
+    if encrypted.unwrap_or(0) == 0 {
+        return Ok(Err(format!("{} is not encrypted yet.", data.file_path)));
+    }
+    Ok(Ok(()))
+}
+
+/// Digests of `data` the user has cracked so far, in task_data order.
+pub fn cracked_digests<Q: Queryable>(conn: &mut Q, user_id: u64, task_id: u64, data: &CrackedPasswordsData) -> Result<Vec<bool>, String> {
+    let solved: Vec<String> = conn
+        .exec(
+            "SELECT digest FROM cracked_hashes WHERE user_id = ? AND universal_task_id = ?",
+            (user_id, task_id),
+        )
+        .map_err(|e| e.to_string())?;
+    Ok(data.md5_digests.iter().map(|digest| solved.iter().any(|s| s.eq_ignore_ascii_case(digest))).collect())
+}
+
+// Passwords arrive one at a time and each one that matches a digest is remembered
+fn verify_cracked_passwords<Q: Queryable>(
+    conn: &mut Q,
+    user_id: u64,
+    task_id: u64,
+    data: &CrackedPasswordsData,
+    answer: Option<Value>,
+) -> Result<Verdict, String> {
+    let password: String = parse_answer(answer, "a cracked password")?;
+    let digest = format!("{:x}", md5::compute(password.trim().as_bytes()));
+    let matched = data.md5_digests.iter().any(|expected| expected.eq_ignore_ascii_case(&digest));
+    if matched {
+        conn.exec_drop(
+            "INSERT IGNORE INTO cracked_hashes (user_id, universal_task_id, digest) VALUES (?, ?, ?)",
+            (user_id, task_id, &digest),
+        )
+        .map_err(|e| e.to_string())?;
+    }
+
+    let cracked = cracked_digests(conn, user_id, task_id, data)?;
+    let found = cracked.iter().filter(|cracked| **cracked).count();
+    if found < cracked.len() {
+        let lead = if matched { "Correct!" } else { "That password does not match any of the hashes." };
+        return Ok(Err(format!("{} {} of {} passwords found.", lead, found, cracked.len())));
+    }
+    Ok(Ok(()))
+}
+
+fn verify_hidden_message(data: &HiddenMessageData, answer: Option<Value>) -> Result<Verdict, String> {
+    let given: String = parse_answer(answer, "the decoded message")?;
+    if !crypto::hash_password(given.trim()).eq_ignore_ascii_case(&data.answer_sha256) {
+        return Ok(Err("That is not the hidden message.".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+/// Runs the verifier registered for the task's type against the user's state and answer.
+pub fn verify<Q: Queryable>(conn: &mut Q, user_id: u64, task_id: u64, spec: &TaskSpec, answer: Option<Value>) -> Result<Verdict, String> {
+    match spec {
+        TaskSpec::Knowledge => Ok(Ok(())),
+        TaskSpec::PasswordChanged => verify_password_changed(conn, user_id),
+        TaskSpec::EmailClassification(data) => verify_email_classification(conn, user_id, data),
+        TaskSpec::FileEncrypted(data) => verify_file_encrypted(conn, user_id, data),
+        TaskSpec::CrackedPasswords(data) => verify_cracked_passwords(conn, user_id, task_id, data, answer),
+        TaskSpec::HiddenMessage(data) => verify_hidden_message(data, answer),
+    }
+}
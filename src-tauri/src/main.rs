@@ -9,14 +9,12 @@ fn main() {
             // Task Commands
             handlers::task::get_universal_tasks,
             handlers::task::get_user_tasks,
-            handlers::task::complete_task,
-            
-            
-            handlers::task::verify_file_encryption,
-            handlers::task::verify_hidden_file,
-            handlers::task::verify_email_classification,
-            handlers::task::verify_strong_password_task,
+            handlers::task::submit_task,
+            handlers::task::get_hash_progress,
             handlers::task::reset_user_task,
+            // Flag Commands
+            handlers::flags::get_user_flags,
+            handlers::flags::regenerate_user_flags,
             // Bank Commands
             handlers::bank::award_points,
             // Existing Commands
@@ -25,6 +23,7 @@ fn main() {
             handlers::account::get_user_details,
             handlers::account::update_user_info,
             handlers::account::delete_account,
+            handlers::account::set_user_role,
             handlers::auth::verify_user,
             handlers::auth::change_password,
             handlers::auth::reset_password,
@@ -33,24 +32,89 @@ fn main() {
             handlers::bank::get_transactions,
             handlers::bank::update_card_details,
             handlers::bank::generate_new_card,
+            handlers::bank::dispute_transaction,
             // Service Commands
             handlers::service::get_all_services,
+            handlers::service::get_service,
+            handlers::service::search_services,
+            handlers::service::get_service_changelog,
+            handlers::service::get_available_updates,
+            handlers::service::apply_service_update,
             handlers::service::get_user_services,
             handlers::service::purchase_service,
+            handlers::service::quote_service_price,
+            handlers::service::get_bundles,
+            handlers::service::quote_bundle_price,
+            handlers::service::purchase_bundle,
+            handlers::service::install_service,
+            handlers::service::enable_service,
+            handlers::service::disable_service,
+            handlers::service::uninstall_service,
+            handlers::service::set_service_status,
+            handlers::service::get_service_states,
+            handlers::service::get_refund_policy,
+            handlers::service::quote_refund,
+            handlers::service::refund_service,
             handlers::mail::get_emails,
             handlers::mail::mark_email_as_read,
             handlers::mail::delete_email,
             handlers::mail::classify_email,
+            handlers::mail::generate_training_emails,
+            handlers::mail::get_raw_email,
+            handlers::mail::inspect_email_links,
+            handlers::mail::grade_email_classification,
+            handlers::mail::get_filter_scores,
+            handlers::mail::release_from_quarantine,
+            handlers::mail::send_email,
+            handlers::mail::reply_to_email,
+            handlers::mail::forward_email,
+            handlers::mail::restore_email,
+            handlers::mail::empty_trash,
+            handlers::mail::move_email,
+            handlers::mail::get_mail_labels,
+            handlers::mail::create_mail_label,
+            handlers::mail::delete_mail_label,
+            handlers::mail::set_email_label,
+            handlers::mail::get_email_threads,
+            handlers::mail::search_emails,
+            handlers::mail_import::import_emails,
+            handlers::attachment::get_email_attachments,
+            handlers::attachment::preview_attachment,
+            handlers::attachment::open_attachment_in_sandbox,
+            handlers::attachment::save_attachment,
+            handlers::email_report::report_email,
+            handlers::email_report::get_email_reports,
+            handlers::email_report::get_awareness_score,
+            handlers::campaign::create_campaign,
+            handlers::campaign::get_campaigns,
+            handlers::campaign::cancel_campaign,
+            handlers::campaign::get_campaign_report,
+            handlers::campaign::export_campaign_report,
+            handlers::link::open_cybox_link,
+            handlers::link::submit_link_credentials,
             handlers::settings::get_settings,
             handlers::settings::update_settings,
             handlers::fs::encrypt_file,
             handlers::fs::is_file_encrypted,
             handlers::fs::decrypt_file_content,
+            handlers::fs::read_seeded_file,
+            handlers::fs::list_user_files,
 
             // Firewall Commands
             handlers::firewall::list_firewall_rules,
             handlers::firewall::add_firewall_rule,
-            handlers::firewall::remove_firewall_rule
+            handlers::firewall::remove_firewall_rule,
+
+            // Entitlement Commands
+            handlers::entitlement::get_entitlements,
+            handlers::cracker::crack_md5_hash,
+
+            // Service Effects Commands
+            handlers::effects::process_service_effects,
+            handlers::effects::get_security_alerts,
+            handlers::effects::acknowledge_alert,
+            handlers::effects::get_remediation_tasks,
+            handlers::effects::verify_remediation
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri app");
@@ -18,6 +18,10 @@ pub struct Transaction {
     pub user_id: u64,
     pub description: String,
     pub amount: f64,
+    pub discount: f64,
+    pub discount_source: Option<String>,
+    /// Set once a dispute has reversed the charge.
+    pub reversed: bool,
     pub created_at: String,
 }
 
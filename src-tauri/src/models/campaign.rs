@@ -0,0 +1,82 @@
+use serde::{Deserialize, Serialize};
+use chrono::NaiveDateTime;
+use super::mail::{EmailLabel, PhishingIndicator};
+
+/// An instructor's simulated phishing campaign. The body may use `{{name}}` for the
+/// recipient's name and `{{link}}` for their tracked link.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct NewCampaign {
+    pub instructor_id: i32,
+    pub name: String,
+    pub from_user: String,
+    pub subject: String,
+    pub body: String,
+    pub label: EmailLabel,
+    pub indicators: Vec<PhishingIndicator>,
+    /// `name@cybox.app` addresses.
+    pub targets: Vec<String>,
+    /// Sends straight away when missing.
+    pub send_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Campaign {
+    pub id: i32,
+    pub instructor_id: i32,
+    pub name: String,
+    pub from_user: String,
+    pub subject: String,
+    pub status: String, // scheduled, cancelled
+    pub send_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CampaignEvent {
+    Delivered,
+    Opened,
+    Clicked,
+    Reported,
+    CredentialsSubmitted,
+}
+
+impl CampaignEvent {
+    pub fn column(&self) -> &'static str {
+        match self {
+            CampaignEvent::Delivered => "delivered_at",
+            CampaignEvent::Opened => "opened_at",
+            CampaignEvent::Clicked => "clicked_at",
+            CampaignEvent::Reported => "reported_at",
+            CampaignEvent::CredentialsSubmitted => "credentials_submitted_at",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CampaignRecipient {
+    pub user_id: i32,
+    pub name: String,
+    pub delivered_at: Option<NaiveDateTime>,
+    pub opened_at: Option<NaiveDateTime>,
+    pub clicked_at: Option<NaiveDateTime>,
+    pub reported_at: Option<NaiveDateTime>,
+    pub credentials_submitted_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct CampaignTotals {
+    pub recipients: u32,
+    pub delivered: u32,
+    pub opened: u32,
+    pub clicked: u32,
+    pub reported: u32,
+    pub credentials_submitted: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CampaignReport {
+    pub campaign: Campaign,
+    pub totals: CampaignTotals,
+    pub recipients: Vec<CampaignRecipient>,
+}
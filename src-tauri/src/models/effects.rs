@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use chrono::NaiveDateTime;
+
+/// A simulated consequence of installing a malicious or dubious service.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Effect {
+    ExfiltrationAlert {
+        severity: String,
+        title: String,
+        message: String,
+    },
+    UnauthorizedDebit {
+        amount: f64,
+        description: String,
+    },
+    SpoofedEmail {
+        from_user: String,
+        subject: String,
+        body: String,
+    },
+    DegradeSetting {
+        setting: String,
+        value: serde_json::Value,
+        message: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SecurityAlert {
+    pub id: u64,
+    pub user_id: u64,
+    pub service_id: Option<u64>,
+    pub severity: String,
+    pub title: String,
+    pub message: String,
+    pub is_acknowledged: bool,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemediationTask {
+    pub id: u64,
+    pub user_id: u64,
+    pub service_id: u64,
+    pub title: String,
+    pub description: String,
+    pub points: i32,
+    pub status: String, // "open" or "resolved"
+    pub created_at: NaiveDateTime,
+    pub resolved_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemediationResult {
+    pub resolved: bool,
+    pub outstanding: Vec<String>,
+}
@@ -0,0 +1,13 @@
+use serde::{Deserialize, Serialize};
+
+/// A user's own answers to the CTF-style tasks, derived from their flag seed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct UserFlags {
+    pub seed: u64,
+    /// Password the Level 4 evidence file must be encrypted with.
+    pub file_password: String,
+    /// The passwords behind the Level 5 hashes, in task_data order.
+    pub cracked_passwords: Vec<String>,
+    /// The Level 6 message hidden across two Base64 fragments.
+    pub hidden_message: String,
+}
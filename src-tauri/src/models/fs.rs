@@ -0,0 +1,11 @@
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserFile {
+    pub id: i32,
+    pub path: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    pub source_attachment_id: Option<i32>,
+    pub created_at: chrono::NaiveDateTime,
+}
@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use super::mail::AttachmentFlag;
+
+/// What the app should do after a `cybox://` link is followed. It never says whether the
+/// link was safe; the consequences of an unsafe one arrive later.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LinkOutcome {
+    Navigate { app: String, path: String },
+    /// Show a login page and report its submission to `submit_link_credentials`. Real and
+    /// fake login pages look the same until the user signs in.
+    Login { app: String },
+    Download { path: String, file_name: String, flags: Vec<AttachmentFlag> },
+}
+
+impl LinkOutcome {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            LinkOutcome::Navigate { .. } => "navigate",
+            LinkOutcome::Login { .. } => "login",
+            LinkOutcome::Download { .. } => "download",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LinkClick {
+    pub id: i32,
+    pub url: String,
+    pub outcome: LinkOutcome,
+}
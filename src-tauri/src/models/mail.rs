@@ -4,11 +4,439 @@ use serde::{Serialize, Deserialize};
 pub struct Email {
     pub id: i32,
     pub user_id: i32,
-    pub universal_email_id: i32,
+    pub universal_email_id: Option<i32>,
     pub is_read: bool,
     pub classification: String,
     pub created_at: chrono::NaiveDateTime,
     pub from_user: String,
     pub subject: String,
     pub body: String,
+    pub headers: EmailHeaders,
+    pub folder: MailFolder,
+    pub labels: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailFolder {
+    Inbox,
+    Sent,
+    Archive,
+    Quarantine,
+    Trash,
+}
+
+impl MailFolder {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MailFolder::Inbox => "inbox",
+            MailFolder::Sent => "sent",
+            MailFolder::Archive => "archive",
+            MailFolder::Quarantine => "quarantine",
+            MailFolder::Trash => "trash",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "inbox" => Ok(MailFolder::Inbox),
+            "sent" => Ok(MailFolder::Sent),
+            "archive" => Ok(MailFolder::Archive),
+            "quarantine" => Ok(MailFolder::Quarantine),
+            "trash" => Ok(MailFolder::Trash),
+            other => Err(format!("Unknown mail folder: {}", other)),
+        }
+    }
+}
+
+/// A user-defined label such as "Work" or "Suspicious". Not to be confused with the
+/// ground-truth `EmailLabel`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MailLabel {
+    pub id: i32,
+    pub name: String,
+    pub color: String,
+}
+
+/// Emails grouped by reply references and subject, oldest first.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailThread {
+    pub thread_id: String,
+    pub subject: String,
+    pub participants: Vec<String>,
+    pub unread: u32,
+    pub last_activity: chrono::NaiveDateTime,
+    pub emails: Vec<Email>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailSearch {
+    pub user_id: i32,
+    pub text: Option<String>,
+    pub folder: Option<MailFolder>,
+    pub is_read: Option<bool>,
+    pub classification: Option<String>,
+    pub label: Option<String>,
+    pub page: Option<u32>, // starts at 1
+    pub page_size: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailPage {
+    pub emails: Vec<Email>,
+    pub total: u64,
+    pub page: u32,
+    pub page_size: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AuthResult {
+    Pass,
+    Fail,
+    SoftFail,
+    Neutral,
+    #[default]
+    None,
+}
+
+impl AuthResult {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthResult::Pass => "pass",
+            AuthResult::Fail => "fail",
+            AuthResult::SoftFail => "softfail",
+            AuthResult::Neutral => "neutral",
+            AuthResult::None => "none",
+        }
+    }
+}
+
+/// One `Received:` line. Hops are stored oldest first; `delay_seconds` is how long before
+/// delivery the hop happened.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReceivedHop {
+    pub from_host: String,
+    pub from_ip: String,
+    pub by_host: String,
+    pub delay_seconds: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct EmailHeaders {
+    pub to: Option<String>,
+    pub reply_to: Option<String>,
+    pub return_path: Option<String>,
+    pub message_id: Option<String>,
+    pub in_reply_to: Option<String>,
+    pub x_mailer: Option<String>,
+    pub received: Vec<ReceivedHop>,
+    pub spf: AuthResult,
+    pub dkim: AuthResult,
+    pub dmarc: AuthResult,
+}
+
+impl EmailHeaders {
+    /// Headers of mail genuinely sent by the domain in `from_user`.
+    pub fn authenticated(from_user: &str, sending_ip: &str, message_id: &str) -> Self {
+        let domain = from_user.rsplit('@').next().unwrap_or(from_user);
+        EmailHeaders {
+            return_path: Some(from_user.to_string()),
+            message_id: Some(format!("<{}@{}>", message_id, domain)),
+            received: vec![ReceivedHop {
+                from_host: format!("mail.{}", domain),
+                from_ip: sending_ip.to_string(),
+                by_host: "mx.cybox.app".to_string(),
+                delay_seconds: 2,
+            }],
+            spf: AuthResult::Pass,
+            dkim: AuthResult::Pass,
+            dmarc: AuthResult::Pass,
+            ..Default::default()
+        }
+    }
+
+    /// Headers of mail whose From is forged: it was really sent from `relay_host`, and
+    /// bounces and replies go to the attacker.
+    pub fn spoofed(relay_host: &str, sending_ip: &str, message_id: &str) -> Self {
+        EmailHeaders {
+            reply_to: Some(format!("support@{}", relay_host)),
+            return_path: Some(format!("bounce@{}", relay_host)),
+            message_id: Some(format!("<{}@{}>", message_id, relay_host)),
+            x_mailer: Some("PHPMailer 5.2.1".to_string()),
+            received: vec![
+                ReceivedHop {
+                    from_host: "localhost".to_string(),
+                    from_ip: "127.0.0.1".to_string(),
+                    by_host: relay_host.to_string(),
+                    delay_seconds: 94,
+                },
+                ReceivedHop {
+                    from_host: relay_host.to_string(),
+                    from_ip: sending_ip.to_string(),
+                    by_host: "mx.cybox.app".to_string(),
+                    delay_seconds: 3,
+                },
+            ],
+            spf: AuthResult::Fail,
+            dkim: AuthResult::None,
+            dmarc: AuthResult::Fail,
+            ..Default::default()
+        }
+    }
+}
+
+/// Ground truth for an email, as opposed to the student's `classification`.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailLabel {
+    Legitimate,
+    Spam,
+    Phishing,
+}
+
+impl EmailLabel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EmailLabel::Legitimate => "legitimate",
+            EmailLabel::Spam => "spam",
+            EmailLabel::Phishing => "phishing",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "legitimate" => Ok(EmailLabel::Legitimate),
+            "spam" => Ok(EmailLabel::Spam),
+            "phishing" => Ok(EmailLabel::Phishing),
+            other => Err(format!("Unknown email label: {}", other)),
+        }
+    }
+
+    /// Reads the student's `classification`, where leaving an email unflagged means legitimate.
+    pub fn from_classification(classification: &str) -> Result<Self, String> {
+        match classification {
+            "none" => Ok(EmailLabel::Legitimate),
+            other => EmailLabel::parse(other),
+        }
+    }
+}
+
+/// Red flags a student should learn to spot.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum PhishingIndicator {
+    LookalikeDomain,
+    SpoofedSender,
+    UnknownSender,
+    GenericGreeting,
+    UrgentLanguage,
+    MismatchedLink,
+    ShortenedLink,
+    IpAddressLink,
+    UnusualScheme,
+    CredentialRequest,
+    SuspiciousAttachment,
+    TooGoodToBeTrue,
+    PoorGrammar,
+}
+
+impl PhishingIndicator {
+    pub fn description(&self) -> &'static str {
+        match self {
+            PhishingIndicator::LookalikeDomain => "The sender's domain imitates a real one with swapped or extra characters.",
+            PhishingIndicator::SpoofedSender => "The headers show the message failed SPF, DKIM or DMARC, so the From address is forged.",
+            PhishingIndicator::UnknownSender => "The message comes from a sender you have no relationship with.",
+            PhishingIndicator::GenericGreeting => "It opens with a generic greeting instead of your name.",
+            PhishingIndicator::UrgentLanguage => "It pressures you to act immediately or face consequences.",
+            PhishingIndicator::MismatchedLink => "The link text shows one address but points somewhere else.",
+            PhishingIndicator::ShortenedLink => "A URL shortener hides where the link really goes.",
+            PhishingIndicator::IpAddressLink => "The link points at a raw IP address rather than a named site.",
+            PhishingIndicator::UnusualScheme => "The link uses a scheme such as javascript: or data: that browsers run instead of visit.",
+            PhishingIndicator::CredentialRequest => "It asks you to enter a password, card number or other credentials.",
+            PhishingIndicator::SuspiciousAttachment => "It carries an attachment that can run code or hide its real type.",
+            PhishingIndicator::TooGoodToBeTrue => "It promises prizes, refunds or deals that are too good to be true.",
+            PhishingIndicator::PoorGrammar => "It contains spelling and grammar mistakes a real company would not make.",
+        }
+    }
+}
+
+/// An email produced by the template generator, with the indicators it was built with.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GeneratedEmail {
+    pub from_user: String,
+    pub subject: String,
+    pub body: String,
+    pub headers: EmailHeaders,
+    pub label: EmailLabel,
+    pub indicators: Vec<PhishingIndicator>,
+}
+
+/// A link found in an email body: what it shows, where it really goes, and what is wrong with it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct InspectedLink {
+    pub text: String,
+    pub href: String,
+    pub scheme: Option<String>,
+    pub host: Option<String>,
+    pub flags: Vec<PhishingIndicator>,
+}
+
+/// Warning signs in an attachment's name and type, visible before it is opened.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentFlag {
+    ExecutableFile,
+    DoubleExtension,
+    MacroEnabled,
+    PasswordProtectedArchive,
+    TypeMismatch,
+}
+
+impl AttachmentFlag {
+    pub fn description(&self) -> &'static str {
+        match self {
+            AttachmentFlag::ExecutableFile => "The file is a program or script that runs as soon as it is opened.",
+            AttachmentFlag::DoubleExtension => "A harmless-looking extension hides the real one at the end of the name.",
+            AttachmentFlag::MacroEnabled => "The document contains macros that run code once content is enabled.",
+            AttachmentFlag::PasswordProtectedArchive => "The archive is encrypted so mail scanners cannot look inside it.",
+            AttachmentFlag::TypeMismatch => "The file's contents do not match the type the sender declared.",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Attachment {
+    pub id: i32,
+    pub email_id: i32,
+    pub file_name: String,
+    pub mime_type: String,
+    pub size_bytes: i64,
+    pub password_protected: bool,
+    pub flags: Vec<AttachmentFlag>,
+}
+
+/// What a careful user can learn about an attachment without running it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AttachmentPreview {
+    pub attachment: Attachment,
+    pub extensions: Vec<String>,
+    pub detected_type: String,
+    pub header_hex: String,
+    pub text_preview: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SandboxVerdict {
+    Clean,
+    Suspicious,
+    Malicious,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SandboxReport {
+    pub attachment_id: i32,
+    pub file_name: String,
+    pub verdict: SandboxVerdict,
+    pub behaviour: Vec<String>,
+    pub notes: Vec<String>,
+}
+
+/// How one email was classified against its ground truth.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailGrade {
+    pub email_id: i32,
+    pub subject: String,
+    pub from_user: String,
+    pub label: EmailLabel,
+    pub classification: EmailLabel,
+    pub correct: bool,
+    pub indicators: Vec<PhishingIndicator>,
+    pub missed: Vec<PhishingIndicator>,
+    pub explanation: String,
+}
+
+/// Precision and recall for one kind of unwanted mail.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct DetectionScore {
+    pub true_positives: u32,
+    pub false_positives: u32,
+    pub false_negatives: u32,
+    pub precision: f64,
+    pub recall: f64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ClassificationGrade {
+    pub emails: Vec<EmailGrade>,
+    pub correct: u32,
+    pub total: u32,
+    pub phishing: DetectionScore,
+    pub spam: DetectionScore,
+}
+
+/// The spam filter's view of one email, next to the student's own classification.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FilterScore {
+    pub email_id: i32,
+    pub subject: String,
+    pub from_user: String,
+    pub filter_label: EmailLabel,
+    pub legitimate: f64,
+    pub spam: f64,
+    pub phishing: f64,
+    pub evidence: Vec<String>,
+    pub classification: EmailLabel,
+    pub agrees: bool,
+    pub quarantined: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ImportSummary {
+    pub imported: Vec<i32>,
+    pub attachments: usize,
+    pub skipped: Vec<String>,
+}
+
+/// What has to happen before a universal email reaches a user's inbox.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DeliveryTrigger {
+    /// Counted from when the user's account was created.
+    AccountCreated,
+    /// A task starts once every task on a lower level is completed.
+    TaskStarted { task_id: i32 },
+    TaskCompleted { task_id: i32 },
+    ServicePurchased { service_id: i32 },
+    SettingChanged { setting: String, value: serde_json::Value },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailReport {
+    pub id: i32,
+    pub user_id: i32,
+    pub email_id: Option<i32>,
+    pub subject: String,
+    /// The desk's verdict, withheld until its reply arrives.
+    pub label: Option<EmailLabel>,
+    /// Whether reporting was right, withheld until the desk's reply arrives.
+    pub correct: Option<bool>,
+    pub note: Option<String>,
+    pub reported_at: chrono::NaiveDateTime,
+    pub reply_at: chrono::NaiveDateTime,
+    pub reply_email_id: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AwarenessScore {
+    pub reports: u32,
+    pub correct_reports: u32,
+    pub false_reports: u32,
+    pub missed_phishing: u32,
+    pub phishing_clicks: u32,
+    pub reporting_accuracy: f64,
+    /// 0 to 100.
+    pub score: u32,
 }
@@ -4,4 +4,10 @@ pub mod mail;
 pub mod task;
 pub mod bank;
 pub mod settings;
-pub mod firewall;
\ No newline at end of file
+pub mod firewall;
+pub mod effects;
+
+pub mod fs;
+pub mod campaign;
+pub mod link;
+pub mod flag;
\ No newline at end of file
@@ -1,17 +1,313 @@
 use serde::{Deserialize, Serialize};
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveDateTime};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Service {
     pub id: i32,
     pub name: String,
     pub description: Option<String>,
-    pub price: Option<i32>,
-    pub category: Option<String>,
-    pub features: Option<String>,
+    pub price: i32,
+    pub category: ServiceCategory,
+    pub features: Vec<ServiceFeature>,
+    pub permissions: Vec<PermissionRequest>,
+    pub risk_rating: RiskRating,
     pub icon_path: Option<String>,
     pub version: Option<String>,
     pub developer: Option<String>,
     pub release_date: Option<NaiveDate>,
-    pub is_important: Option<bool>,
+    pub is_important: bool,
+}
+
+impl Service {
+    /// Catalog rules the store relies on. Checked whenever services are loaded.
+    pub fn validate(&self) -> Result<(), String> {
+        let fail = |reason: String| Err(format!("Invalid service '{}': {}", self.name, reason));
+
+        if self.name.trim().is_empty() {
+            return fail("name is empty".to_string());
+        }
+        if self.price < 0 {
+            return fail(format!("negative price {}", self.price));
+        }
+        if let Some(feature) = self.features.iter().find(|f| f.name.trim().is_empty()) {
+            return fail(format!("feature with empty name ({:?})", feature.description));
+        }
+        for (index, permission) in self.permissions.iter().enumerate() {
+            if self.permissions[..index].iter().any(|p| p.scope == permission.scope) {
+                return fail(format!("{} permission requested twice", permission.scope.as_str()));
+            }
+            if permission.reason.trim().is_empty() {
+                return fail(format!("{} permission has no reason", permission.scope.as_str()));
+            }
+        }
+        if self.category == ServiceCategory::Malware && self.risk_rating < RiskRating::High {
+            return fail("malware must be rated high or critical risk".to_string());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceCategory {
+    Security,
+    Antivirus,
+    Utility,
+    Malware,
+}
+
+impl ServiceCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceCategory::Security => "Security",
+            ServiceCategory::Antivirus => "Antivirus",
+            ServiceCategory::Utility => "Utility",
+            ServiceCategory::Malware => "Malware",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "Security" => Ok(ServiceCategory::Security),
+            "Antivirus" => Ok(ServiceCategory::Antivirus),
+            "Utility" => Ok(ServiceCategory::Utility),
+            "Malware" => Ok(ServiceCategory::Malware),
+            other => Err(format!("Unknown service category: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceFeature {
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionScope {
+    Network,
+    Filesystem,
+    Mail,
+    Bank,
+}
+
+impl PermissionScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PermissionScope::Network => "network",
+            PermissionScope::Filesystem => "filesystem",
+            PermissionScope::Mail => "mail",
+            PermissionScope::Bank => "bank",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PermissionAccess {
+    Read,
+    Write,
+    Full,
+}
+
+/// A permission the app asks for, shown in the store's install prompt.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PermissionRequest {
+    pub scope: PermissionScope,
+    pub access: PermissionAccess,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum RiskRating {
+    Low,
+    Medium,
+    High,
+    Critical,
+}
+
+impl RiskRating {
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "low" => Ok(RiskRating::Low),
+            "medium" => Ok(RiskRating::Medium),
+            "high" => Ok(RiskRating::High),
+            "critical" => Ok(RiskRating::Critical),
+            other => Err(format!("Unknown risk rating: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceBundle {
+    pub id: i32,
+    pub name: String,
+    pub description: Option<String>,
+    pub price: i32,
+    pub service_ids: Vec<i32>,
+}
+
+/// Price computed by the backend from the catalog, sales and coupons.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PriceQuote {
+    pub service_id: Option<i32>,
+    pub bundle_id: Option<i32>,
+    pub base_price: f64,
+    pub sale_discount: f64,
+    pub coupon_discount: f64,
+    pub final_price: f64,
+    pub sale_name: Option<String>,
+    pub coupon_code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceStatus {
+    Owned,
+    Installed,
+    Enabled,
+    Disabled,
+    Uninstalled,
+}
+
+impl ServiceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ServiceStatus::Owned => "owned",
+            ServiceStatus::Installed => "installed",
+            ServiceStatus::Enabled => "enabled",
+            ServiceStatus::Disabled => "disabled",
+            ServiceStatus::Uninstalled => "uninstalled",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "owned" => Ok(ServiceStatus::Owned),
+            "installed" => Ok(ServiceStatus::Installed),
+            "enabled" => Ok(ServiceStatus::Enabled),
+            "disabled" => Ok(ServiceStatus::Disabled),
+            "uninstalled" => Ok(ServiceStatus::Uninstalled),
+            other => Err(format!("Unknown service status: {}", other)),
+        }
+    }
+
+    /// owned -> installed -> enabled <-> disabled, and any installed state can be uninstalled
+    /// and later reinstalled.
+    pub fn can_transition_to(&self, next: ServiceStatus) -> bool {
+        use ServiceStatus::*;
+        matches!(
+            (self, next),
+            (Owned, Installed)
+                | (Uninstalled, Installed)
+                | (Installed, Enabled)
+                | (Disabled, Enabled)
+                | (Enabled, Disabled)
+                | (Installed, Uninstalled)
+                | (Enabled, Uninstalled)
+                | (Disabled, Uninstalled)
+        )
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserServiceState {
+    pub service_id: i32,
+    pub name: String,
+    pub status: ServiceStatus,
+    pub purchased_at: Option<NaiveDateTime>,
+    pub installed_at: Option<NaiveDateTime>,
+    pub enabled_at: Option<NaiveDateTime>,
+    pub disabled_at: Option<NaiveDateTime>,
+    pub uninstalled_at: Option<NaiveDateTime>,
+    pub updated_at: Option<NaiveDateTime>,
+    pub installed_version: Option<String>,
+    pub latest_version: Option<String>,
+    pub update_available: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    HashCracking,
+    Firewall,
+    Vpn,
+}
+
+impl Capability {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Capability::HashCracking => "hash_cracking",
+            Capability::Firewall => "firewall",
+            Capability::Vpn => "vpn",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value {
+            "hash_cracking" => Ok(Capability::HashCracking),
+            "firewall" => Ok(Capability::Firewall),
+            "vpn" => Ok(Capability::Vpn),
+            other => Err(format!("Unknown capability: {}", other)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefundPolicy {
+    pub window_hours: i32,
+    pub partial_window_hours: i32,
+    pub partial_refund_percent: i32,
+    pub refund_if_used: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefundQuote {
+    pub service_id: i32,
+    pub price_paid: f64,
+    pub refund_percent: i32,
+    pub refund_amount: f64,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceSort {
+    Relevance,
+    Name,
+    Price,
+    ReleaseDate,
+    Risk,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct ServiceQuery {
+    pub user_id: Option<i32>,
+    pub text: Option<String>,
+    #[serde(default)]
+    pub categories: Vec<ServiceCategory>,
+    pub min_price: Option<i32>,
+    pub max_price: Option<i32>,
+    pub owned: Option<bool>, // needs user_id
+    pub sort_by: Option<ServiceSort>,
+    #[serde(default)]
+    pub descending: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceVersion {
+    pub service_id: i32,
+    pub version: String,
+    pub changelog: String,
+    pub released_at: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServiceUpdate {
+    pub service_id: i32,
+    pub name: String,
+    pub installed_version: Option<String>,
+    pub latest_version: String,
+    pub changelog: Vec<ServiceVersion>,
 }
@@ -9,18 +9,33 @@ pub struct UniversalTask {
     pub learning_module: Option<String>,
     pub points: i32,
     pub task_type: String,
-    pub task_data: Option<String>,
+    /// Whether the task takes an answer. Its data holds the expected answers, so it stays on the backend.
+    pub has_answer: bool,
     pub level: i32,
 }
 
+/// Where a user stands on a task, computed from the prerequisite graph and their progress.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub enum TaskStatus {
+    /// Some prerequisite is not completed yet.
+    Locked,
+    Available,
+    /// A submission was made but did not pass yet.
+    #[serde(rename = "In Progress")]
+    InProgress,
+    Completed,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct UserTask {
-    pub id: u64,
+    /// The user_tasks row, if the user has one for this task yet.
+    pub id: Option<u64>,
     pub user_id: u64,
     pub universal_task_id: u64,
-    pub status: String, // e.g., "To Do", "In Progress", "Completed"
+    pub status: TaskStatus,
+    pub prerequisites: Vec<u64>,
     pub completed_at: Option<NaiveDateTime>,
-    pub created_at: NaiveDateTime,
+    pub created_at: Option<NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -30,4 +45,75 @@ pub struct Task {
     pub description: String,
     pub completed: bool,
     pub created_at: NaiveDateTime,
-}
\ No newline at end of file
+}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RequiredClassification {
+    pub universal_email_id: i32,
+    pub classification: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct EmailClassificationData {
+    pub required_classifications: Vec<RequiredClassification>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileEncryptedData {
+    pub file_path: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CrackedPasswordsData {
+    /// MD5 digests of the passwords to crack. The passwords themselves are never stored.
+    pub md5_digests: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HiddenMessageData {
+    pub puzzle_type: Option<String>,
+    /// SHA-256 of the expected answer, so the answer is not shipped to the client.
+    pub answer_sha256: String,
+}
+
+/// A task's `task_type` with its `task_data` parsed into the shape that type requires.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "task_type", content = "task_data", rename_all = "snake_case")]
+pub enum TaskSpec {
+    /// Read the learning module and acknowledge it.
+    Knowledge,
+    /// The account password is no longer the default one.
+    PasswordChanged,
+    EmailClassification(EmailClassificationData),
+    FileEncrypted(FileEncryptedData),
+    CrackedPasswords(CrackedPasswordsData),
+    HiddenMessage(HiddenMessageData),
+}
+
+impl TaskSpec {
+    pub fn parse(task_type: &str, task_data: Option<&str>) -> Result<Self, String> {
+        let task_data: serde_json::Value = match task_data {
+            Some(json) => serde_json::from_str(json).map_err(|e| format!("Invalid task_data: {}", e))?,
+            None => serde_json::Value::Null,
+        };
+        serde_json::from_value(serde_json::json!({ "task_type": task_type, "task_data": task_data }))
+            .map_err(|e| format!("task_data does not fit task type {}: {}", task_type, e))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct TaskSubmission {
+    pub task_id: u64,
+    pub completed: bool,
+    pub message: String,
+    /// Tasks this submission unlocked by completing their last prerequisite.
+    pub unlocked: Vec<u64>,
+}
+
+/// Which of a cracked_passwords task's digests the user has solved, in task_data order.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct HashProgress {
+    pub task_id: u64,
+    pub cracked: Vec<bool>,
+    pub completed: bool,
+}
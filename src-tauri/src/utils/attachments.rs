@@ -0,0 +1,113 @@
+use crate::models::mail::AttachmentFlag;
+
+const EXECUTABLE_EXTENSIONS: &[&str] = &["exe", "scr", "com", "bat", "cmd", "msi", "js", "jse", "vbs", "ps1", "hta", "jar", "lnk"];
+const MACRO_EXTENSIONS: &[&str] = &["docm", "dotm", "xlsm", "xltm", "xlam", "pptm", "potm"];
+const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "rar", "7z"];
+const DOCUMENT_EXTENSIONS: &[&str] = &["pdf", "doc", "docx", "xls", "xlsx", "txt", "jpg", "jpeg", "png", "csv"];
+
+/// Every extension in the name, lowercased: `Invoice.pdf.exe` gives `["pdf", "exe"]`.
+pub fn extensions(file_name: &str) -> Vec<String> {
+    file_name.split('.').skip(1).map(|ext| ext.trim().to_ascii_lowercase()).filter(|ext| !ext.is_empty()).collect()
+}
+
+/// The real type of the file judged by its leading magic bytes, the way `file(1)` would.
+pub fn detect_type(content: &[u8], file_name: &str) -> &'static str {
+    let ext = extensions(file_name).pop().unwrap_or_default();
+    if content.starts_with(b"MZ") {
+        "application/x-msdownload"
+    } else if content.starts_with(b"%PDF") {
+        "application/pdf"
+    } else if content.starts_with(b"PK") {
+        // Office documents are zip containers too
+        match ext.as_str() {
+            "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+            "docm" => "application/vnd.ms-word.document.macroEnabled.12",
+            "xlsm" => "application/vnd.ms-excel.sheet.macroEnabled.12",
+            _ => "application/zip",
+        }
+    } else if content.starts_with(&[0x89, b'P', b'N', b'G']) {
+        "image/png"
+    } else if std::str::from_utf8(content).is_ok() {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+pub fn flags(file_name: &str, mime_type: &str, content: &[u8], password_protected: bool) -> Vec<AttachmentFlag> {
+    let extensions = extensions(file_name);
+    let last = extensions.last().map(String::as_str).unwrap_or("");
+    let mut flags = Vec::new();
+
+    if EXECUTABLE_EXTENSIONS.contains(&last) {
+        flags.push(AttachmentFlag::ExecutableFile);
+        if extensions[..extensions.len() - 1].iter().any(|ext| DOCUMENT_EXTENSIONS.contains(&ext.as_str())) {
+            flags.push(AttachmentFlag::DoubleExtension);
+        }
+    }
+    if MACRO_EXTENSIONS.contains(&last) || mime_type.contains("macroEnabled") {
+        flags.push(AttachmentFlag::MacroEnabled);
+    }
+    if password_protected && ARCHIVE_EXTENSIONS.contains(&last) {
+        flags.push(AttachmentFlag::PasswordProtectedArchive);
+    }
+    if detect_type(content, file_name) != mime_type {
+        flags.push(AttachmentFlag::TypeMismatch);
+    }
+    flags
+}
+
+/// First bytes as hex, as a hex viewer shows them.
+pub fn header_hex(content: &[u8]) -> String {
+    content.iter().take(16).map(|byte| format!("{:02x}", byte)).collect::<Vec<_>>().join(" ")
+}
+
+/// Printable text from the start of the file, with everything else shown as dots.
+pub fn text_preview(content: &[u8]) -> String {
+    content
+        .iter()
+        .take(256)
+        .map(|&byte| if byte.is_ascii_graphic() || byte == b' ' || byte == b'\n' { byte as char } else { '.' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extensions_are_lowercased_in_order() {
+        assert_eq!(extensions("Invoice.PDF.exe"), vec!["pdf", "exe"]);
+        assert!(extensions("README").is_empty());
+    }
+
+    #[test]
+    fn document_disguised_as_executable_has_a_double_extension() {
+        let disguised = flags("Invoice.pdf.exe", "application/x-msdownload", b"MZ\x90\x00", false);
+        assert_eq!(disguised, vec![AttachmentFlag::ExecutableFile, AttachmentFlag::DoubleExtension]);
+        // A dotted name that is not a document is only an executable
+        let dotted = flags("setup.v2.exe", "application/x-msdownload", b"MZ\x90\x00", false);
+        assert_eq!(dotted, vec![AttachmentFlag::ExecutableFile]);
+    }
+
+    #[test]
+    fn macros_are_detected_by_extension_or_type() {
+        let docm = "application/vnd.ms-word.document.macroEnabled.12";
+        assert_eq!(flags("Report.docm", docm, b"PK\x03\x04", false), vec![AttachmentFlag::MacroEnabled]);
+        let docx = "application/vnd.openxmlformats-officedocument.wordprocessingml.document";
+        assert_eq!(flags("Report.docx", docx, b"PK\x03\x04", false), Vec::new());
+    }
+
+    #[test]
+    fn content_that_does_not_match_its_type_is_flagged() {
+        assert_eq!(detect_type(b"MZ", "photo.png"), "application/x-msdownload");
+        assert_eq!(flags("photo.png", "image/png", b"MZ\x90\x00", false), vec![AttachmentFlag::TypeMismatch]);
+        assert_eq!(flags("notes.txt", "text/plain", b"hello", false), Vec::new());
+    }
+
+    #[test]
+    fn only_password_protected_archives_are_flagged() {
+        assert_eq!(flags("files.zip", "application/zip", b"PK\x03\x04", true), vec![AttachmentFlag::PasswordProtectedArchive]);
+        assert_eq!(flags("files.zip", "application/zip", b"PK\x03\x04", false), Vec::new());
+    }
+}
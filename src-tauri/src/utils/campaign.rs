@@ -0,0 +1,93 @@
+use crate::models::campaign::{CampaignRecipient, CampaignReport, CampaignTotals};
+use crate::utils::html;
+use chrono::NaiveDateTime;
+
+/// The app route a recipient's tracked link points at.
+pub fn tracking_link(token: &str) -> String {
+    format!("cybox://campaign/{}", token)
+}
+
+/// Fills in `{{name}}` and `{{link}}` for one recipient.
+pub fn render_body(template: &str, name: &str, token: &str) -> String {
+    let link = format!(r#"<a href="{}" class="text-blue-400 underline">Click here</a>"#, tracking_link(token));
+    template.replace("{{name}}", &html::escape(name)).replace("{{link}}", &link)
+}
+
+pub fn totals(recipients: &[CampaignRecipient]) -> CampaignTotals {
+    let count = |field: fn(&CampaignRecipient) -> Option<NaiveDateTime>| {
+        recipients.iter().filter(|recipient| field(recipient).is_some()).count() as u32
+    };
+    CampaignTotals {
+        recipients: recipients.len() as u32,
+        delivered: count(|r| r.delivered_at),
+        opened: count(|r| r.opened_at),
+        clicked: count(|r| r.clicked_at),
+        reported: count(|r| r.reported_at),
+        credentials_submitted: count(|r| r.credentials_submitted_at),
+    }
+}
+
+/// Quotes a field when needed. Fields a spreadsheet would run as a formula get a leading
+/// apostrophe, since names and subjects come from users.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn csv_time(time: Option<NaiveDateTime>) -> String {
+    time.map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string()).unwrap_or_default()
+}
+
+/// One row per recipient followed by a totals row.
+pub fn report_csv(report: &CampaignReport) -> String {
+    let mut csv = String::from("campaign,recipient,delivered_at,opened_at,clicked_at,reported_at,credentials_submitted_at\n");
+    let campaign = csv_field(&report.campaign.name);
+    for recipient in &report.recipients {
+        let row = [
+            campaign.clone(),
+            csv_field(&recipient.name),
+            csv_time(recipient.delivered_at),
+            csv_time(recipient.opened_at),
+            csv_time(recipient.clicked_at),
+            csv_time(recipient.reported_at),
+            csv_time(recipient.credentials_submitted_at),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    let totals = &report.totals;
+    csv.push_str(&format!(
+        "{},TOTAL ({} recipients),{},{},{},{},{}\n",
+        campaign, totals.recipients, totals.delivered, totals.opened, totals.clicked, totals.reported, totals.credentials_submitted
+    ));
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn formulas_are_neutralised() {
+        assert_eq!(csv_field("=HYPERLINK(\"http://x\")"), "\"'=HYPERLINK(\"\"http://x\"\")\"");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-2+3"), "'-2+3");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("a=b"), "a=b");
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+/// A parsed `cybox://app/path?query` URL. These are only followed from inside the app.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeepLink {
+    pub app: String,
+    pub segments: Vec<String>,
+    pub query: HashMap<String, String>,
+}
+
+impl DeepLink {
+    /// True when a query flag such as `phishing=true` is set.
+    pub fn flag(&self, key: &str) -> bool {
+        matches!(self.query.get(key).map(String::as_str), Some("true") | Some("1") | Some(""))
+    }
+
+    pub fn path(&self) -> String {
+        self.segments.join("/")
+    }
+}
+
+/// Where a link leads once the router has looked at it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Route {
+    /// A login form. With `phishing=true` it is a fake page that captures what is typed.
+    Login { app: String },
+    Download { file_name: String },
+    /// A recipient's tracked link from an instructor's campaign.
+    Campaign { token: String },
+    App { app: String, path: String },
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = text.get(i + 1..i + 3).and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (b'+', _) => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+pub fn parse(url: &str) -> Result<DeepLink, String> {
+    let url = url.trim();
+    let rest = url
+        .get(..8)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("cybox://"))
+        .map(|_| &url[8..])
+        .ok_or_else(|| format!("Not a cybox:// link: {}", url))?;
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+
+    let mut segments = path.split('/').filter(|segment| !segment.is_empty()).map(percent_decode);
+    let app = segments
+        .next()
+        .map(|app| app.to_lowercase())
+        .ok_or_else(|| format!("The link {} does not name an app", url))?;
+    // Segments like ".." would let a download escape the downloads folder
+    let segments: Vec<String> = segments.collect();
+    if segments.iter().any(|segment| segment == ".." || segment == "." || segment.contains('/')) {
+        return Err(format!("Invalid path in link {}", url));
+    }
+
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (percent_decode(key).to_lowercase(), percent_decode(value))
+        })
+        .collect();
+
+    Ok(DeepLink { app, segments, query })
+}
+
+pub fn route(link: &DeepLink) -> Route {
+    match (link.app.as_str(), link.segments.as_slice()) {
+        ("campaign", [token]) => Route::Campaign { token: token.clone() },
+        (_, [download, file_name]) if download == "download" => Route::Download { file_name: file_name.clone() },
+        (app, [login]) if login == "login" => Route::Login { app: app.to_string() },
+        (app, _) => Route::App { app: app.to_string(), path: link.path() },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_app_path_and_query() {
+        let link = parse("  CYBOX://Bank/login?phishing=true&Next=%2Fhome+page#top ").unwrap();
+        assert_eq!(link.app, "bank");
+        assert_eq!(link.segments, vec!["login"]);
+        assert_eq!(link.query.get("next").map(String::as_str), Some("/home page"));
+        assert!(link.flag("phishing"));
+        assert!(!link.flag("missing"));
+        assert_eq!(route(&link), Route::Login { app: "bank".to_string() });
+    }
+
+    #[test]
+    fn routes_each_kind_of_link() {
+        let routed = |url: &str| route(&parse(url).unwrap());
+        assert_eq!(routed("cybox://campaign/abc123"), Route::Campaign { token: "abc123".to_string() });
+        assert_eq!(
+            routed("cybox://filemanager/download/Scan%200231.zip"),
+            Route::Download { file_name: "Scan 0231.zip".to_string() }
+        );
+        assert_eq!(
+            routed("cybox://settings/network"),
+            Route::App { app: "settings".to_string(), path: "network".to_string() }
+        );
+        assert_eq!(routed("cybox://mail"), Route::App { app: "mail".to_string(), path: String::new() });
+    }
+
+    #[test]
+    fn rejects_other_schemes_and_empty_links() {
+        assert!(parse("https://cybox.app/bank").is_err());
+        assert!(parse("cybox:/bank").is_err());
+        assert!(parse("cybox://").is_err());
+        assert!(parse("cybox://?phishing=true").is_err());
+    }
+
+    #[test]
+    fn rejects_path_traversal() {
+        for url in [
+            "cybox://filemanager/download/..",
+            "cybox://filemanager/download/%2E%2E",
+            "cybox://filemanager/download/%2e",
+            "cybox://filemanager/download/..%2F..%2Fetc%2Fpasswd",
+            "cybox://filemanager/download/a%2Fb",
+        ] {
+            assert!(parse(url).is_err(), "{}", url);
+        }
+        // Dots inside a name are fine
+        assert!(parse("cybox://filemanager/download/report..v2.pdf").is_ok());
+    }
+
+    #[test]
+    fn bad_percent_escapes_are_kept_literally() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+    }
+}
@@ -0,0 +1,267 @@
+use crate::models::mail::{AuthResult, EmailHeaders, EmailLabel, GeneratedEmail, PhishingIndicator};
+use crate::utils::html;
+use crate::utils::links::URL_SHORTENERS;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+// Organisations the simulated inbox deals with: (name, real domain, link path)
+const BRANDS: &[(&str, &str, &str)] = &[
+    ("CyberBank", "cyberbank.fake", "account/security"),
+    ("Cybox", "cybox.app", "settings/account"),
+    ("ParcelPoint", "parcelpoint.fake", "track"),
+    ("SecureNet", "securenet.fake", "billing"),
+    ("PayFlow", "payflow.fake", "wallet"),
+];
+
+const SENDER_MAILBOXES: &[&str] = &["security", "support", "no-reply", "accounts", "billing"];
+
+const URGENCY_PHRASES: &[&str] = &[
+    "within 24 hours",
+    "immediately",
+    "before midnight today",
+    "in the next 2 hours",
+    "as soon as possible",
+];
+
+const THREATS: &[&str] = &[
+    "your account will be permanently suspended",
+    "all pending payments will be cancelled",
+    "your access will be revoked",
+    "we will be forced to close your account",
+];
+
+const SPAM_SENDERS: &[&str] = &["winner@lucky-draw.fake", "deals@megasavings.fake", "promo@best-offers.fake", "rewards@giftzone.fake"];
+
+const PRIZES: &[&str] = &["a brand new laptop", "a $500 gift card", "an all-expenses-paid holiday", "1,000,000 bonus points"];
+
+/// Swaps characters or adds words so the domain reads like the real one at a glance.
+fn lookalike_domain(rng: &mut ChaCha8Rng, domain: &str) -> String {
+    let (name, tld) = domain.split_once('.').unwrap_or((domain, "fake"));
+    match rng.gen_range(0..4) {
+        0 => {
+            let swaps = [("o", "0"), ("l", "1"), ("m", "rn"), ("i", "l"), ("e", "3")];
+            swaps
+                .iter()
+                .find(|(from, _)| name.contains(from))
+                .map(|(from, to)| format!("{}.{}", name.replacen(from, to, 1), tld))
+                .unwrap_or_else(|| format!("{}-secure.{}", name, tld))
+        }
+        1 => format!("{}-{}.{}", name, ["secure", "verify", "support", "alerts"].choose(rng).unwrap(), tld),
+        2 => format!("{}.{}.account-check.xyz", name, tld),
+        _ => format!("{}.{}", name, ["co", "net", "info"].choose(rng).unwrap()),
+    }
+}
+
+fn random_ip(rng: &mut ChaCha8Rng) -> String {
+    format!("{}.{}.{}.{}", rng.gen_range(11..223), rng.gen_range(0..255), rng.gen_range(0..255), rng.gen_range(1..254))
+}
+
+fn message_id(rng: &mut ChaCha8Rng) -> String {
+    format!("{:016x}", rng.gen::<u64>())
+}
+
+fn anchor(href: &str, text: &str) -> String {
+    format!("<a href=\"{}\" class=\"text-blue-400 underline\">{}</a>", href, text)
+}
+
+/// Picks where the phishing link really goes, and the indicators that choice plants.
+fn phishing_link(rng: &mut ChaCha8Rng, lookalike: &str, path: &str) -> (String, Vec<PhishingIndicator>) {
+    match rng.gen_range(0..3) {
+        0 => (format!("https://{}/{}", lookalike, path), vec![PhishingIndicator::MismatchedLink]),
+        1 => (
+            format!("https://{}/{}", URL_SHORTENERS.choose(rng).unwrap(), rng.gen_range(100000..999999)),
+            vec![PhishingIndicator::MismatchedLink, PhishingIndicator::ShortenedLink],
+        ),
+        _ => (
+            format!("http://{}/{}", random_ip(rng), path),
+            vec![PhishingIndicator::MismatchedLink, PhishingIndicator::IpAddressLink],
+        ),
+    }
+}
+
+fn phishing_email(rng: &mut ChaCha8Rng, recipient: &str) -> GeneratedEmail {
+    let (brand, domain, path) = *BRANDS.choose(rng).unwrap();
+    let lookalike = lookalike_domain(rng, domain);
+    let mailbox = SENDER_MAILBOXES.choose(rng).unwrap();
+    let urgency = URGENCY_PHRASES.choose(rng).unwrap();
+    let threat = THREATS.choose(rng).unwrap();
+
+    let mut indicators = vec![PhishingIndicator::LookalikeDomain, PhishingIndicator::UrgentLanguage];
+
+    // Spear phishing uses the recipient's name; bulk phishing does not
+    let greeting = if rng.gen_bool(0.3) {
+        format!("Hi {},", recipient)
+    } else {
+        indicators.push(PhishingIndicator::GenericGreeting);
+        ["Dear customer,", "Dear user,", "Hello valued member,"].choose(rng).unwrap().to_string()
+    };
+
+    let (href, link_indicators) = phishing_link(rng, &lookalike, path);
+    indicators.extend(link_indicators);
+    let link = anchor(&href, &format!("https://{}/{}", domain, path));
+
+    let (subject, request) = match rng.gen_range(0..3) {
+        0 => {
+            indicators.push(PhishingIndicator::CredentialRequest);
+            (
+                format!("{} security notice: unusual sign-in", brand),
+                format!("We detected a sign-in to your {} account from an unrecognised device. Confirm your password {} or {}:", brand, urgency, threat),
+            )
+        }
+        1 => {
+            indicators.push(PhishingIndicator::CredentialRequest);
+            (
+                format!("Payment declined - update your {} billing details", brand),
+                format!("Your last payment could not be processed. Re-enter your card number and CVC {} or {}:", urgency, threat),
+            )
+        }
+        _ => (
+            format!("Action required on your {} account", brand),
+            format!("We have placed a temporary hold on your account. Review the activity {} or {}:", urgency, threat),
+        ),
+    };
+
+    let mut closing = format!("Thank you,<br>The {} Team", brand);
+    if rng.gen_bool(0.4) {
+        indicators.push(PhishingIndicator::PoorGrammar);
+        closing = format!("Thanks you for you're cooperation,<br>{} Securty Departement", brand);
+    }
+
+    let sending_ip = random_ip(rng);
+    let id = message_id(rng);
+
+    GeneratedEmail {
+        from_user: format!("{}@{}", mailbox, lookalike),
+        subject,
+        body: format!("<p>{}</p><p>{}</p><p>{}</p><p>{}</p>", greeting, request, link, closing),
+        headers: EmailHeaders::spoofed(&lookalike, &sending_ip, &id),
+        label: EmailLabel::Phishing,
+        indicators,
+    }
+}
+
+fn spam_email(rng: &mut ChaCha8Rng) -> GeneratedEmail {
+    let sender = SPAM_SENDERS.choose(rng).unwrap();
+    let prize = PRIZES.choose(rng).unwrap();
+    let offer_domain = sender.split_once('@').map(|(_, d)| d).unwrap_or("offers.fake");
+
+    // Bulk mailers usually publish SPF but rarely sign their mail
+    let mut headers = EmailHeaders::authenticated(sender, &random_ip(rng), &message_id(rng));
+    headers.dkim = AuthResult::None;
+    headers.dmarc = AuthResult::None;
+    headers.x_mailer = Some("MassMail Pro 3.1".to_string());
+
+    GeneratedEmail {
+        from_user: sender.to_string(),
+        subject: format!("Congratulations! You have been selected to receive {}", prize),
+        body: format!(
+            "<p>Dear friend,</p><p>You are one of only 3 lucky winners this month! Claim {} now, no purchase necessary.</p><p>{}</p>",
+            prize,
+            anchor(&format!("https://{}/claim", offer_domain), "Claim my prize")
+        ),
+        headers,
+        label: EmailLabel::Spam,
+        indicators: vec![
+            PhishingIndicator::UnknownSender,
+            PhishingIndicator::GenericGreeting,
+            PhishingIndicator::TooGoodToBeTrue,
+        ],
+    }
+}
+
+fn legitimate_email(rng: &mut ChaCha8Rng, recipient: &str) -> GeneratedEmail {
+    let (brand, domain, path) = *BRANDS.choose(rng).unwrap();
+    let url = format!("https://{}/{}", domain, path);
+
+    let (subject, text) = match rng.gen_range(0..3) {
+        0 => (
+            format!("Your {} monthly statement is ready", brand),
+            "Your statement for last month is now available. You can view it any time by signing in as usual.".to_string(),
+        ),
+        1 => (
+            format!("{}: your privacy settings were updated", brand),
+            "This is a confirmation that you changed your privacy settings. No action is needed if this was you.".to_string(),
+        ),
+        _ => (
+            format!("Welcome to the new {} dashboard", brand),
+            "We have refreshed the dashboard layout. Your data and settings are unchanged.".to_string(),
+        ),
+    };
+
+    let from_user = format!("no-reply@{}", domain);
+    let headers = EmailHeaders::authenticated(&from_user, &random_ip(rng), &message_id(rng));
+
+    GeneratedEmail {
+        from_user,
+        subject,
+        body: format!(
+            "<p>Hi {},</p><p>{}</p><p>{}</p><p>Regards,<br>The {} Team</p>",
+            recipient,
+            text,
+            anchor(&url, &url),
+            brand
+        ),
+        headers,
+        label: EmailLabel::Legitimate,
+        indicators: Vec::new(),
+    }
+}
+
+/// Builds `count` training emails for `recipient`. The same seed always gives the same emails,
+/// on every platform and rand version. Roughly half are phishing and the rest split between spam
+/// and legitimate mail.
+pub fn generate_emails(seed: u64, recipient: &str, count: usize) -> Vec<GeneratedEmail> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let recipient = &html::escape(recipient);
+    (0..count)
+        .map(|_| match rng.gen_range(0..4) {
+            0 | 1 => phishing_email(&mut rng, recipient),
+            2 => spam_email(&mut rng),
+            _ => legitimate_email(&mut rng, recipient),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_emails() {
+        let first = generate_emails(42, "alice", 12);
+        let second = generate_emails(42, "alice", 12);
+        assert_eq!(first.len(), 12);
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!(a.from_user, b.from_user);
+            assert_eq!(a.subject, b.subject);
+            assert_eq!(a.body, b.body);
+            assert_eq!(a.label, b.label);
+            assert_eq!(a.indicators, b.indicators);
+        }
+    }
+
+    #[test]
+    fn different_seeds_give_different_emails() {
+        let first: Vec<String> = generate_emails(1, "alice", 12).into_iter().map(|email| email.body).collect();
+        let second: Vec<String> = generate_emails(2, "alice", 12).into_iter().map(|email| email.body).collect();
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn lookalike_senders_are_detected() {
+        for email in generate_emails(3, "alice", 60) {
+            if email.indicators.contains(&PhishingIndicator::LookalikeDomain) {
+                let domain = email.from_user.split_once('@').unwrap().1;
+                assert!(crate::utils::links::imitated_domain(domain).is_some(), "{}", domain);
+            }
+        }
+    }
+
+    #[test]
+    fn recipient_is_escaped() {
+        let emails = generate_emails(7, "<script>alert(1)</script>", 40);
+        assert!(emails.iter().all(|email| !email.body.contains("<script>")));
+        assert!(emails.iter().any(|email| email.body.contains("&lt;script&gt;")));
+    }
+}
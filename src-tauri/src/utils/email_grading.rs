@@ -0,0 +1,154 @@
+use crate::models::mail::{AwarenessScore, ClassificationGrade, DetectionScore, Email, EmailGrade, EmailLabel, PhishingIndicator};
+use crate::utils::html;
+
+fn red_flags(indicators: &[PhishingIndicator]) -> String {
+    indicators.iter().map(|indicator| indicator.description()).collect::<Vec<_>>().join(" ")
+}
+
+fn explain(label: EmailLabel, classification: EmailLabel, indicators: &[PhishingIndicator]) -> String {
+    match (label, classification) {
+        (EmailLabel::Legitimate, EmailLabel::Legitimate) => {
+            "Correct. This email is legitimate: it comes from a genuine sender and asks nothing unusual of you.".to_string()
+        }
+        (label, classification) if label == classification => {
+            format!("Correct, this is {}. The red flags were: {}", label.as_str(), red_flags(indicators))
+        }
+        (EmailLabel::Legitimate, classification) => format!(
+            "This email is legitimate, not {}. It shows none of the usual red flags, and flagging real mail means you miss it.",
+            classification.as_str()
+        ),
+        (label, EmailLabel::Legitimate) => {
+            format!("You left this unflagged, but it is {}. Look again: {}", label.as_str(), red_flags(indicators))
+        }
+        (label, classification) => format!(
+            "This is {}, not {}. Phishing tries to steal credentials or install malware, while spam only pushes unwanted offers. {}",
+            label.as_str(),
+            classification.as_str(),
+            red_flags(indicators)
+        ),
+    }
+}
+
+fn ratio(hits: u32, misses: u32) -> f64 {
+    // Nothing to find and nothing wrongly found is a perfect score
+    if hits + misses == 0 {
+        1.0
+    } else {
+        hits as f64 / (hits + misses) as f64
+    }
+}
+
+fn score(grades: &[EmailGrade], target: EmailLabel) -> DetectionScore {
+    let mut score = DetectionScore::default();
+    for grade in grades {
+        match (grade.label == target, grade.classification == target) {
+            (true, true) => score.true_positives += 1,
+            (false, true) => score.false_positives += 1,
+            (true, false) => score.false_negatives += 1,
+            (false, false) => {}
+        }
+    }
+    score.precision = ratio(score.true_positives, score.false_positives);
+    score.recall = ratio(score.true_positives, score.false_negatives);
+    score
+}
+
+pub fn grade_email(email: &Email, label: EmailLabel, indicators: Vec<PhishingIndicator>) -> Result<EmailGrade, String> {
+    let classification = EmailLabel::from_classification(&email.classification)?;
+    let correct = label == classification;
+
+    Ok(EmailGrade {
+        email_id: email.id,
+        subject: email.subject.clone(),
+        from_user: email.from_user.clone(),
+        label,
+        classification,
+        correct,
+        missed: if correct { Vec::new() } else { indicators.clone() },
+        explanation: explain(label, classification, &indicators),
+        indicators,
+    })
+}
+
+/// Totals the per-email grades and scores phishing and spam detection separately.
+pub fn summarise(emails: Vec<EmailGrade>) -> ClassificationGrade {
+    ClassificationGrade {
+        correct: emails.iter().filter(|grade| grade.correct).count() as u32,
+        total: emails.len() as u32,
+        phishing: score(&emails, EmailLabel::Phishing),
+        spam: score(&emails, EmailLabel::Spam),
+        emails,
+    }
+}
+
+/// The security desk's answer to a phishing report, as HTML.
+pub fn desk_reply(subject: &str, label: EmailLabel, indicators: &[PhishingIndicator]) -> String {
+    let verdict = match label {
+        EmailLabel::Phishing => format!(
+            "<p><b>Good catch!</b> This email is phishing and we have blocked the sender. The red flags were: {}</p>",
+            html::escape(&red_flags(indicators))
+        ),
+        EmailLabel::Spam => format!(
+            "<p><b>Good catch.</b> This email is spam rather than phishing: it is unwanted, but not an attack. \
+             Marking it as spam is enough next time. The red flags were: {}</p>",
+            html::escape(&red_flags(indicators))
+        ),
+        EmailLabel::Legitimate => "<p><b>This was legitimate.</b> We checked the sender and the links and found \
+             nothing wrong. Reporting real mail is safer than ignoring a threat, but look for concrete red flags first.</p>"
+            .to_string(),
+    };
+    format!(
+        "<p>Hello,</p><p>Thanks for reporting \"{}\" to the security desk.</p>{}<p>Security Desk</p>",
+        html::escape(subject),
+        verdict
+    )
+}
+
+/// Scores how well the user reports threats. False reports, phishing they read without
+/// reporting and phishing links they followed all count against them.
+pub fn awareness(correct_reports: u32, false_reports: u32, missed_phishing: u32, phishing_clicks: u32) -> AwarenessScore {
+    AwarenessScore {
+        reports: correct_reports + false_reports,
+        correct_reports,
+        false_reports,
+        missed_phishing,
+        phishing_clicks,
+        reporting_accuracy: ratio(correct_reports, false_reports),
+        score: (ratio(correct_reports, false_reports + missed_phishing + phishing_clicks) * 100.0).round() as u32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn desk_reply_explains_the_verdict() {
+        let reply = desk_reply("Verify <now> & win", EmailLabel::Phishing, &[PhishingIndicator::UrgentLanguage]);
+        assert!(reply.contains("\"Verify &lt;now&gt; &amp; win\""));
+        assert!(reply.contains("Good catch!"));
+        assert!(reply.contains(PhishingIndicator::UrgentLanguage.description()));
+
+        let reply = desk_reply("Sale", EmailLabel::Spam, &[]);
+        assert!(reply.contains("spam rather than phishing"));
+
+        let reply = desk_reply("Payslip", EmailLabel::Legitimate, &[]);
+        assert!(reply.contains("This was legitimate."));
+        assert!(!reply.contains("red flags were"));
+    }
+
+    #[test]
+    fn awareness_counts_every_mistake_against_the_score() {
+        let score = awareness(3, 1, 1, 0);
+        assert_eq!((score.reports, score.reporting_accuracy, score.score), (4, 0.75, 60));
+
+        let score = awareness(0, 0, 2, 1);
+        assert_eq!((score.reports, score.score), (0, 0));
+    }
+
+    #[test]
+    fn no_reports_and_no_mistakes_is_perfect() {
+        let score = awareness(0, 0, 0, 0);
+        assert_eq!((score.reporting_accuracy, score.score), (1.0, 100));
+    }
+}
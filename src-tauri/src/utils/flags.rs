@@ -0,0 +1,118 @@
+use crate::models::flag::UserFlags;
+use crate::utils::{crypto, mime};
+use once_cell::sync::Lazy;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use regex::Regex;
+
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{flag:([a-z0-9_]+)\}\}").unwrap());
+
+/// Passwords the Level 5 hashes are drawn from. All of them are in the MD5 Cracker's wordlist.
+pub const CRACKABLE_PASSWORDS: &[&str] = &[
+    "User321", "adminIam230", "mod78mod", "Klein679", "Roco89080", "Sunny2024", "guest4free", "Marta1987",
+    "backup99", "Orion556", "tiger7742", "Lena0815", "devops42", "Hugo2468", "pixel303", "Nova7171",
+    "river88x", "Kappa512", "zeta4040", "Milo1234",
+];
+
+const FILE_PASSWORD_WORDS: &[&str] = &["TopSecret", "Classified", "Evidence", "Redacted", "Confidential", "Sealed"];
+
+const HIDDEN_WORDS: &[&str] = &[
+    "cybersecurity", "firewall", "encryption", "authentication", "ransomware", "honeypot", "sandbox", "keylogger",
+];
+
+const CRACKED_PASSWORD_COUNT: usize = 5;
+
+/// A random seed. Kept below 2^53 so the frontend can round-trip it through a JS number.
+pub fn new_seed() -> u64 {
+    rand::thread_rng().gen_range(0..1u64 << 53)
+}
+
+/// Derives a user's flags from their seed. The same seed always gives the same flags, across
+/// platforms and rand releases, since ChaCha8 has a fixed output stream.
+pub fn generate(seed: u64) -> UserFlags {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let file_password = format!("{}#{:04}", FILE_PASSWORD_WORDS.choose(&mut rng).unwrap(), rng.gen_range(0..10000));
+    let cracked_passwords = CRACKABLE_PASSWORDS
+        .choose_multiple(&mut rng, CRACKED_PASSWORD_COUNT)
+        .map(|password| password.to_string())
+        .collect();
+    let word = format!("{}{:02}", HIDDEN_WORDS.choose(&mut rng).unwrap(), rng.gen_range(0..100));
+    let hidden_message = word.chars().map(String::from).collect::<Vec<_>>().join("_");
+    UserFlags { seed, file_password, cracked_passwords, hidden_message }
+}
+
+/// The two Base64 fragments the hidden message is split into. The first ends on a character
+/// and the second starts with its separator, so joining the decoded halves gives the message.
+pub fn fragments(flags: &UserFlags) -> (String, String) {
+    let characters = flags.hidden_message.chars().filter(|c| *c != '_').count();
+    let split = (characters / 2 * 2).saturating_sub(1);
+    let (first, second) = flags.hidden_message.split_at(split);
+    (mime::encode_base64(first.as_bytes()), mime::encode_base64(second.as_bytes()))
+}
+
+/// The value behind a `{{flag:<name>}}` placeholder, or `None` for unknown names.
+pub fn value(flags: &UserFlags, name: &str) -> Option<String> {
+    if let Some(index) = name.strip_prefix("md5_").and_then(|index| index.parse::<usize>().ok()) {
+        let password = flags.cracked_passwords.get(index.checked_sub(1)?)?;
+        return Some(format!("{:x}", md5::compute(password.as_bytes())));
+    }
+    match name {
+        "file_password" => Some(flags.file_password.clone()),
+        "hidden_message_sha256" => Some(crypto::hash_password(&flags.hidden_message)),
+        "fragment_1" => Some(fragments(flags).0),
+        "fragment_2" => Some(fragments(flags).1),
+        _ => None,
+    }
+}
+
+/// Fills the `{{flag:<name>}}` placeholders in seeded content with the user's own flags.
+/// Unknown placeholders are left as they are.
+pub fn render(text: &str, flags: &UserFlags) -> String {
+    PLACEHOLDER
+        .replace_all(text, |captures: &regex::Captures| value(flags, &captures[1]).unwrap_or_else(|| captures[0].to_string()))
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_gives_same_flags() {
+        assert_eq!(generate(42), generate(42));
+        assert_eq!(generate(42).cracked_passwords.len(), CRACKED_PASSWORD_COUNT);
+    }
+
+    #[test]
+    fn different_seeds_give_different_flags() {
+        assert_ne!(generate(1), generate(2));
+    }
+
+    #[test]
+    fn fragments_join_to_the_hidden_message() {
+        for seed in 0..20 {
+            let flags = generate(seed);
+            let (first, second) = fragments(&flags);
+            let mut joined = mime::decode_base64(first.as_bytes());
+            joined.extend(mime::decode_base64(second.as_bytes()));
+            assert_eq!(String::from_utf8(joined).unwrap(), flags.hidden_message);
+        }
+    }
+
+    #[test]
+    fn md5_values_hash_the_cracked_passwords() {
+        let flags = generate(7);
+        let expected = format!("{:x}", md5::compute(flags.cracked_passwords[0].as_bytes()));
+        assert_eq!(value(&flags, "md5_1"), Some(expected));
+        assert_eq!(value(&flags, "md5_0"), None);
+        assert_eq!(value(&flags, "md5_6"), None);
+    }
+
+    #[test]
+    fn render_fills_known_placeholders_only() {
+        let flags = generate(3);
+        let rendered = render("pw {{flag:file_password}} {{flag:nope}}", &flags);
+        assert_eq!(rendered, format!("pw {} {{{{flag:nope}}}}", flags.file_password));
+    }
+}
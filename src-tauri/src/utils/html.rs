@@ -0,0 +1,246 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static TAG: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"^<(/?)([a-zA-Z][a-zA-Z0-9]*)((?:[^>"']|"[^"]*"|'[^']*')*)>"#).unwrap());
+static CLOSING_TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"</([a-zA-Z][a-zA-Z0-9]*)\s*>").unwrap());
+static ATTRIBUTE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r#"([a-zA-Z_:][-a-zA-Z0-9_:.]*)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+)))?"#).unwrap()
+});
+
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "abbr", "b", "blockquote", "br", "caption", "code", "dd", "div", "dl", "dt", "em", "figcaption", "figure",
+    "h1", "h2", "h3", "h4", "h5", "h6", "hr", "i", "img", "kbd", "li", "mark", "ol", "p", "pre", "s", "small", "span",
+    "strong", "sub", "sup", "table", "tbody", "td", "tfoot", "th", "thead", "tr", "u", "ul",
+];
+
+// Elements whose content can run code or restyle the whole app; removed along with their content
+const DROPPED_WITH_CONTENT: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "form", "frameset", "noscript", "template", "textarea", "title",
+    "select", "svg", "math",
+];
+
+const GLOBAL_ATTRIBUTES: &[&str] = &["class", "title", "lang", "dir", "align"];
+
+fn allowed_attribute(tag: &str, name: &str) -> bool {
+    GLOBAL_ATTRIBUTES.contains(&name)
+        || matches!(
+            (tag, name),
+            ("a", "href") | ("a", "name")
+                | ("img", "src") | ("img", "alt") | ("img", "width") | ("img", "height")
+                | ("td", "colspan") | ("td", "rowspan") | ("th", "colspan") | ("th", "rowspan")
+                | ("ol", "start")
+        )
+}
+
+/// Schemes links and images may use. cybox:// routes inside the app.
+const ALLOWED_SCHEMES: &[&str] = &["http", "https", "mailto", "cybox"];
+
+/// Comments are stripped unless they start with this marker. Content authors use it for
+/// deliberate hidden clues, e.g. `<!-- cybox:hidden Fragment 2/2: ... -->`, and the marker
+/// itself is removed from the output.
+pub const HIDDEN_COMMENT_MARKER: &str = "cybox:hidden";
+
+/// Decodes the named entities mail commonly uses plus numeric character references.
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest.find(';').filter(|end| *end <= 10).map(|end| &rest[1..end]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            _ => {
+                let code = match entity.strip_prefix('#') {
+                    Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok(),
+                    Some(decimal) => decimal.parse().ok(),
+                    None => None,
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+        match (entity, character) {
+            (Some(entity), Some(character)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// True for relative URLs and ones whose scheme is on the allowlist.
+fn safe_url(url: &str) -> bool {
+    // Browsers ignore whitespace and control characters inside a scheme, so "java\tscript:" still runs
+    let url: String = decode_entities(url).chars().filter(|c| !c.is_whitespace() && !c.is_control()).collect();
+    match url.find([':', '/', '?', '#']) {
+        Some(index) if url[index..].starts_with(':') => ALLOWED_SCHEMES.contains(&url[..index].to_ascii_lowercase().as_str()),
+        _ => true,
+    }
+}
+
+fn clean_attributes(tag: &str, attributes: &str) -> String {
+    let mut clean = String::new();
+    for capture in ATTRIBUTE.captures_iter(attributes) {
+        let name = capture[1].to_ascii_lowercase();
+        if !allowed_attribute(tag, &name) || clean.contains(&format!(" {}=", name)) {
+            continue;
+        }
+        let value = capture.get(2).or(capture.get(3)).or(capture.get(4)).map(|m| m.as_str()).unwrap_or("");
+        if matches!(name.as_str(), "href" | "src") && !safe_url(value) {
+            continue;
+        }
+        clean.push_str(&format!(" {}=\"{}\"", name, escape(&decode_entities(value))));
+    }
+    clean
+}
+
+fn hidden_comment(comment: &str) -> Option<String> {
+    let note = comment.trim_start().strip_prefix(HIDDEN_COMMENT_MARKER)?;
+    // Nothing inside may close the comment early
+    Some(format!("<!-- {} -->", note.trim().replace("--", "- -").replace('>', "&gt;")))
+}
+
+/// Makes HTML safe to render in the app with an allowlist of tags, attributes and URL schemes.
+/// Unknown tags are dropped but their text is kept, and links survive so exercises can still
+/// show a deceptive link, but nothing in the result can run.
+pub fn sanitize_html(html: &str) -> String {
+    let mut clean = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        clean.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if let Some(comment) = rest.strip_prefix("<!--") {
+            let end = comment.find("-->").unwrap_or(comment.len());
+            if let Some(kept) = hidden_comment(&comment[..end]) {
+                clean.push_str(&kept);
+            }
+            rest = comment.get(end + 3..).unwrap_or("");
+            continue;
+        }
+        if rest.starts_with("<!") || rest.starts_with("<?") {
+            rest = rest.find('>').map(|end| &rest[end + 1..]).unwrap_or("");
+            continue;
+        }
+
+        let Some(capture) = TAG.captures(rest) else {
+            clean.push_str("&lt;");
+            rest = &rest[1..];
+            continue;
+        };
+        let length = capture[0].len();
+        let closing = !capture[1].is_empty();
+        let tag = capture[2].to_ascii_lowercase();
+
+        if DROPPED_WITH_CONTENT.contains(&tag.as_str()) {
+            rest = &rest[length..];
+            if !closing {
+                let end = CLOSING_TAG
+                    .captures_iter(rest)
+                    .find(|capture| capture[1].eq_ignore_ascii_case(&tag))
+                    .and_then(|capture| capture.get(0))
+                    .map(|whole| whole.end());
+                rest = end.map(|end| &rest[end..]).unwrap_or("");
+            }
+            continue;
+        }
+        if ALLOWED_TAGS.contains(&tag.as_str()) {
+            if closing {
+                clean.push_str(&format!("</{}>", tag));
+            } else {
+                clean.push_str(&format!("<{}{}>", tag, clean_attributes(&tag, &capture[3])));
+            }
+        }
+        rest = &rest[length..];
+    }
+    clean.push_str(rest);
+    clean
+}
+
+/// Escapes text for inclusion in HTML.
+pub fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dangerous_elements_are_dropped_with_their_content() {
+        let clean = sanitize_html("<p>a<script>alert(1)</script>b<STYLE>body{}</style >c<svg><script>1</script></svg>d</p>");
+        assert_eq!(clean, "<p>abcd</p>");
+        assert_eq!(sanitize_html("<iframe src=\"x\">inner</IFRAME>after"), "after");
+        // An unclosed dropped element takes the rest of the document with it
+        assert_eq!(sanitize_html("ok<textarea>never closed"), "ok");
+    }
+
+    #[test]
+    fn unknown_tags_and_attributes_are_stripped() {
+        assert_eq!(sanitize_html("<unknown attr=1>kept</unknown>"), "kept");
+        assert_eq!(
+            sanitize_html("<p onclick=\"x()\" class=note style=\"color:red\">hi</p>"),
+            "<p class=\"note\">hi</p>"
+        );
+        assert_eq!(sanitize_html("<img src=x onerror=alert(1)>"), "<img src=\"x\">");
+    }
+
+    #[test]
+    fn script_urls_are_removed_however_they_are_written() {
+        for href in [
+            "javascript:alert(1)",
+            "JavaScript:alert(1)",
+            "java\tscript:alert(1)",
+            " java\nscript:alert(1)",
+            "jav&#x61;script:alert(1)",
+            "jav&#97;script:alert(1)",
+            "javascript&colon;alert(1)&#58;",
+            "data:text/html,<script>alert(1)</script>",
+            "vbscript:msgbox",
+        ] {
+            let clean = sanitize_html(&format!("<a href=\"{}\">x</a>", href));
+            assert!(!clean.contains("href"), "{} -> {}", href, clean);
+        }
+        assert_eq!(
+            sanitize_html("<a href='https://a.b/?q=1&amp;r=2'>l</a>"),
+            "<a href=\"https://a.b/?q=1&amp;r=2\">l</a>"
+        );
+        assert_eq!(sanitize_html("<a href=\"cybox://bank/login\">l</a>"), "<a href=\"cybox://bank/login\">l</a>");
+    }
+
+    #[test]
+    fn unclosed_quotes_and_comments_cannot_smuggle_markup() {
+        // The quote never closes, so this is not a tag and is shown as text
+        assert_eq!(sanitize_html("<a href=\"x>y<script>alert(1)</script>"), "&lt;a href=\"x>y");
+        assert_eq!(sanitize_html("before<!-- never closed <script>alert(1)</script>"), "before");
+        assert_eq!(sanitize_html("a<!-- secret -->b<!DOCTYPE html>c<?xml x?>d"), "abcd");
+        assert_eq!(sanitize_html("1 < 2 & 3 > 2"), "1 &lt; 2 & 3 > 2");
+    }
+
+    #[test]
+    fn hidden_comments_keep_their_note_only() {
+        assert_eq!(
+            sanitize_html("Renew<!-- cybox:hidden Fragment 2/2: X3Nf -->"),
+            "Renew<!-- Fragment 2/2: X3Nf -->"
+        );
+        assert_eq!(
+            sanitize_html("<!-- cybox:hidden a --><script>alert(1)</script> -->"),
+            "<!-- a --> -->"
+        );
+        assert_eq!(sanitize_html("<!--cybox:hidden x--!><b>y</b>-->"), "<!-- x- -!&gt;<b&gt;y</b&gt; -->");
+        assert_eq!(sanitize_html("<!-- not cybox:hidden -->"), "");
+    }
+}
@@ -0,0 +1,241 @@
+use crate::models::mail::{InspectedLink, PhishingIndicator};
+use crate::utils::html;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+static ANCHOR: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r#"(?is)<a\b[^>]*?\bhref\s*=\s*(?:"([^"]*)"|'([^']*)')[^>]*>(.*?)</a\s*>"#).unwrap());
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+static BARE_DOMAIN: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)^(www\.)?[a-z0-9-]+(\.[a-z0-9-]+)+(/\S*)?$").unwrap());
+
+/// Domains the simulated inbox legitimately receives mail and links from.
+pub const TRUSTED_DOMAINS: &[&str] = &[
+    "cyberbank.fake",
+    "cybox.app",
+    "parcelpoint.fake",
+    "securenet.fake",
+    "payflow.fake",
+    "securevpn.fake",
+];
+
+pub const URL_SHORTENERS: &[&str] = &["bit.ly", "tinyurl.com", "t.co", "is.gd", "goo.gl", "ow.ly", "buff.ly", "cutt.ly", "rebrand.ly"];
+
+// cybox:// links navigate inside the app itself, so they are expected
+const EXPECTED_SCHEMES: &[&str] = &["http", "https", "mailto", "cybox"];
+
+struct ParsedUrl {
+    scheme: Option<String>,
+    host: Option<String>,
+}
+
+fn parse_url(url: &str) -> ParsedUrl {
+    let url = url.trim();
+    let (scheme, rest) = match url.find(':') {
+        Some(index) if url[..index].chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c)) && index > 0 => {
+            (Some(url[..index].to_ascii_lowercase()), &url[index + 1..])
+        }
+        _ => (None, url),
+    };
+
+    // mailto:, javascript: and data: URLs have no authority part
+    let authority = match (scheme.as_deref(), rest.strip_prefix("//")) {
+        (_, Some(after)) => after,
+        (None, None) => rest,
+        (Some(_), None) => return ParsedUrl { scheme, host: None },
+    };
+
+    let authority = authority.split(['/', '?', '#']).next().unwrap_or("");
+    let host_port = authority.rsplit('@').next().unwrap_or(authority);
+    let host = if let Some(bracketed) = host_port.strip_prefix('[') {
+        bracketed.split(']').next().unwrap_or("")
+    } else {
+        host_port.split(':').next().unwrap_or("")
+    };
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+
+    ParsedUrl {
+        scheme,
+        host: if host.is_empty() { None } else { Some(host) },
+    }
+}
+
+fn without_www(host: &str) -> &str {
+    host.strip_prefix("www.").unwrap_or(host)
+}
+
+fn is_ip_literal(host: &str) -> bool {
+    // Browsers also accept a single decimal number such as http://3232235777/
+    host.parse::<Ipv4Addr>().is_ok() || host.parse::<Ipv6Addr>().is_ok() || (!host.is_empty() && host.chars().all(|c| c.is_ascii_digit()))
+}
+
+fn belongs_to(host: &str, domain: &str) -> bool {
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+fn is_shortener(host: &str) -> bool {
+    URL_SHORTENERS.iter().any(|shortener| belongs_to(host, shortener))
+}
+
+/// Undoes the character swaps attackers use to imitate a name: 0 for o, 1 for l, rn for m.
+fn normalise_confusables(name: &str) -> String {
+    name.replace("rn", "m").replace('0', "o").replace('1', "l").replace('3', "e").replace('5', "s")
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The trusted domain `host` imitates, if any. Hosts that really belong to a trusted domain
+/// are not lookalikes.
+pub fn imitated_domain(host: &str) -> Option<&'static str> {
+    let host = without_www(host);
+    if TRUSTED_DOMAINS.iter().any(|domain| belongs_to(host, domain)) || is_ip_literal(host) {
+        return None;
+    }
+
+    let first_label = host.split('.').next().unwrap_or(host);
+    TRUSTED_DOMAINS.iter().copied().find(|domain| {
+        let name = domain.split('.').next().unwrap_or(domain);
+        normalise_confusables(first_label) == name
+            || is_near_miss(first_label, name)
+            || host.split(['.', '-']).any(|label| label == name)
+    })
+}
+
+/// A typo of `name`. Short labels differ from each other in a couple of letters by chance
+/// (inbox and cybox), so they are never near misses and longer ones get one typo per four letters.
+fn is_near_miss(label: &str, name: &str) -> bool {
+    const MIN_LENGTH: usize = 5;
+    let length = label.chars().count().min(name.chars().count());
+    length >= MIN_LENGTH && edit_distance(label, name) <= length / 4
+}
+
+/// Whether the displayed text claims to be a URL, and if so the host it claims.
+fn displayed_host(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.contains("://") || BARE_DOMAIN.is_match(text) {
+        parse_url(text).host.map(|host| without_www(&host).to_string())
+    } else {
+        None
+    }
+}
+
+/// Flags the red flags on a single link.
+pub fn inspect_link(text: &str, href: &str) -> InspectedLink {
+    let parsed = parse_url(href);
+    let mut flags = Vec::new();
+
+    if let Some(scheme) = &parsed.scheme {
+        if !EXPECTED_SCHEMES.contains(&scheme.as_str()) {
+            flags.push(PhishingIndicator::UnusualScheme);
+        }
+    }
+
+    if let Some(shown) = displayed_host(text) {
+        let real = parsed.host.as_deref().map(without_www);
+        if real != Some(shown.as_str()) {
+            flags.push(PhishingIndicator::MismatchedLink);
+        }
+    }
+
+    // App routes such as cybox://bank are not network hosts
+    let web_host = parsed.host.as_deref().filter(|_| matches!(parsed.scheme.as_deref(), Some("http") | Some("https") | None));
+    if let Some(host) = web_host {
+        if is_ip_literal(host) {
+            flags.push(PhishingIndicator::IpAddressLink);
+        }
+        if is_shortener(host) {
+            flags.push(PhishingIndicator::ShortenedLink);
+        } else if imitated_domain(host).is_some() {
+            flags.push(PhishingIndicator::LookalikeDomain);
+        }
+    }
+
+    InspectedLink {
+        text: text.to_string(),
+        href: href.to_string(),
+        scheme: parsed.scheme,
+        host: parsed.host,
+        flags,
+    }
+}
+
+/// Every anchor in an HTML email body, in document order.
+pub fn inspect_links(body: &str) -> Vec<InspectedLink> {
+    ANCHOR
+        .captures_iter(body)
+        .map(|captures| {
+            let href = captures.get(1).or_else(|| captures.get(2)).map_or("", |m| m.as_str());
+            let text = TAG.replace_all(&captures[3], "");
+            inspect_link(html::decode_entities(text.trim()).as_str(), &html::decode_entities(href))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(body: &str) -> Vec<PhishingIndicator> {
+        inspect_links(body).into_iter().flat_map(|link| link.flags).collect()
+    }
+
+    #[test]
+    fn trusted_links_are_clean() {
+        let body = r#"<p><a href="https://cyberbank.fake/account">https://cyberbank.fake/account</a>
+            <a href='https://www.cybox.app/settings'>Settings</a> <a href="cybox://bank">Open bank</a></p>"#;
+        let links = inspect_links(body);
+        assert_eq!(links.len(), 3);
+        assert!(links.iter().all(|link| link.flags.is_empty()));
+        assert_eq!(links[1].host.as_deref(), Some("www.cybox.app"));
+    }
+
+    #[test]
+    fn mismatched_text_is_flagged() {
+        let found = flags(r#"<a href="https://evil.example/login">https://<b>cyberbank.fake</b>/login</a>"#);
+        assert_eq!(found, vec![PhishingIndicator::MismatchedLink]);
+    }
+
+    #[test]
+    fn lookalikes_are_flagged() {
+        for host in ["cyb3rbank.fake", "cyberbannk.fake", "cyberbank-secure.fake", "cybox.app.account-check.xyz", "paypflow.co"] {
+            let found = flags(&format!(r#"<a href="https://{}/">Sign in</a>"#, host));
+            assert_eq!(found, vec![PhishingIndicator::LookalikeDomain], "{}", host);
+        }
+    }
+
+    #[test]
+    fn short_unrelated_labels_are_not_lookalikes() {
+        for host in ["inbox.example", "paypal.example", "cyber.example", "box.example"] {
+            assert_eq!(imitated_domain(host), None, "{}", host);
+        }
+    }
+
+    #[test]
+    fn ip_shortener_and_scheme_are_flagged() {
+        assert_eq!(flags(r#"<a href="http://192.168.4.20/login">Log in</a>"#), vec![PhishingIndicator::IpAddressLink]);
+        assert_eq!(flags(r#"<a href="http://3232235777/">Log in</a>"#), vec![PhishingIndicator::IpAddressLink]);
+        assert_eq!(flags(r#"<a href="https://bit.ly/x1">Track parcel</a>"#), vec![PhishingIndicator::ShortenedLink]);
+        assert_eq!(
+            flags(r#"<a href="javascript:alert(1)">Open</a>"#),
+            vec![PhishingIndicator::UnusualScheme]
+        );
+    }
+
+    #[test]
+    fn entities_in_href_are_decoded() {
+        let links = inspect_links(r#"<a href="https://cyberbank.fake/?a=1&amp;b=2">here</a>"#);
+        assert_eq!(links[0].href, "https://cyberbank.fake/?a=1&b=2");
+    }
+}
@@ -0,0 +1,440 @@
+use crate::models::mail::{AuthResult, EmailHeaders, ReceivedHop};
+use crate::utils::html;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+static ENCODED_WORD: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)=\?([^?]+)\?([bq])\?([^?]*)\?=").unwrap());
+// Whitespace between two encoded words is not part of the text
+static ENCODED_WORD_GAP: Lazy<Regex> = Lazy::new(|| Regex::new(r"(\?=)\s+(=\?)").unwrap());
+static RECEIVED: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"(?is)from\s+(\S+).*?\[([0-9a-f.:]+)\].*?\bby\s+(\S+)").unwrap());
+static AUTH_RESULT: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\b(spf|dkim|dmarc)=([a-z]+)").unwrap());
+
+/// A file carried by an imported message.
+#[derive(Debug, Clone)]
+pub struct ParsedAttachment {
+    pub file_name: String,
+    pub mime_type: String,
+    pub content: Vec<u8>,
+}
+
+/// An email parsed from its RFC 5322 source, reduced to what the mail store keeps.
+#[derive(Debug, Clone)]
+pub struct ParsedMessage {
+    pub from_user: String,
+    pub subject: String,
+    pub body: String,
+    pub date: Option<chrono::NaiveDateTime>,
+    pub headers: EmailHeaders,
+    pub attachments: Vec<ParsedAttachment>,
+}
+
+struct Part {
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Part {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    fn headers_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers.iter().filter(move |(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    /// The bare `type/subtype` of the part, defaulting to plain text as RFC 2045 does.
+    fn content_type(&self) -> String {
+        self.header("Content-Type")
+            .and_then(|value| value.split(';').next())
+            .map(|value| value.trim().to_ascii_lowercase())
+            .filter(|value| !value.is_empty())
+            .unwrap_or_else(|| "text/plain".to_string())
+    }
+
+    fn parameter(&self, header: &str, name: &str) -> Option<String> {
+        parameter(self.header(header)?, name)
+    }
+
+    fn decoded_body(&self) -> Vec<u8> {
+        match self.header("Content-Transfer-Encoding").map(|value| value.trim().to_ascii_lowercase()).as_deref() {
+            Some("base64") => decode_base64(&self.body),
+            Some("quoted-printable") => decode_quoted_printable(&self.body),
+            _ => self.body.clone(),
+        }
+    }
+
+    fn text(&self) -> String {
+        let charset = self.parameter("Content-Type", "charset").unwrap_or_default();
+        decode_charset(&charset, &self.decoded_body())
+    }
+
+    fn file_name(&self) -> Option<String> {
+        self.parameter("Content-Disposition", "filename")
+            .or_else(|| self.parameter("Content-Type", "name"))
+            .map(|name| decode_encoded_words(&name))
+    }
+
+    fn is_attachment(&self) -> bool {
+        let disposition = self.header("Content-Disposition").unwrap_or("").trim().to_ascii_lowercase();
+        disposition.starts_with("attachment") || (self.file_name().is_some() && !self.content_type().starts_with("multipart/"))
+    }
+}
+
+fn parameter(value: &str, name: &str) -> Option<String> {
+    value.split(';').skip(1).find_map(|param| {
+        let (key, value) = param.split_once('=')?;
+        if key.trim().eq_ignore_ascii_case(name) {
+            Some(value.trim().trim_matches('"').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+fn decode_charset(charset: &str, bytes: &[u8]) -> String {
+    match charset.trim().to_ascii_lowercase().as_str() {
+        // Latin-1 maps each byte straight to the code point with the same value
+        "iso-8859-1" | "latin1" | "windows-1252" | "us-ascii" => bytes.iter().map(|&byte| byte as char).collect(),
+        _ => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+pub fn decode_base64(input: &[u8]) -> Vec<u8> {
+    fn value(byte: u8) -> Option<u32> {
+        match byte {
+            b'A'..=b'Z' => Some((byte - b'A') as u32),
+            b'a'..=b'z' => Some((byte - b'a' + 26) as u32),
+            b'0'..=b'9' => Some((byte - b'0' + 52) as u32),
+            b'+' | b'-' => Some(62),
+            b'/' | b'_' => Some(63),
+            _ => None,
+        }
+    }
+
+    let mut out = Vec::with_capacity(input.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for &byte in input {
+        if byte == b'=' {
+            break;
+        }
+        let Some(value) = value(byte) else {
+            continue;
+        };
+        buffer = (buffer << 6) | value;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+            buffer &= (1 << bits) - 1;
+        }
+    }
+    out
+}
+
+pub fn encode_base64(input: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(input.len().div_ceil(3) * 4);
+    for chunk in input.chunks(3) {
+        let buffer = chunk.iter().enumerate().fold(0u32, |buffer, (index, &byte)| buffer | (byte as u32) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                out.push(ALPHABET[(buffer >> (18 - 6 * index) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+pub fn decode_quoted_printable(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len());
+    let mut index = 0;
+    while index < input.len() {
+        if input[index] == b'=' {
+            let rest = &input[index + 1..];
+            if rest.starts_with(b"\r\n") {
+                index += 3;
+                continue;
+            }
+            if rest.starts_with(b"\n") {
+                index += 2;
+                continue;
+            }
+            if let Some(byte) = rest.get(..2).and_then(|hex| std::str::from_utf8(hex).ok()).and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                out.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        out.push(input[index]);
+        index += 1;
+    }
+    out
+}
+
+/// Decodes RFC 2047 encoded words such as `=?UTF-8?B?...?=` in header values.
+pub fn decode_encoded_words(value: &str) -> String {
+    let value = ENCODED_WORD_GAP.replace_all(value, "$1$2");
+    ENCODED_WORD
+        .replace_all(&value, |captures: &regex::Captures| {
+            let bytes = if captures[2].eq_ignore_ascii_case("b") {
+                decode_base64(captures[3].as_bytes())
+            } else {
+                decode_quoted_printable(captures[3].replace('_', " ").as_bytes())
+            };
+            decode_charset(&captures[1], &bytes)
+        })
+        .into_owned()
+}
+
+/// Splits a header block from its body and unfolds continuation lines.
+fn split_part(raw: &[u8]) -> Part {
+    let find = |needle: &[u8]| raw.windows(needle.len()).position(|window| window == needle);
+    let (head_end, body_start) = match (find(b"\r\n\r\n"), find(b"\n\n")) {
+        // A part with no headers starts straight away with the blank line
+        _ if raw.starts_with(b"\r\n") => (0, 2),
+        _ if raw.starts_with(b"\n") => (0, 1),
+        (Some(crlf), Some(lf)) if lf < crlf => (lf, lf + 2),
+        (Some(crlf), _) => (crlf, crlf + 4),
+        (None, Some(lf)) => (lf, lf + 2),
+        (None, None) => (raw.len(), raw.len()),
+    };
+    let head = String::from_utf8_lossy(&raw[..head_end]);
+
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in head.lines() {
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+
+    Part { headers, body: raw[body_start..].to_vec() }
+}
+
+fn trim_line_end(line: &[u8]) -> &[u8] {
+    let end = line.iter().rposition(|byte| !byte.is_ascii_whitespace()).map_or(0, |last| last + 1);
+    &line[..end]
+}
+
+/// Splits a multipart body on its boundary lines. Works on bytes so 8bit and binary parts
+/// come through unchanged; the line break before each boundary belongs to the boundary.
+fn split_multipart(body: &[u8], boundary: &str) -> Vec<Vec<u8>> {
+    let delimiter = format!("--{}", boundary).into_bytes();
+    let mut parts = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    for line in body.split_inclusive(|byte| *byte == b'\n') {
+        let trimmed = trim_line_end(line);
+        let Some(rest) = trimmed.strip_prefix(delimiter.as_slice()) else {
+            if let Some(part) = current.as_mut() {
+                part.extend_from_slice(line);
+            }
+            continue;
+        };
+        if !rest.is_empty() && rest != b"--" {
+            if let Some(part) = current.as_mut() {
+                part.extend_from_slice(line);
+            }
+            continue;
+        }
+        if let Some(mut part) = current.take() {
+            let line_break = if part.ends_with(b"\r\n") { 2 } else { usize::from(part.ends_with(b"\n")) };
+            part.truncate(part.len() - line_break);
+            parts.push(part);
+        }
+        if rest == b"--" {
+            break;
+        }
+        current = Some(Vec::new());
+    }
+    parts
+}
+
+#[derive(Default)]
+struct Collected {
+    html: Option<String>,
+    text: Option<String>,
+    attachments: Vec<ParsedAttachment>,
+}
+
+fn collect(part: &Part, collected: &mut Collected) {
+    let content_type = part.content_type();
+    if content_type.starts_with("multipart/") {
+        if let Some(boundary) = part.parameter("Content-Type", "boundary") {
+            for raw in split_multipart(&part.body, &boundary) {
+                collect(&split_part(&raw), collected);
+            }
+        }
+    } else if part.is_attachment() {
+        collected.attachments.push(ParsedAttachment {
+            file_name: part.file_name().unwrap_or_else(|| "attachment.bin".to_string()),
+            mime_type: content_type,
+            content: part.decoded_body(),
+        });
+    } else if content_type == "text/html" && collected.html.is_none() {
+        collected.html = Some(part.text());
+    } else if content_type == "text/plain" && collected.text.is_none() {
+        collected.text = Some(part.text());
+    }
+}
+
+fn received_hops(part: &Part) -> Vec<ReceivedHop> {
+    // Received lines are prepended by each relay, so the file lists them newest first
+    let mut hops: Vec<ReceivedHop> = part
+        .headers_named("Received")
+        .filter_map(|value| {
+            let captures = RECEIVED.captures(value)?;
+            Some(ReceivedHop {
+                from_host: captures[1].to_string(),
+                from_ip: captures[2].to_string(),
+                by_host: captures[3].trim_end_matches(';').to_string(),
+                delay_seconds: 0,
+            })
+        })
+        .collect();
+    hops.reverse();
+    let count = hops.len() as i64;
+    for (index, hop) in hops.iter_mut().enumerate() {
+        hop.delay_seconds = count - index as i64;
+    }
+    hops
+}
+
+fn auth_result(value: &str) -> AuthResult {
+    match value.to_ascii_lowercase().as_str() {
+        "pass" => AuthResult::Pass,
+        "fail" | "hardfail" | "permerror" => AuthResult::Fail,
+        "softfail" => AuthResult::SoftFail,
+        "neutral" => AuthResult::Neutral,
+        _ => AuthResult::None,
+    }
+}
+
+fn headers_of(part: &Part) -> EmailHeaders {
+    let address = |name: &str| part.header(name).map(|value| decode_encoded_words(value).trim_matches(['<', '>', ' ']).to_string());
+    let mut headers = EmailHeaders {
+        to: part.header("To").map(decode_encoded_words),
+        reply_to: part.header("Reply-To").map(decode_encoded_words),
+        return_path: address("Return-Path"),
+        message_id: part.header("Message-ID").map(str::to_string),
+        in_reply_to: part.header("In-Reply-To").map(str::to_string),
+        x_mailer: part.header("X-Mailer").or_else(|| part.header("User-Agent")).map(str::to_string),
+        received: received_hops(part),
+        ..Default::default()
+    };
+    for value in part.headers_named("Authentication-Results") {
+        for captures in AUTH_RESULT.captures_iter(value) {
+            let result = auth_result(&captures[2]);
+            match captures[1].to_ascii_lowercase().as_str() {
+                "spf" => headers.spf = result,
+                "dkim" => headers.dkim = result,
+                _ => headers.dmarc = result,
+            }
+        }
+    }
+    headers
+}
+
+/// Parses one message. Plain-text bodies are converted to HTML, and HTML bodies are
+/// sanitised since real samples often carry scripts.
+pub fn parse_message(raw: &[u8]) -> Result<ParsedMessage, String> {
+    let part = split_part(raw);
+    let from_user = part.header("From").map(decode_encoded_words).ok_or_else(|| "Message has no From header".to_string())?;
+
+    let mut collected = Collected::default();
+    collect(&part, &mut collected);
+    let body = match (collected.html, collected.text) {
+        (Some(html_body), _) => html::sanitize_html(&html_body),
+        (None, Some(text)) => format!("<p>{}</p>", html::escape(text.trim()).replace("\r\n", "\n").replace('\n', "<br>")),
+        (None, None) => String::new(),
+    };
+
+    Ok(ParsedMessage {
+        from_user,
+        subject: part.header("Subject").map(decode_encoded_words).unwrap_or_else(|| "(no subject)".to_string()),
+        body,
+        date: part
+            .header("Date")
+            .and_then(|value| chrono::DateTime::parse_from_rfc2822(value.trim()).ok())
+            .map(|date| date.naive_utc()),
+        headers: headers_of(&part),
+        attachments: collected.attachments,
+    })
+}
+
+/// Splits an mbox archive into raw messages, undoing `>From ` quoting. Works on bytes so
+/// messages in other charsets or with 8bit parts are not mangled.
+pub fn split_mbox(raw: &[u8]) -> Vec<Vec<u8>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+    let mut previous_blank = true;
+    for line in raw.split_inclusive(|byte| *byte == b'\n') {
+        if previous_blank && line.starts_with(b"From ") {
+            if let Some(message) = current.take() {
+                messages.push(message);
+            }
+            current = Some(Vec::new());
+        } else if let Some(message) = current.as_mut() {
+            let quotes = line.iter().take_while(|byte| **byte == b'>').count();
+            let unquoted = quotes > 0 && line[quotes..].starts_with(b"From ");
+            message.extend_from_slice(if unquoted { &line[1..] } else { line });
+        }
+        previous_blank = trim_line_end(line).is_empty();
+    }
+    if let Some(message) = current {
+        messages.push(message);
+    }
+    messages
+}
+
+/// Whether the file looks like an mbox archive rather than a single message.
+pub fn is_mbox(raw: &[u8]) -> bool {
+    raw.starts_with(b"From ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_parts_survive_multipart_splitting() {
+        let mut raw = b"From: Alice <alice@example.com>\r\nSubject: Scan\r\nContent-Type: multipart/mixed; boundary=\"b1\"\r\n\r\n\
+            preamble\r\n--b1\r\nContent-Type: text/plain\r\n\r\nSee attached.\r\n--b1\r\n\
+            Content-Type: application/octet-stream\r\nContent-Disposition: attachment; filename=\"blob.bin\"\r\n\
+            Content-Transfer-Encoding: binary\r\n\r\n"
+            .to_vec();
+        let blob: Vec<u8> = vec![0xff, 0xfe, 0x00, 0x80, b'\n', 0xc3, 0x28, b'-', b'-'];
+        raw.extend_from_slice(&blob);
+        raw.extend_from_slice(b"\r\n--b1--\r\nepilogue\r\n");
+
+        let message = parse_message(&raw).unwrap();
+        assert_eq!(message.subject, "Scan");
+        assert!(message.body.contains("See attached."));
+        assert_eq!(message.attachments.len(), 1);
+        assert_eq!(message.attachments[0].file_name, "blob.bin");
+        assert_eq!(message.attachments[0].content, blob);
+    }
+
+    #[test]
+    fn lines_that_only_start_with_the_boundary_are_content() {
+        let parts = split_multipart(b"--b1\nA\n--b1x is not a boundary\n--b1--\n", "b1");
+        assert_eq!(parts, vec![b"A\n--b1x is not a boundary".to_vec()]);
+    }
+
+    #[test]
+    fn mbox_is_split_and_unquoted_bytewise() {
+        let raw = b"From a@x Mon Jan  1 00:00:00 2024\nSubject: one\n\nHi \xe9\n>From the start\n\nFrom b@x Mon Jan  1 00:00:00 2024\nSubject: two\n\nBye\n";
+        let messages = split_mbox(raw);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0], b"Subject: one\n\nHi \xe9\nFrom the start\n\n".to_vec());
+        assert_eq!(messages[1], b"Subject: two\n\nBye\n".to_vec());
+        assert!(is_mbox(raw));
+    }
+}
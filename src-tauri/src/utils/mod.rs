@@ -1,2 +1,17 @@
 pub mod crypto;
-pub mod generators;
\ No newline at end of file
+pub mod generators;
+pub mod pricing;
+pub mod email_generator;
+pub mod rfc5322;
+pub mod links;
+pub mod attachments;
+pub mod email_grading;
+pub mod spam_filter;
+pub mod html;
+pub mod threading;
+pub mod mime;
+pub mod redact;
+pub mod campaign;
+pub mod deep_link;
+pub mod flags;
+pub mod task_graph;
\ No newline at end of file
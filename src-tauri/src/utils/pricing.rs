@@ -0,0 +1,203 @@
+use crate::models::service::{PriceQuote, RefundPolicy};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+
+pub struct AppliedCoupon {
+    pub id: i32,
+    pub code: String,
+    pub discount: Decimal,
+}
+
+pub fn percent_of(amount: Decimal, percent: i32) -> Decimal {
+    (amount * Decimal::from(percent) / Decimal::from(100)).round_dp(2)
+}
+
+/// What a coupon takes off `amount`: its percentage if it has one, otherwise its flat amount.
+/// Never more than `amount` itself.
+pub fn coupon_discount(amount: Decimal, percent_off: Option<i32>, amount_off: Option<i32>) -> Decimal {
+    let discount = match (percent_off, amount_off) {
+        (Some(percent), _) => percent_of(amount, percent),
+        (None, Some(flat)) => Decimal::from(flat),
+        (None, None) => Decimal::ZERO,
+    };
+    discount.min(amount)
+}
+
+/// Decimal breakdown of a price; converted to a `PriceQuote` for the webview.
+pub struct Pricing {
+    pub base_price: Decimal,
+    pub sale_discount: Decimal,
+    pub sale_name: Option<String>,
+    pub coupon: Option<AppliedCoupon>,
+}
+
+impl Pricing {
+    /// Bundles are priced below the sum of their services; the difference shows as a sale.
+    pub fn bundle(catalog_prices: &[Decimal], bundle_price: Decimal, coupon: Option<AppliedCoupon>) -> Self {
+        let catalog_total: Decimal = catalog_prices.iter().sum();
+        Pricing {
+            base_price: catalog_total.max(bundle_price),
+            sale_discount: (catalog_total - bundle_price).max(Decimal::ZERO),
+            sale_name: Some("Bundle price".to_string()),
+            coupon,
+        }
+    }
+
+    pub fn coupon_discount(&self) -> Decimal {
+        self.coupon.as_ref().map_or(Decimal::ZERO, |c| c.discount)
+    }
+
+    pub fn discount(&self) -> Decimal {
+        self.sale_discount + self.coupon_discount()
+    }
+
+    pub fn final_price(&self) -> Decimal {
+        (self.base_price - self.discount()).max(Decimal::ZERO)
+    }
+
+    pub fn discount_source(&self) -> Option<String> {
+        let mut parts = Vec::new();
+        if let (Some(sale), true) = (&self.sale_name, self.sale_discount > Decimal::ZERO) {
+            parts.push(format!("Sale: {}", sale));
+        }
+        if let Some(coupon) = &self.coupon {
+            parts.push(format!("Coupon: {}", coupon.code));
+        }
+        if parts.is_empty() { None } else { Some(parts.join("; ")) }
+    }
+
+    pub fn to_quote(&self, service_id: Option<i32>, bundle_id: Option<i32>) -> PriceQuote {
+        PriceQuote {
+            service_id,
+            bundle_id,
+            base_price: self.base_price.to_f64().unwrap_or(0.0),
+            sale_discount: self.sale_discount.to_f64().unwrap_or(0.0),
+            coupon_discount: self.coupon_discount().to_f64().unwrap_or(0.0),
+            final_price: self.final_price().to_f64().unwrap_or(0.0),
+            sale_name: self.sale_name.clone(),
+            coupon_code: self.coupon.as_ref().map(|c| c.code.clone()),
+        }
+    }
+}
+
+/// Splits the paid price of a bundle across its services in proportion to their catalog
+/// prices. The last service takes the rounding remainder, so the shares add up to `price`.
+pub fn split_bundle_price(price: Decimal, catalog_prices: &[Decimal]) -> Vec<Decimal> {
+    let catalog_total: Decimal = catalog_prices.iter().sum();
+    let mut remaining = price;
+    let mut shares = Vec::with_capacity(catalog_prices.len());
+    for (index, base_price) in catalog_prices.iter().enumerate() {
+        let share = if index + 1 == catalog_prices.len() {
+            remaining
+        } else if catalog_total.is_zero() {
+            Decimal::ZERO
+        } else {
+            (price * *base_price / catalog_total).round_dp(2)
+        };
+        remaining -= share;
+        shares.push(share);
+    }
+    shares
+}
+
+/// Percentage of the paid price to give back under `policy`, with the reason shown to the user.
+pub fn refund_percent(policy: &RefundPolicy, used: bool, minutes_since_purchase: i64) -> Result<(i32, String), String> {
+    if used && !policy.refund_if_used {
+        return Err("Services cannot be refunded once they have been used".to_string());
+    }
+
+    let full_window = i64::from(policy.window_hours) * 60;
+    let partial_window = i64::from(policy.partial_window_hours) * 60;
+
+    if used {
+        Ok((policy.partial_refund_percent, "Partial refund: the service has been used".to_string()))
+    } else if minutes_since_purchase <= full_window {
+        Ok((100, format!("Full refund within {} hours of purchase", policy.window_hours)))
+    } else if minutes_since_purchase <= partial_window {
+        Ok((policy.partial_refund_percent, format!("Partial refund after {} hours", policy.window_hours)))
+    } else {
+        Err("The refund window for this purchase has closed".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coupon(discount: i64) -> Option<AppliedCoupon> {
+        Some(AppliedCoupon { id: 1, code: "SAVE".to_string(), discount: Decimal::from(discount) })
+    }
+
+    #[test]
+    fn percentages_round_to_cents() {
+        assert_eq!(percent_of(Decimal::from(99), 15), Decimal::new(1485, 2));
+        assert_eq!(percent_of(Decimal::new(1999, 2), 33), Decimal::new(660, 2));
+    }
+
+    #[test]
+    fn coupons_prefer_percent_and_never_exceed_the_amount() {
+        let amount = Decimal::from(40);
+        assert_eq!(coupon_discount(amount, Some(25), Some(30)), Decimal::from(10));
+        assert_eq!(coupon_discount(amount, None, Some(15)), Decimal::from(15));
+        assert_eq!(coupon_discount(amount, None, Some(60)), amount);
+        assert_eq!(coupon_discount(amount, None, None), Decimal::ZERO);
+    }
+
+    #[test]
+    fn sale_and_coupon_stack_down_to_a_zero_floor() {
+        let pricing = Pricing { base_price: Decimal::from(100), sale_discount: Decimal::from(20), sale_name: Some("Spring".to_string()), coupon: coupon(10) };
+        assert_eq!(pricing.final_price(), Decimal::from(70));
+        assert_eq!(pricing.discount_source().as_deref(), Some("Sale: Spring; Coupon: SAVE"));
+
+        let free = Pricing { base_price: Decimal::from(10), sale_discount: Decimal::from(5), sale_name: None, coupon: coupon(50) };
+        assert_eq!(free.final_price(), Decimal::ZERO);
+    }
+
+    #[test]
+    fn no_discount_has_no_source() {
+        let pricing = Pricing { base_price: Decimal::from(30), sale_discount: Decimal::ZERO, sale_name: Some("Spring".to_string()), coupon: None };
+        assert_eq!(pricing.final_price(), Decimal::from(30));
+        assert_eq!(pricing.discount_source(), None);
+    }
+
+    #[test]
+    fn bundles_show_their_saving_as_a_sale() {
+        let prices = [Decimal::from(30), Decimal::from(50)];
+        let pricing = Pricing::bundle(&prices, Decimal::from(60), coupon(5));
+        assert_eq!(pricing.base_price, Decimal::from(80));
+        assert_eq!(pricing.sale_discount, Decimal::from(20));
+        assert_eq!(pricing.final_price(), Decimal::from(55));
+
+        // A bundle dearer than its parts is charged at the bundle price with no saving
+        let pricing = Pricing::bundle(&prices, Decimal::from(90), None);
+        assert_eq!((pricing.base_price, pricing.sale_discount), (Decimal::from(90), Decimal::ZERO));
+    }
+
+    #[test]
+    fn bundle_shares_add_up_to_the_price() {
+        let shares = split_bundle_price(Decimal::from(100), &[Decimal::from(10), Decimal::from(10), Decimal::from(10)]);
+        assert_eq!(shares, vec![Decimal::new(3333, 2), Decimal::new(3333, 2), Decimal::new(3334, 2)]);
+        assert_eq!(split_bundle_price(Decimal::from(5), &[Decimal::ZERO, Decimal::ZERO]), vec![Decimal::ZERO, Decimal::from(5)]);
+    }
+
+    fn policy(refund_if_used: bool) -> RefundPolicy {
+        RefundPolicy { window_hours: 24, partial_window_hours: 72, partial_refund_percent: 50, refund_if_used }
+    }
+
+    #[test]
+    fn refunds_are_full_then_partial_then_closed() {
+        let policy = policy(false);
+        assert_eq!(refund_percent(&policy, false, 0).unwrap().0, 100);
+        assert_eq!(refund_percent(&policy, false, 24 * 60).unwrap().0, 100);
+        assert_eq!(refund_percent(&policy, false, 24 * 60 + 1).unwrap().0, 50);
+        assert_eq!(refund_percent(&policy, false, 72 * 60).unwrap().0, 50);
+        assert!(refund_percent(&policy, false, 72 * 60 + 1).is_err());
+    }
+
+    #[test]
+    fn used_services_get_a_partial_refund_only_if_the_policy_allows() {
+        assert!(refund_percent(&policy(false), true, 0).is_err());
+        assert_eq!(refund_percent(&policy(true), true, 0).unwrap().0, 50);
+        assert_eq!(refund_percent(&policy(true), true, 48 * 60).unwrap().0, 50);
+    }
+}
@@ -0,0 +1,100 @@
+use crate::utils::links::TRUSTED_DOMAINS;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::HashMap;
+
+static ADDRESS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\b([a-z0-9._%+-]+)@([a-z0-9-]+(?:\.[a-z0-9-]+)+)\b").unwrap());
+static URL_HOST: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?i)\b(https?://)([a-z0-9-]+(?:\.[a-z0-9-]+)+)").unwrap());
+
+/// Replaces real mailbox names and domains with stable placeholders, so the same address
+/// always maps to the same stand-in across a whole import. Simulated domains are kept.
+#[derive(Default)]
+pub struct Redactor {
+    domains: HashMap<String, String>,
+    mailboxes: HashMap<String, String>,
+}
+
+fn is_simulated(domain: &str) -> bool {
+    domain.ends_with(".fake") || domain.ends_with(".example") || TRUSTED_DOMAINS.iter().any(|trusted| domain == *trusted || domain.ends_with(&format!(".{}", trusted)))
+}
+
+impl Redactor {
+    fn domain(&mut self, domain: &str) -> String {
+        let domain = domain.to_lowercase();
+        if is_simulated(&domain) {
+            return domain;
+        }
+        let next = self.domains.len() + 1;
+        self.domains.entry(domain).or_insert_with(|| format!("redacted-{}.example", next)).clone()
+    }
+
+    fn mailbox(&mut self, address: &str, domain: &str) -> String {
+        if is_simulated(&domain.to_lowercase()) {
+            return address.to_string();
+        }
+        let key = address.to_lowercase();
+        let domain = self.domain(domain);
+        let next = self.mailboxes.len() + 1;
+        self.mailboxes.entry(key).or_insert_with(|| format!("user{}@{}", next, domain)).clone()
+    }
+
+    pub fn redact(&mut self, text: &str) -> String {
+        let text = ADDRESS.replace_all(text, |captures: &regex::Captures| self.mailbox(&captures[0], &captures[2]));
+        URL_HOST
+            .replace_all(&text, |captures: &regex::Captures| format!("{}{}", &captures[1], self.domain(&captures[2])))
+            .into_owned()
+    }
+
+    /// Redacts a bare host name such as a relay in a `Received` line.
+    pub fn redact_host(&mut self, host: &str) -> String {
+        if host.contains('.') {
+            self.domain(host)
+        } else {
+            host.to_string()
+        }
+    }
+
+    pub fn redact_option(&mut self, text: &Option<String>) -> Option<String> {
+        text.as_deref().map(|text| self.redact(text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn addresses_map_to_stable_placeholders() {
+        let mut redactor = Redactor::default();
+        assert_eq!(
+            redactor.redact("From bob@acme.com, cc Bob@ACME.com and carol@acme.com"),
+            "From user1@redacted-1.example, cc user1@redacted-1.example and user2@redacted-1.example"
+        );
+        // The mapping holds across calls, as it must for a whole import
+        assert_eq!(redactor.redact("bob@acme.com"), "user1@redacted-1.example");
+    }
+
+    #[test]
+    fn url_hosts_share_the_address_mapping() {
+        let mut redactor = Redactor::default();
+        assert_eq!(
+            redactor.redact("Write to it@portal.acme.com or visit https://portal.acme.com/login"),
+            "Write to user1@redacted-1.example or visit https://redacted-1.example/login"
+        );
+    }
+
+    #[test]
+    fn simulated_domains_are_kept() {
+        let mut redactor = Redactor::default();
+        let text = "alerts@cyberbank.fake sent https://www.cybox.app/help to test@mail.example";
+        assert_eq!(redactor.redact(text), text);
+    }
+
+    #[test]
+    fn hosts_without_a_domain_are_kept() {
+        let mut redactor = Redactor::default();
+        assert_eq!(redactor.redact_host("mx.acme.com"), "redacted-1.example");
+        assert_eq!(redactor.redact_host("localhost"), "localhost");
+        assert_eq!(redactor.redact_option(&None), None);
+    }
+}
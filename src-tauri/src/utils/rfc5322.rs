@@ -0,0 +1,174 @@
+use crate::models::mail::Email;
+use chrono::{Duration, NaiveDateTime};
+
+const CRLF: &str = "\r\n";
+
+fn format_date(date: NaiveDateTime) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S +0000").to_string()
+}
+
+fn domain_of(address: &str) -> &str {
+    address.trim_end_matches('>').rsplit('@').next().unwrap_or(address)
+}
+
+/// RFC 2047 Q-encoding for header values that are not plain ASCII.
+fn encode_header_value(value: &str) -> String {
+    if value.is_ascii() {
+        return value.to_string();
+    }
+    let mut encoded = String::from("=?UTF-8?Q?");
+    for byte in value.bytes() {
+        match byte {
+            b' ' => encoded.push('_'),
+            b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'!' | b'*' | b'+' | b'-' | b'/' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("={:02X}", byte)),
+        }
+    }
+    encoded.push_str("?=");
+    encoded
+}
+
+/// Quoted-printable body with CRLF line endings and soft breaks at 76 characters.
+fn encode_quoted_printable(body: &str) -> String {
+    let mut lines = Vec::new();
+    for line in body.lines() {
+        let bytes = line.as_bytes();
+        let mut out = String::new();
+        let mut width = 0;
+        for (index, &byte) in bytes.iter().enumerate() {
+            let is_last = index + 1 == bytes.len();
+            let token = match byte {
+                b'=' => "=3D".to_string(),
+                b' ' | b'\t' if is_last => format!("={:02X}", byte),
+                b' ' | b'\t' | 33..=126 => (byte as char).to_string(),
+                _ => format!("={:02X}", byte),
+            };
+            if width + token.len() > 75 {
+                out.push('=');
+                out.push_str(CRLF);
+                width = 0;
+            }
+            width += token.len();
+            out.push_str(&token);
+        }
+        lines.push(out);
+    }
+    lines.join(CRLF)
+}
+
+/// Renders the complete message source as the recipient's mail server would have stored it.
+pub fn render_message(email: &Email, recipient: &str) -> String {
+    let headers = &email.headers;
+    let mut out = Vec::new();
+
+    if let Some(return_path) = &headers.return_path {
+        out.push(format!("Return-Path: <{}>", return_path));
+    }
+
+    // Each relay prepends its Received line, so the newest hop comes first
+    for hop in headers.received.iter().rev() {
+        out.push(format!(
+            "Received: from {} ({} [{}]){crlf}\tby {} with ESMTP;{crlf}\t{}",
+            hop.from_host,
+            hop.from_host,
+            hop.from_ip,
+            hop.by_host,
+            format_date(email.created_at - Duration::seconds(hop.delay_seconds)),
+            crlf = CRLF,
+        ));
+    }
+
+    let mail_from = headers.return_path.as_deref().unwrap_or(&email.from_user);
+    out.push(format!(
+        "Authentication-Results: mx.cybox.app;{crlf}\tspf={} smtp.mailfrom={};{crlf}\tdkim={} header.d={};{crlf}\tdmarc={} header.from={}",
+        headers.spf.as_str(),
+        domain_of(mail_from),
+        headers.dkim.as_str(),
+        domain_of(&email.from_user),
+        headers.dmarc.as_str(),
+        domain_of(&email.from_user),
+        crlf = CRLF,
+    ));
+
+    let message_id = headers
+        .message_id
+        .clone()
+        .unwrap_or_else(|| format!("<{}.{}@cybox.app>", email.id, email.created_at.and_utc().timestamp()));
+    out.push(format!("Message-ID: {}", message_id));
+    if let Some(in_reply_to) = &headers.in_reply_to {
+        out.push(format!("In-Reply-To: {}", in_reply_to));
+    }
+    out.push(format!("Date: {}", format_date(email.created_at)));
+    out.push(format!("From: {}", email.from_user));
+    out.push(format!("To: {}", headers.to.as_deref().unwrap_or(recipient)));
+    if let Some(reply_to) = &headers.reply_to {
+        out.push(format!("Reply-To: {}", reply_to));
+    }
+    out.push(format!("Subject: {}", encode_header_value(&email.subject)));
+    if let Some(mailer) = &headers.x_mailer {
+        out.push(format!("X-Mailer: {}", mailer));
+    }
+    out.push("MIME-Version: 1.0".to_string());
+    out.push("Content-Type: text/html; charset=\"utf-8\"".to_string());
+    out.push("Content-Transfer-Encoding: quoted-printable".to_string());
+
+    format!("{}{crlf}{crlf}{}{crlf}", out.join(CRLF), encode_quoted_printable(&email.body), crlf = CRLF)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::mail::{EmailHeaders, MailFolder};
+
+    fn email(body: &str) -> Email {
+        Email {
+            id: 7,
+            user_id: 1,
+            universal_email_id: None,
+            is_read: false,
+            classification: "none".to_string(),
+            created_at: NaiveDateTime::parse_from_str("2024-01-02 03:04:05", "%Y-%m-%d %H:%M:%S").unwrap(),
+            from_user: "alerts@bank.example".to_string(),
+            subject: "Café au lait".to_string(),
+            body: body.to_string(),
+            headers: EmailHeaders::spoofed("relay.example", "203.0.113.9", "abc"),
+            folder: MailFolder::Inbox,
+            labels: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn non_ascii_headers_are_q_encoded() {
+        assert_eq!(encode_header_value("Hello World"), "Hello World");
+        assert_eq!(encode_header_value("Café au"), "=?UTF-8?Q?Caf=C3=A9_au?=");
+    }
+
+    #[test]
+    fn quoted_printable_escapes_and_soft_breaks() {
+        assert_eq!(encode_quoted_printable("a=b \nc"), "a=3Db=20\r\nc");
+        let encoded = encode_quoted_printable(&"x".repeat(100));
+        assert_eq!(encoded, format!("{}=\r\n{}", "x".repeat(75), "x".repeat(25)));
+        assert!(encoded.split(CRLF).all(|line| line.len() <= 76));
+    }
+
+    #[test]
+    fn received_lines_are_newest_first_and_folded() {
+        let source = render_message(&email("hi"), "alice@cybox.app");
+        let newest = source.find("Received: from relay.example").unwrap();
+        let oldest = source.find("Received: from localhost").unwrap();
+        assert!(newest < oldest);
+        assert!(source.contains("\r\n\tby mx.cybox.app with ESMTP;\r\n\tTue, 02 Jan 2024 03:04:02 +0000"));
+    }
+
+    #[test]
+    fn renders_a_complete_crlf_message() {
+        let source = render_message(&email("line one\nline two"), "alice@cybox.app");
+        assert!(source.contains("spf=fail smtp.mailfrom=relay.example;"));
+        assert!(source.contains("dmarc=fail header.from=bank.example"));
+        assert!(source.contains("\r\nDate: Tue, 02 Jan 2024 03:04:05 +0000\r\n"));
+        assert!(source.contains("\r\nTo: alice@cybox.app\r\n"));
+        assert!(source.contains("\r\nSubject: =?UTF-8?Q?Caf=C3=A9_au_lait?=\r\n"));
+        assert!(source.ends_with("quoted-printable\r\n\r\nline one\r\nline two\r\n"));
+        assert!(source.match_indices('\n').all(|(index, _)| source.as_bytes()[index - 1] == b'\r'));
+    }
+}
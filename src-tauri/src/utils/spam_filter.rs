@@ -0,0 +1,257 @@
+use crate::models::mail::{AuthResult, Email, EmailLabel};
+use crate::utils::links;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+
+static TAG: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<[^>]*>").unwrap());
+static WORD: Lazy<Regex> = Lazy::new(|| Regex::new(r"[a-z0-9$£€']{3,20}").unwrap());
+
+const LABELS: [EmailLabel; 3] = [EmailLabel::Legitimate, EmailLabel::Spam, EmailLabel::Phishing];
+
+/// The user's own decisions outweigh the bundled corpus.
+pub const USER_EXAMPLE_WEIGHT: f64 = 3.0;
+
+/// Bundled examples every user's filter starts from: (label, sender, subject and body).
+const BASE_CORPUS: &[(EmailLabel, &str, &str)] = &[
+    (EmailLabel::Legitimate, "no-reply@cyberbank.fake", "Your monthly statement is ready. View it any time by signing in as usual."),
+    (EmailLabel::Legitimate, "support@securenet.fake", "Your support ticket has been resolved. Reply to this email if you need anything else."),
+    (EmailLabel::Legitimate, "team@cybox.app", "Welcome to Cybox. Your next lesson covers password managers and two-factor authentication."),
+    (EmailLabel::Legitimate, "no-reply@parcelpoint.fake", "Your parcel was delivered today at 14:02 and signed for at the front door."),
+    (EmailLabel::Legitimate, "billing@payflow.fake", "Receipt for your payment of $12.99. No action is needed."),
+    (EmailLabel::Legitimate, "alex@cybox.app", "Notes from today's meeting are attached to the shared project page. See you tomorrow."),
+    (EmailLabel::Spam, "deals@megasavings.fake", "Limited time offer! Buy now and save 80% on luxury watches. Unsubscribe here."),
+    (EmailLabel::Spam, "winner@lucky-draw.fake", "Congratulations you are a winner! Claim your free prize now, no purchase necessary."),
+    (EmailLabel::Spam, "promo@best-offers.fake", "Cheap pills, cheap loans, amazing deals. Act now to get free bonus points."),
+    (EmailLabel::Spam, "rewards@giftzone.fake", "Exclusive gift card giveaway! You have been selected. Click to claim your reward."),
+    (EmailLabel::Spam, "news@crypto-gains.fake", "Make $5000 a week from home with this one crypto trick. Guaranteed profit."),
+    (EmailLabel::Phishing, "security@cyberbank-secure.fake", "Unusual sign-in detected. Verify your password immediately or your account will be suspended."),
+    (EmailLabel::Phishing, "support@paypf1ow.fake", "Your payment was declined. Update your card number and CVC within 24 hours to avoid closure."),
+    (EmailLabel::Phishing, "admin@cybox-verify.fake", "Dear user, your mailbox is full. Log in to confirm your credentials or lose access."),
+    (EmailLabel::Phishing, "it-helpdesk@securenet-alerts.fake", "Dear customer, confirm your account details now. Failure to act will result in suspension."),
+    (EmailLabel::Phishing, "hr@payroll-update.fake", "Open the attached invoice and enable content to view your updated salary details urgently."),
+];
+
+fn domain_of(address: &str) -> &str {
+    address.rsplit('@').next().unwrap_or(address).trim_end_matches('>')
+}
+
+fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    WORD.find_iter(text).map(|m| m.as_str().to_string())
+}
+
+fn base_tokens(from_user: &str, text: &str) -> HashSet<String> {
+    let text = TAG.replace_all(text, " ").to_lowercase();
+    let mut tokens: HashSet<String> = words(&text).collect();
+    tokens.insert(format!("from:{}", domain_of(from_user).to_lowercase()));
+    tokens
+}
+
+/// Words plus structural features: sender domain, link targets and red flags, and
+/// authentication results.
+pub fn tokenize(email: &Email) -> HashSet<String> {
+    let mut tokens = base_tokens(&email.from_user, &format!("{} {}", email.subject, email.body));
+    for link in links::inspect_links(&email.body) {
+        if let Some(host) = link.host {
+            tokens.insert(format!("host:{}", host));
+        }
+        for flag in link.flags {
+            tokens.insert(format!("flag:{:?}", flag).to_lowercase());
+        }
+    }
+    for (check, result) in [("spf", email.headers.spf), ("dkim", email.headers.dkim), ("dmarc", email.headers.dmarc)] {
+        if result != AuthResult::None {
+            tokens.insert(format!("{}:{}", check, result.as_str()));
+        }
+    }
+    tokens
+}
+
+#[derive(Default)]
+struct ClassCounts {
+    documents: f64,
+    tokens: HashMap<String, f64>,
+    total: f64,
+}
+
+/// A naive-Bayes classifier over legitimate, spam and phishing mail. Emails are token sets, so
+/// a token counts once per email however often it appears, and only tokens an email contains
+/// are scored.
+pub struct SpamFilter {
+    classes: HashMap<EmailLabel, ClassCounts>,
+    vocabulary: HashSet<String>,
+}
+
+pub struct FilterOutcome {
+    pub label: EmailLabel,
+    pub probabilities: HashMap<EmailLabel, f64>,
+    pub evidence: Vec<String>,
+}
+
+impl FilterOutcome {
+    /// Probability the email is spam or phishing.
+    pub fn unwanted(&self) -> f64 {
+        1.0 - self.probabilities[&EmailLabel::Legitimate]
+    }
+}
+
+impl SpamFilter {
+    /// A filter trained on the bundled corpus only.
+    pub fn with_base_corpus() -> Self {
+        let mut filter = SpamFilter {
+            classes: LABELS.iter().map(|label| (*label, ClassCounts::default())).collect(),
+            vocabulary: HashSet::new(),
+        };
+        for (label, from_user, text) in BASE_CORPUS {
+            filter.train(*label, &base_tokens(from_user, text), 1.0);
+        }
+        filter
+    }
+
+    pub fn train(&mut self, label: EmailLabel, tokens: &HashSet<String>, weight: f64) {
+        let class = self.classes.entry(label).or_default();
+        class.documents += weight;
+        for token in tokens {
+            *class.tokens.entry(token.clone()).or_insert(0.0) += weight;
+            class.total += weight;
+            self.vocabulary.insert(token.clone());
+        }
+    }
+
+    // Laplace-smoothed log P(token | label)
+    fn token_log_likelihood(&self, label: EmailLabel, token: &str) -> f64 {
+        let class = &self.classes[&label];
+        let count = class.tokens.get(token).copied().unwrap_or(0.0);
+        ((count + 1.0) / (class.total + self.vocabulary.len() as f64)).ln()
+    }
+
+    pub fn classify(&self, tokens: &HashSet<String>) -> FilterOutcome {
+        let documents: f64 = self.classes.values().map(|class| class.documents).sum();
+        // Tokens never seen in training carry no information
+        let known: Vec<&String> = tokens.iter().filter(|token| self.vocabulary.contains(*token)).collect();
+
+        let log_scores: HashMap<EmailLabel, f64> = LABELS
+            .iter()
+            .map(|label| {
+                let prior = ((self.classes[label].documents + 1.0) / (documents + LABELS.len() as f64)).ln();
+                let likelihood: f64 = known.iter().map(|token| self.token_log_likelihood(*label, token)).sum();
+                (*label, prior + likelihood)
+            })
+            .collect();
+
+        let max = log_scores.values().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let normaliser: f64 = log_scores.values().map(|score| (score - max).exp()).sum();
+        let probabilities: HashMap<EmailLabel, f64> =
+            log_scores.iter().map(|(label, score)| (*label, (score - max).exp() / normaliser)).collect();
+        let label = LABELS
+            .iter()
+            .copied()
+            .max_by(|a, b| probabilities[a].total_cmp(&probabilities[b]))
+            .unwrap_or(EmailLabel::Legitimate);
+
+        // The tokens that pushed hardest towards the chosen label over the runner-up labels
+        let mut evidence: Vec<(f64, &String)> = known
+            .iter()
+            .map(|token| {
+                let chosen = self.token_log_likelihood(label, token);
+                let other = LABELS
+                    .iter()
+                    .filter(|other| **other != label)
+                    .map(|other| self.token_log_likelihood(*other, token))
+                    .fold(f64::NEG_INFINITY, f64::max);
+                (chosen - other, *token)
+            })
+            .filter(|(weight, _)| *weight > 0.0)
+            .collect();
+        evidence.sort_by(|a, b| b.0.total_cmp(&a.0).then_with(|| a.1.cmp(b.1)));
+
+        FilterOutcome {
+            label,
+            probabilities,
+            evidence: evidence.into_iter().take(5).map(|(_, token)| token.clone()).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::mail::{EmailHeaders, MailFolder};
+
+    fn email(from_user: &str, subject: &str, body: &str, headers: EmailHeaders) -> Email {
+        Email {
+            id: 1,
+            user_id: 1,
+            universal_email_id: None,
+            is_read: false,
+            classification: "none".to_string(),
+            created_at: chrono::NaiveDateTime::default(),
+            from_user: from_user.to_string(),
+            subject: subject.to_string(),
+            body: body.to_string(),
+            headers,
+            folder: MailFolder::Inbox,
+            labels: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn tokens_include_structure() {
+        let tokens = tokenize(&email(
+            "security@cyberbank-secure.fake",
+            "Verify now",
+            r#"<p>Confirm your <b>password</b>: <a href="http://45.153.160.12/login">https://cyberbank.fake</a></p>"#,
+            EmailHeaders::spoofed("cyberbank-secure.fake", "45.153.160.12", "abc"),
+        ));
+        for token in ["verify", "password", "from:cyberbank-secure.fake", "host:45.153.160.12", "flag:ipaddresslink", "spf:fail"] {
+            assert!(tokens.contains(token), "missing {}", token);
+        }
+        // Markup is not content, and one- and two-letter words are noise
+        assert!(!tokens.contains("href") && !tokens.contains("p"));
+    }
+
+    #[test]
+    fn repeated_words_count_once() {
+        let once = base_tokens("a@b.fake", "prize");
+        let many = base_tokens("a@b.fake", "prize prize prize prize");
+        assert_eq!(once, many);
+    }
+
+    #[test]
+    fn base_corpus_separates_the_classes() {
+        let filter = SpamFilter::with_base_corpus();
+        let phishing = filter.classify(&base_tokens(
+            "alerts@cyberbank-verify.fake",
+            "Unusual sign-in detected. Verify your password immediately or your account will be suspended.",
+        ));
+        assert_eq!(phishing.label, EmailLabel::Phishing);
+        assert!(phishing.unwanted() > 0.5);
+        assert!(phishing.evidence.len() <= 5 && !phishing.evidence.is_empty());
+
+        let spam = filter.classify(&base_tokens("offers@giftzone.fake", "Congratulations winner! Claim your free prize now."));
+        assert_eq!(spam.label, EmailLabel::Spam);
+
+        let legitimate = filter.classify(&base_tokens("no-reply@cyberbank.fake", "Your monthly statement is ready to view."));
+        assert_eq!(legitimate.label, EmailLabel::Legitimate);
+        let total: f64 = legitimate.probabilities.values().sum();
+        assert!((total - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn unknown_tokens_leave_the_prior() {
+        let filter = SpamFilter::with_base_corpus();
+        let outcome = filter.classify(&HashSet::from(["zzzunseen".to_string()]));
+        assert!(outcome.evidence.is_empty());
+        assert_eq!(outcome.probabilities.len(), LABELS.len());
+    }
+
+    #[test]
+    fn user_training_changes_the_verdict() {
+        let mut filter = SpamFilter::with_base_corpus();
+        let tokens = base_tokens("newsletter@garden-club.fake", "Garden club newsletter: seed swap this weekend");
+        for _ in 0..3 {
+            filter.train(EmailLabel::Spam, &tokens, USER_EXAMPLE_WEIGHT);
+        }
+        assert_eq!(filter.classify(&tokens).label, EmailLabel::Spam);
+    }
+}
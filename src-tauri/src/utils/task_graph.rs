@@ -0,0 +1,132 @@
+use crate::models::task::TaskStatus;
+use std::collections::{HashMap, HashSet};
+
+/// Tasks and the prerequisites each one needs completed first. Always acyclic.
+#[derive(Debug, Clone)]
+pub struct TaskGraph {
+    prerequisites: HashMap<u64, Vec<u64>>,
+    /// Every task, each one after all of its prerequisites.
+    order: Vec<u64>,
+}
+
+impl TaskGraph {
+    /// Builds the graph from `(task, prerequisite)` edges, rejecting unknown tasks and cycles.
+    pub fn new(tasks: &[u64], edges: &[(u64, u64)]) -> Result<Self, String> {
+        let mut prerequisites: HashMap<u64, Vec<u64>> = tasks.iter().map(|task| (*task, Vec::new())).collect();
+        for (task, prerequisite) in edges {
+            if !prerequisites.contains_key(prerequisite) {
+                return Err(format!("Task {} requires unknown task {}", task, prerequisite));
+            }
+            prerequisites
+                .get_mut(task)
+                .ok_or_else(|| format!("Prerequisite set for unknown task {}", task))?
+                .push(*prerequisite);
+        }
+
+        let mut graph = TaskGraph { prerequisites, order: Vec::with_capacity(tasks.len()) };
+        let mut done = HashSet::new();
+        let mut path = Vec::new();
+        for task in tasks {
+            graph.visit(*task, &mut done, &mut path)?;
+        }
+        Ok(graph)
+    }
+
+    // Depth-first: a task already on the current path means the prerequisites loop back on themselves
+    fn visit(&mut self, task: u64, done: &mut HashSet<u64>, path: &mut Vec<u64>) -> Result<(), String> {
+        if done.contains(&task) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|on_path| *on_path == task) {
+            let cycle: Vec<String> = path[start..].iter().chain([&task]).map(u64::to_string).collect();
+            return Err(format!("Task prerequisites form a cycle: {}", cycle.join(" -> ")));
+        }
+        path.push(task);
+        for prerequisite in self.prerequisites[&task].clone() {
+            self.visit(prerequisite, done, path)?;
+        }
+        path.pop();
+        done.insert(task);
+        self.order.push(task);
+        Ok(())
+    }
+
+    pub fn prerequisites(&self, task: u64) -> &[u64] {
+        self.prerequisites.get(&task).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// Tasks that list `task` as a direct prerequisite.
+    pub fn dependents(&self, task: u64) -> Vec<u64> {
+        self.order.iter().copied().filter(|other| self.prerequisites(*other).contains(&task)).collect()
+    }
+
+    /// Tasks in an order where each comes after its prerequisites.
+    pub fn order(&self) -> &[u64] {
+        &self.order
+    }
+
+    pub fn is_unlocked(&self, task: u64, completed: &HashSet<u64>) -> bool {
+        self.prerequisites(task).iter().all(|prerequisite| completed.contains(prerequisite))
+    }
+
+    /// A task's status for a user who has completed `completed` and started `started`.
+    pub fn status(&self, task: u64, completed: &HashSet<u64>, started: &HashSet<u64>) -> TaskStatus {
+        if completed.contains(&task) {
+            TaskStatus::Completed
+        } else if !self.is_unlocked(task, completed) {
+            TaskStatus::Locked
+        } else if started.contains(&task) {
+            TaskStatus::InProgress
+        } else {
+            TaskStatus::Available
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(tasks: &[u64]) -> HashSet<u64> {
+        tasks.iter().copied().collect()
+    }
+
+    #[test]
+    fn orders_tasks_after_their_prerequisites() {
+        let graph = TaskGraph::new(&[1, 2, 3], &[(1, 3), (3, 2)]).unwrap();
+        assert_eq!(graph.order(), &[2, 3, 1]);
+    }
+
+    #[test]
+    fn rejects_cycles_and_self_loops() {
+        let cycle = TaskGraph::new(&[1, 2, 3], &[(2, 1), (3, 2), (1, 3)]).unwrap_err();
+        assert!(cycle.contains("cycle"), "{}", cycle);
+        let self_loop = TaskGraph::new(&[1, 2], &[(2, 2)]).unwrap_err();
+        assert!(self_loop.contains("2 -> 2"), "{}", self_loop);
+    }
+
+    #[test]
+    fn rejects_unknown_tasks() {
+        assert!(TaskGraph::new(&[1, 2], &[(2, 9)]).is_err());
+        assert!(TaskGraph::new(&[1, 2], &[(9, 1)]).is_err());
+    }
+
+    #[test]
+    fn status_follows_progress() {
+        let graph = TaskGraph::new(&[1, 2, 3, 4], &[(2, 1), (3, 2), (4, 1)]).unwrap();
+        let completed = set(&[1]);
+        let started = set(&[2, 3]);
+        assert_eq!(graph.status(1, &completed, &started), TaskStatus::Completed);
+        assert_eq!(graph.status(2, &completed, &started), TaskStatus::InProgress);
+        assert_eq!(graph.status(3, &completed, &started), TaskStatus::Locked);
+        assert_eq!(graph.status(4, &completed, &started), TaskStatus::Available);
+    }
+
+    #[test]
+    fn dependents_unlock_once_every_prerequisite_is_complete() {
+        let graph = TaskGraph::new(&[1, 2, 3], &[(3, 1), (3, 2)]).unwrap();
+        assert_eq!(graph.dependents(1), vec![3]);
+        assert!(!graph.is_unlocked(3, &set(&[1])));
+        assert!(graph.is_unlocked(3, &set(&[1, 2])));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::models::mail::{Email, EmailThread};
+use std::collections::HashMap;
+
+/// The subject with any stack of reply and forward prefixes removed, lowercased.
+pub fn normalise_subject(subject: &str) -> String {
+    let mut subject = subject.trim();
+    loop {
+        let lower = subject.to_lowercase();
+        let Some(prefix) = ["re:", "fwd:", "fw:"].iter().find(|prefix| lower.starts_with(*prefix)) else {
+            break;
+        };
+        subject = subject[prefix.len()..].trim_start();
+    }
+    subject.to_lowercase()
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+    parents[index] = root;
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let (a, b) = (find(parents, a), find(parents, b));
+    if a != b {
+        parents[b] = a;
+    }
+}
+
+/// The sender and every listed recipient, lowercased.
+fn participants(email: &Email) -> Vec<String> {
+    let recipients = email.headers.to.as_deref().unwrap_or("").split(',');
+    std::iter::once(email.from_user.as_str())
+        .chain(recipients)
+        .map(|address| address.trim().to_lowercase())
+        .filter(|address| !address.is_empty())
+        .collect()
+}
+
+/// Whether `reply` reads as an answer to `original` without a reply reference: its subject
+/// is prefixed and it comes from someone already in the conversation.
+fn answers(reply: &Email, original: &Email) -> bool {
+    let prefixed = normalise_subject(&reply.subject) != reply.subject.trim().to_lowercase();
+    prefixed && participants(original).contains(&reply.from_user.trim().to_lowercase())
+}
+
+/// Groups emails into conversations. Replies join the thread of the message they answer.
+/// Without a reply reference, a "Re:" or "Fwd:" email joins an email with the same subject
+/// only when its sender took part in that email, so unrelated mail that happens to share a
+/// generic subject stays apart. Threads come back with the most recently active first.
+pub fn thread_emails(emails: Vec<Email>) -> Vec<EmailThread> {
+    let mut parents: Vec<usize> = (0..emails.len()).collect();
+    let mut by_message_id: HashMap<&str, usize> = HashMap::new();
+    let mut by_subject: HashMap<String, Vec<usize>> = HashMap::new();
+
+    for (index, email) in emails.iter().enumerate() {
+        if let Some(message_id) = &email.headers.message_id {
+            by_message_id.insert(message_id.as_str(), index);
+        }
+        let subject = normalise_subject(&email.subject);
+        if !subject.is_empty() {
+            by_subject.entry(subject).or_default().push(index);
+        }
+    }
+    for (index, email) in emails.iter().enumerate() {
+        if let Some(parent) = email.headers.in_reply_to.as_deref().and_then(|id| by_message_id.get(id)) {
+            union(&mut parents, *parent, index);
+        }
+        let same_subject = by_subject.get(&normalise_subject(&email.subject)).map(Vec::as_slice).unwrap_or(&[]);
+        for other in same_subject {
+            if *other != index && answers(email, &emails[*other]) {
+                union(&mut parents, *other, index);
+            }
+        }
+    }
+
+    let roots: Vec<usize> = (0..emails.len()).map(|index| find(&mut parents, index)).collect();
+    let mut groups: HashMap<usize, Vec<Email>> = HashMap::new();
+    for (email, root) in emails.into_iter().zip(roots) {
+        groups.entry(root).or_default().push(email);
+    }
+
+    let mut threads: Vec<EmailThread> = groups
+        .into_values()
+        .map(|mut emails| {
+            emails.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+            let first = &emails[0];
+            let mut participants: Vec<String> = Vec::new();
+            for email in &emails {
+                if !participants.contains(&email.from_user) {
+                    participants.push(email.from_user.clone());
+                }
+            }
+            EmailThread {
+                thread_id: first.headers.message_id.clone().unwrap_or_else(|| format!("email-{}", first.id)),
+                subject: first.subject.clone(),
+                participants,
+                unread: emails.iter().filter(|email| !email.is_read).count() as u32,
+                last_activity: emails.iter().map(|email| email.created_at).max().unwrap_or(first.created_at),
+                emails,
+            }
+        })
+        .collect();
+    threads.sort_by(|a, b| b.last_activity.cmp(&a.last_activity).then_with(|| a.thread_id.cmp(&b.thread_id)));
+    threads
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::mail::{EmailHeaders, MailFolder};
+
+    fn email(id: i32, from_user: &str, to: &str, subject: &str, minute: u32) -> Email {
+        let headers = EmailHeaders {
+            to: Some(to.to_string()),
+            message_id: Some(format!("<{}@cybox.app>", id)),
+            ..EmailHeaders::default()
+        };
+        Email {
+            id,
+            user_id: 1,
+            universal_email_id: None,
+            is_read: true,
+            classification: "none".to_string(),
+            created_at: chrono::NaiveDate::from_ymd_opt(2024, 5, 1).unwrap().and_hms_opt(9, minute, 0).unwrap(),
+            from_user: from_user.to_string(),
+            subject: subject.to_string(),
+            body: String::new(),
+            headers,
+            folder: MailFolder::Inbox,
+            labels: Vec::new(),
+        }
+    }
+
+    fn thread_ids(threads: &[EmailThread]) -> Vec<Vec<i32>> {
+        threads.iter().map(|thread| thread.emails.iter().map(|email| email.id).collect()).collect()
+    }
+
+    #[test]
+    fn prefixes_are_stripped() {
+        assert_eq!(normalise_subject("Re: FW: re:Fwd:  Lunch plans "), "lunch plans");
+        assert_eq!(normalise_subject("Lunch plans"), "lunch plans");
+        assert_eq!(normalise_subject("Regarding lunch"), "regarding lunch");
+        assert_eq!(normalise_subject("Re:"), "");
+    }
+
+    #[test]
+    fn reply_references_join_threads() {
+        let original = email(1, "alice@cybox.app", "bob@cybox.app", "Lunch", 0);
+        let mut reply = email(2, "bob@cybox.app", "alice@cybox.app", "Different subject", 5);
+        reply.headers.in_reply_to = original.headers.message_id.clone();
+        let threads = thread_emails(vec![reply, original]);
+        assert_eq!(thread_ids(&threads), vec![vec![1, 2]]);
+        assert_eq!(threads[0].participants, vec!["alice@cybox.app", "bob@cybox.app"]);
+        assert_eq!(threads[0].thread_id, "<1@cybox.app>");
+    }
+
+    #[test]
+    fn prefixed_replies_from_participants_join_by_subject() {
+        let threads = thread_emails(vec![
+            email(1, "alice@cybox.app", "bob@cybox.app", "Lunch", 0),
+            email(2, "bob@cybox.app", "alice@cybox.app", "Re: Lunch", 5),
+        ]);
+        assert_eq!(thread_ids(&threads), vec![vec![1, 2]]);
+    }
+
+    #[test]
+    fn generic_subjects_from_strangers_stay_apart() {
+        let threads = thread_emails(vec![
+            email(1, "alice@cybox.app", "me@cybox.app", "Hello", 0),
+            email(2, "carol@cybox.app", "me@cybox.app", "Hello", 1),
+            email(3, "dave@cybox.app", "me@cybox.app", "Re: Hello", 2),
+        ]);
+        assert_eq!(threads.len(), 3);
+    }
+
+    #[test]
+    fn threads_are_most_recent_first() {
+        let mut unread = email(3, "carol@cybox.app", "me@cybox.app", "Later", 30);
+        unread.is_read = false;
+        let threads = thread_emails(vec![email(1, "alice@cybox.app", "me@cybox.app", "Earlier", 0), unread]);
+        assert_eq!(thread_ids(&threads), vec![vec![3], vec![1]]);
+        assert_eq!(threads[0].unread, 1);
+    }
+}
@@ -12,14 +12,16 @@ export interface Service {
   id: number;
   name: string;
   description: string | null;
-  price: number | null;
-  category: string | null;
-  features: string | null;
+  price: number;
+  category: 'Security' | 'Antivirus' | 'Utility' | 'Malware';
+  features: { name: string; description: string }[];
+  permissions: { scope: 'network' | 'filesystem' | 'mail' | 'bank'; access: 'read' | 'write' | 'full'; reason: string }[];
+  risk_rating: 'low' | 'medium' | 'high' | 'critical';
   icon_path: string | null;
   version: string | null;
   developer: string | null;
   release_date: string | null;
-  is_important: boolean | null;
+  is_important: boolean;
 }
 
 interface AuthContextType {
@@ -27,7 +29,7 @@ interface AuthContextType {
   login: (userData: User) => void;
   logout: () => void;
   purchasedServices: Service[];
-  purchaseService: (service: Service) => Promise<void>;
+  purchaseService: (service: Service, couponCode?: string) => Promise<void>;
   fetchPurchasedServices: () => Promise<void>;
 }
 
@@ -59,15 +61,16 @@ export const AuthProvider = ({ children }: { children: ReactNode }) => {
     }
   }, [user]);
 
-  const purchaseService = async (service: Service) => {
-    if (!user || service.price === null) {
-      throw new Error("User not logged in or service has no price.");
+  const purchaseService = async (service: Service, couponCode?: string) => {
+    if (!user) {
+      throw new Error("User not logged in.");
     }
     try {
+      // Price is computed by the backend from the catalog
       await invoke('purchase_service', {
         userId: user.id,
         serviceId: service.id,
-        price: service.price,
+        couponCode: couponCode ?? null,
       });
       
       setPurchasedServices(prev => [...prev, service]);
@@ -18,6 +18,7 @@ interface Transaction {
   id: number;
   description: string;
   amount: number;
+  reversed: boolean;
   created_at: string;
 }
 
@@ -25,8 +26,9 @@ const BankDashboard = () => {
   const { user, logout } = useAuth();
   const [account, setAccount] = useState<BankAccount | null>(null);
   const [transactions, setTransactions] = useState<Transaction[]>([]);
+  const [disputeMessage, setDisputeMessage] = useState("");
 
-  useEffect(() => {
+  const refresh = React.useCallback(() => {
     if (user) {
       invoke<BankAccount>("get_bank_details", { userId: user.id })
         .then(setAccount)
@@ -38,6 +40,20 @@ const BankDashboard = () => {
     }
   }, [user]);
 
+  useEffect(() => {
+    refresh();
+  }, [refresh]);
+
+  const handleDispute = (transactionId: number) => {
+    if (!user) return;
+    invoke<string>("dispute_transaction", { userId: user.id, transactionId })
+      .then((message) => {
+        setDisputeMessage(message);
+        refresh();
+      })
+      .catch((err) => setDisputeMessage(typeof err === "string" ? err : "The dispute could not be filed"));
+  };
+
   if (!account) {
     return <div>Loading...</div>; // Or a more sophisticated loading state
   }
@@ -97,6 +113,7 @@ const BankDashboard = () => {
 
       <div className="mt-6">
         <h3 className="text-primary font-semibold mb-2">Transaction History</h3>
+        {disputeMessage && <p className="text-sm text-gray-300 mb-2">{disputeMessage}</p>}
         <ul className="text-sm max-h-40 overflow-y-auto space-y-1">
           {transactions.map((tx) => (
             <li
@@ -105,8 +122,19 @@ const BankDashboard = () => {
                 tx.amount < 0 ? "text-red-400" : "text-green-400"
               }`}
             >
-              <span>{tx.description}</span>
               <span>
+                {tx.description}
+                {tx.reversed && <span className="ml-2 text-gray-400">(reversed)</span>}
+              </span>
+              <span className="flex items-center gap-2">
+                {tx.amount < 0 && !tx.reversed && (
+                  <button
+                    onClick={() => handleDispute(tx.id)}
+                    className="text-xs text-gray-400 hover:text-white"
+                  >
+                    Dispute
+                  </button>
+                )}
                 {tx.amount < 0 ? "-" : "+"}P {Math.abs(tx.amount).toFixed(2)}
               </span>
             </li>
@@ -1,17 +1,11 @@
 'use client';
 
 import React, { useState } from 'react';
-import CryptoJS from 'crypto-js';
-
-const commonPasswords = [
-  'User321',
-  'adminIam230',
-  'mod78mod',
-  'Klein679',
-  'Roco89080',
-];
+import { invoke } from '@tauri-apps/api/core';
+import { useAuth } from '@/Context/AuthContext';
 
 export default function CrackerApp() {
+  const { user } = useAuth();
   const [hash, setHash] = useState('');
   const [result, setResult] = useState('');
   const [isCracking, setIsCracking] = useState(false);
@@ -26,19 +20,12 @@ export default function CrackerApp() {
     setIsCracking(true);
     setResult('');
 
-    setTimeout(() => {
-      for (const password of commonPasswords) {
-        const calculatedHash = CryptoJS.MD5(password).toString();
-        if (calculatedHash === hash) {
-          setResult(`Password found: ${password}`);
-          setIsCracking(false);
-          return;
-        }
-      }
-
-      setResult('Password not found in the common password list.');
-      setIsCracking(false);
-    }, 1000); 
+    invoke<string | null>('crack_md5_hash', { userId: user?.id, hash })
+      .then(password => {
+        setResult(password ? `Password found: ${password}` : 'Password not found in the common password list.');
+      })
+      .catch(err => setResult(String(err)))
+      .finally(() => setIsCracking(false));
   };
 
   return (
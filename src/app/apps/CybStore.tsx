@@ -47,14 +47,7 @@ const ServiceCard = ({ service, onSelect }: { service: Service, onSelect: (servi
 );
 
 const ServiceModal = ({ service, onClose, onBuy, isPurchased }: { service: Service, onClose: () => void, onBuy: (service: Service) => void, isPurchased: boolean }) => {
-  const features = useMemo(() => {
-    try {
-      return service.features ? JSON.parse(service.features) : [];
-    } catch (e) {
-      console.error("Failed to parse service features:", e);
-      return [];
-    }
-  }, [service.features]);
+  const features = service.features;
 
   return (
     <div className="fixed inset-0 bg-black/60 flex items-center justify-center z-50">
@@ -77,8 +70,19 @@ const ServiceModal = ({ service, onClose, onBuy, isPurchased }: { service: Servi
               <li>
                 <strong>Features:</strong>
                 <ul className="list-disc list-inside ml-4 mt-1">
-                  {features.map((feature: string, i: number) => (
-                    <li key={i}>{feature}</li>
+                  {features.map((feature, i) => (
+                    <li key={i}><strong>{feature.name}</strong>: {feature.description}</li>
+                  ))}
+                </ul>
+              </li>
+            )}
+            <li><strong>Risk:</strong> {service.risk_rating}</li>
+            {service.permissions.length > 0 && (
+              <li>
+                <strong>Requested permissions:</strong>
+                <ul className="list-disc list-inside ml-4 mt-1">
+                  {service.permissions.map((permission, i) => (
+                    <li key={i}>{permission.scope} ({permission.access}): {permission.reason}</li>
                   ))}
                 </ul>
               </li>
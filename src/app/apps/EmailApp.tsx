@@ -1,19 +1,48 @@
 'use client'
 import React, { useState, useRef, useEffect } from 'react';
-import { FaInbox, FaPaperPlane, FaTrash, FaEnvelope, FaEnvelopeOpenText, FaExclamationTriangle, FaLink, FaBan } from 'react-icons/fa';
+import { FaInbox, FaPaperPlane, FaTrash, FaEnvelope, FaEnvelopeOpenText, FaExclamationTriangle, FaLink, FaBan, FaShieldAlt, FaFlag, FaUndo, FaTag, FaSearch } from 'react-icons/fa';
 import { invoke } from '@tauri-apps/api/core';
 import { useAuth } from '../../Context/AuthContext';
+import { AppContext } from '../../Context/AppWrapper';
 
 type Email = {
   id: number;
   user_id: number;
-  universal_email_id: number;
+  universal_email_id: number | null;
   is_read: boolean;
   classification: string;
   created_at: string;
   from_user: string;
   subject: string;
   body: string;
+  folder: string;
+  labels: string[];
+};
+
+type LinkOutcome =
+  | { type: 'navigate'; app: string; path: string }
+  | { type: 'login'; app: string }
+  | { type: 'download'; path: string; file_name: string; flags: string[] };
+
+// cybox:// app names and the windows they open
+const LINK_APPS: Record<string, string> = {
+  bank: 'Bank',
+  mail: 'Mail',
+  settings: 'Settings',
+  filemanager: 'Files',
+  files: 'Files',
+  browser: 'Browser',
+  console: 'Console',
+  store: 'CybStore',
+  cybstore: 'CybStore',
+  tasks: 'Task',
+  cracker: 'MD5 Cracker',
+};
+
+type MailLabel = {
+  id: number;
+  name: string;
+  color: string;
 };
 
 export default function EmailApp() {
@@ -23,19 +52,51 @@ export default function EmailApp() {
   const [isComposing, setIsComposing] = useState(false);
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<string | null>(null);
+  const [labels, setLabels] = useState<MailLabel[]>([]);
+  const [searchText, setSearchText] = useState('');
+  const [labelFilter, setLabelFilter] = useState('');
+  const [searchResults, setSearchResults] = useState<Email[] | null>(null);
+  const [loginPage, setLoginPage] = useState<{ clickId: number; app: string } | null>(null);
   const bodyRef = useRef<HTMLTextAreaElement>(null);
   const { user } = useAuth();
+  const appContext = React.useContext(AppContext);
 
   useEffect(() => {
     console.log('EmailApp component mounted');
     if (user) {
       console.log('User is available:', user);
       fetchEmails();
+      fetchLabels();
     } else {
       console.log('User is not available yet');
     }
   }, [user]);
 
+  // Searching runs on the backend so the body text of seeded mail is included
+  useEffect(() => {
+    if (!user || (!searchText.trim() && !labelFilter)) {
+      setSearchResults(null);
+      return;
+    }
+    const timer = setTimeout(() => {
+      invoke<{ emails: Email[] }>('search_emails', {
+        query: { user_id: user.id, text: searchText.trim() || null, folder: activeTab, label: labelFilter || null, page_size: 100 },
+      })
+        .then(page => setSearchResults(page.emails))
+        .catch(error => console.error('Failed to search emails:', error));
+    }, 300);
+    return () => clearTimeout(timer);
+  }, [user, searchText, labelFilter, activeTab, emails]);
+
+  const fetchLabels = async () => {
+    if (!user) return;
+    try {
+      setLabels(await invoke<MailLabel[]>('get_mail_labels', { userId: user.id }));
+    } catch (error) {
+      console.error('Failed to fetch labels:', error);
+    }
+  };
+
   const fetchEmails = async () => {
     if (!user) return;
     console.log('Fetching emails for user:', user.name);
@@ -74,7 +135,8 @@ export default function EmailApp() {
   const deleteEmail = async (id: number) => {
     try {
       await invoke('delete_email', { emailId: id });
-      setEmails(prevEmails => prevEmails.filter(e => e.id !== id));
+      // Deleting moves to the trash; deleting from the trash is permanent
+      setEmails(prevEmails => prevEmails.flatMap(e => e.id !== id ? [e] : e.folder === 'trash' ? [] : [{ ...e, folder: 'trash' }]));
       if (selectedEmail && selectedEmail.id === id) {
         setSelectedEmail(null);
       }
@@ -83,6 +145,42 @@ export default function EmailApp() {
     }
   };
 
+  const restoreEmail = async (id: number) => {
+    try {
+      const folder = await invoke<string>('restore_email', { emailId: id });
+      setEmails(prevEmails => prevEmails.map(e => e.id === id ? { ...e, folder } : e));
+      if (selectedEmail && selectedEmail.id === id) {
+        setSelectedEmail(null);
+      }
+    } catch (error) {
+      console.error('Failed to restore email:', error);
+    }
+  };
+
+  const toggleLabel = async (email: Email, label: MailLabel) => {
+    const applied = !email.labels.includes(label.name);
+    try {
+      await invoke('set_email_label', { emailId: email.id, labelId: label.id, applied });
+      const labelsFor = (e: Email) => applied ? [...e.labels, label.name] : e.labels.filter(name => name !== label.name);
+      setEmails(prevEmails => prevEmails.map(e => e.id === email.id ? { ...e, labels: labelsFor(e) } : e));
+      setSelectedEmail(prev => prev && prev.id === email.id ? { ...prev, labels: labelsFor(prev) } : prev);
+    } catch (error) {
+      console.error('Failed to label email:', error);
+    }
+  };
+
+  const createLabel = async () => {
+    if (!user) return;
+    const name = prompt('Label name');
+    if (!name) return;
+    try {
+      await invoke('create_mail_label', { userId: user.id, name, color: null });
+      fetchLabels();
+    } catch (error) {
+      alert(`Failed to create label: ${error}`);
+    }
+  };
+
   const classifyEmail = async (id: number, classification: 'spam' | 'phishing') => {
     try {
       const newClassification = await invoke('classify_email', { emailId: id, classification });
@@ -97,8 +195,84 @@ export default function EmailApp() {
     }
   };
 
+  const openLinkedApp = (app: string, url: string) => {
+    const appWindow = LINK_APPS[app];
+    if (appWindow) {
+      appContext?.openApp(appWindow);
+    } else {
+      alert(`Nothing in Cybox opens ${url}`);
+    }
+  };
+
+  // Only links clicked inside Cybox are followed; cybox:// is not registered with the OS
+  const handleBodyClick = async (event: React.MouseEvent<HTMLDivElement>) => {
+    const href = (event.target as HTMLElement).closest('a')?.getAttribute('href');
+    if (!user || !selectedEmail || !href?.startsWith('cybox://')) return;
+    event.preventDefault();
+    try {
+      const click: { id: number; url: string; outcome: LinkOutcome } =
+        await invoke('open_cybox_link', { userId: user.id, url: href, emailId: selectedEmail.id });
+      const outcome = click.outcome;
+      if (outcome.type === 'download') {
+        alert(`${outcome.file_name} was saved to ${outcome.path}`);
+      } else if (outcome.type === 'login') {
+        setLoginPage({ clickId: click.id, app: outcome.app });
+      } else {
+        openLinkedApp(outcome.app, click.url);
+      }
+    } catch (error) {
+      console.error('Failed to open link:', error);
+    }
+  };
+
+  // What was typed never leaves this form; only the fact of signing in is recorded
+  const submitLoginPage = async (event: React.FormEvent<HTMLFormElement>) => {
+    event.preventDefault();
+    if (!user || !loginPage) return;
+    const page = loginPage;
+    setLoginPage(null);
+    try {
+      await invoke('submit_link_credentials', { userId: user.id, clickId: page.clickId });
+      if (LINK_APPS[page.app]) {
+        appContext?.openApp(LINK_APPS[page.app]);
+      }
+    } catch (error) {
+      console.error('Failed to sign in:', error);
+    }
+  };
+
+  const reportEmail = async (id: number) => {
+    if (!user) return;
+    try {
+      await invoke('report_email', { userId: user.id, emailId: id, note: null });
+      alert('Reported to the security desk. They will reply shortly.');
+    } catch (error) {
+      console.error('Failed to report email:', error);
+      alert(`Failed to report email: ${error}`);
+    }
+  };
+
 
 
+  const sendEmail = async (event: React.FormEvent<HTMLFormElement>) => {
+    event.preventDefault();
+    if (!user) return;
+    const form = new FormData(event.currentTarget);
+    try {
+      await invoke('send_email', {
+        userId: user.id,
+        to: String(form.get('to')).split(',').map(address => address.trim()).filter(Boolean),
+        subject: String(form.get('subject')),
+        body: String(form.get('body')),
+        exerciseLabel: null,
+      });
+      setIsComposing(false);
+      fetchEmails();
+    } catch (error) {
+      alert(`Failed to send email: ${error}`);
+    }
+  };
+
   const addLink = () => {
     const url = prompt("Enter the URL");
     if (url && bodyRef.current) {
@@ -110,11 +284,11 @@ export default function EmailApp() {
   if (isComposing) {
     return (
       <div className="w-full h-full flex flex-col text-white p-6">
-        <form>
+        <form onSubmit={sendEmail}>
           <h2 className="text-2xl font-bold mb-4">New Email</h2>
           <div className="mb-4">
             <label htmlFor="to" className="block text-sm font-medium text-neutral-400 mb-1">To</label>
-            <input type="email" name="to" id="to" className="w-full bg-neutral-800 rounded-md py-2 px-4 text-white" required />
+            <input type="text" name="to" id="to" placeholder="name@cybox.app" className="w-full bg-neutral-800 rounded-md py-2 px-4 text-white" required />
           </div>
           <div className="mb-4">
             <label htmlFor="subject" className="block text-sm font-medium text-neutral-400 mb-1">Subject</label>
@@ -136,10 +310,24 @@ export default function EmailApp() {
     );
   }
 
-  const emailsToDisplay = emails;
+  const emailsToDisplay = searchResults ?? emails.filter(email => email.folder === activeTab);
+  const labelColor = (name: string) => labels.find(label => label.name === name)?.color ?? '#60a5fa';
 
   return (
-    <div className="w-full flex h-full text-white">
+    <div className="relative w-full flex h-full text-white">
+      {loginPage && (
+        <div className="absolute inset-0 z-10 flex items-center justify-center bg-black/60">
+          <form onSubmit={submitLoginPage} className="bg-neutral-900 p-8 w-80 rounded-xl shadow-lg text-center">
+            <h2 className="text-lg font-bold mb-4">Sign in to {LINK_APPS[loginPage.app] ?? loginPage.app}</h2>
+            <input type="text" name="username" placeholder="Username" autoComplete="off" className="w-full px-3 py-2 rounded-md bg-black mb-3" required />
+            <input type="password" name="password" placeholder="Password" autoComplete="off" className="w-full px-3 py-2 rounded-md bg-black mb-4" required />
+            <div className="flex justify-between">
+              <button type="button" onClick={() => setLoginPage(null)} className="text-neutral-400 hover:text-white">Cancel</button>
+              <button type="submit" className="bg-teal-400 text-black px-4 py-2 rounded hover:opacity-90">Sign in</button>
+            </div>
+          </form>
+        </div>
+      )}
       {/* Sidebar */}
       <div className="w-16 bg-neutral-800/50 px-6 py-3 rounded-xl m-2 flex flex-col items-center gap-4">
         <button onClick={() => setIsComposing(true)} className="w-10 h-10 bg-blue-600 text-white rounded-full hover:bg-blue-700 transition-colors flex items-center justify-center">
@@ -152,6 +340,9 @@ export default function EmailApp() {
           <button onClick={() => setActiveTab('sent')} className={`w-10 h-10 rounded-full flex items-center justify-center hover:bg-neutral-700 transition-colors ${activeTab === 'sent' ? 'bg-neutral-700' : ''}`}>
             <FaPaperPlane />
           </button>
+          <button onClick={() => setActiveTab('quarantine')} className={`w-10 h-10 rounded-full flex items-center justify-center hover:bg-neutral-700 transition-colors ${activeTab === 'quarantine' ? 'bg-neutral-700' : ''}`} title="Quarantine">
+            <FaShieldAlt />
+          </button>
           <button onClick={() => setActiveTab('trash')} className={`w-10 h-10 rounded-full flex items-center justify-center hover:bg-neutral-700 transition-colors ${activeTab === 'trash' ? 'bg-neutral-700' : ''}`}>
             <FaTrash />
           </button>
@@ -161,6 +352,24 @@ export default function EmailApp() {
       {/* Email List */}
       <div className="w-1/3 border-r border-neutral-700 overflow-y-auto">
         <div className="p-4 font-bold text-xl border-b border-neutral-700">{activeTab.charAt(0).toUpperCase() + activeTab.slice(1)}</div>
+        <div className="p-2 border-b border-neutral-700 flex items-center gap-2">
+          <FaSearch className="text-neutral-500" />
+          <input
+            type="text"
+            value={searchText}
+            onChange={(e) => setSearchText(e.target.value)}
+            placeholder="Search mail"
+            className="flex-1 bg-neutral-800 rounded-md py-1 px-2 text-sm text-white"
+          />
+          <select
+            value={labelFilter}
+            onChange={(e) => setLabelFilter(e.target.value)}
+            className="bg-neutral-800 rounded-md py-1 px-2 text-sm text-white"
+          >
+            <option value="">All labels</option>
+            {labels.map(label => <option key={label.id} value={label.name}>{label.name}</option>)}
+          </select>
+        </div>
         {loading ? (
           <div className="flex items-center justify-center h-full">
             <p className="text-neutral-500">Loading emails...</p>
@@ -194,6 +403,13 @@ export default function EmailApp() {
                   <div>
                     <p className="text-sm">{email.from_user}</p>
                     <p className="text-xs text-neutral-500">{email.subject}</p>
+                    {email.labels.length > 0 && (
+                      <div className="flex gap-1 mt-1">
+                        {email.labels.map(name => (
+                          <span key={name} className="text-[10px] px-1 rounded" style={{ backgroundColor: labelColor(name) }}>{name}</span>
+                        ))}
+                      </div>
+                    )}
                   </div>
                 </div>
               </div>
@@ -215,6 +431,14 @@ export default function EmailApp() {
                 <button onClick={() => classifyEmail(selectedEmail.id, 'phishing')} className={`p-2 hover:bg-neutral-700 rounded-full ${selectedEmail.classification === 'phishing' ? 'bg-yellow-200/20 text-yellow-500' : ''}`} title="Mark as Phishing">
                   <FaExclamationTriangle />
                 </button>
+                <button onClick={() => reportEmail(selectedEmail.id)} className="p-2 hover:bg-neutral-700 rounded-full" title="Report to Security Desk">
+                  <FaFlag />
+                </button>
+                {selectedEmail.folder === 'trash' && (
+                  <button onClick={() => restoreEmail(selectedEmail.id)} className="p-2 hover:bg-neutral-700 rounded-full" title="Restore">
+                    <FaUndo />
+                  </button>
+                )}
                 <button onClick={() => deleteEmail(selectedEmail.id)} className="p-2 hover:bg-neutral-700 rounded-full" title="Delete">
                   <FaTrash />
                 </button>
@@ -229,7 +453,21 @@ export default function EmailApp() {
                 <p className="text-sm text-neutral-400">To: {user?.name}</p>
               </div>
             </div>
-            <div className="prose prose-invert max-w-none  select-text" dangerouslySetInnerHTML={{ __html: selectedEmail.body }}></div>
+            <div className="flex flex-wrap items-center gap-2 mb-4">
+              <FaTag className="text-neutral-500" />
+              {labels.map(label => (
+                <button
+                  key={label.id}
+                  onClick={() => toggleLabel(selectedEmail, label)}
+                  className={`text-xs px-2 py-0.5 rounded-full border ${selectedEmail.labels.includes(label.name) ? 'text-white' : 'text-neutral-400 border-neutral-600'}`}
+                  style={selectedEmail.labels.includes(label.name) ? { backgroundColor: label.color, borderColor: label.color } : undefined}
+                >
+                  {label.name}
+                </button>
+              ))}
+              <button onClick={createLabel} className="text-xs text-blue-400 hover:text-blue-500">+ New label</button>
+            </div>
+            <div className="prose prose-invert max-w-none  select-text" onClick={handleBodyClick} dangerouslySetInnerHTML={{ __html: selectedEmail.body }}></div>
             {selectedEmail.classification === 'phishing' && (
               <div className="mt-6 p-4 bg-yellow-900/30 text-yellow-400 rounded-lg flex items-center gap-3">
                 <FaExclamationTriangle />
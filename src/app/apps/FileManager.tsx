@@ -10,7 +10,7 @@ import { RxCross2 } from 'react-icons/rx'
 const mockFiles: FileItem[] = [
   { name: 'Notes.txt', type: 'file', content: 'Meeting Notes 2025-08-29:\n- Discuss Q3 financial results\n- Plan for new product launch\n- Review marketing strategy', path: undefined } as FileItem,
   { name: 'Projects', type: 'folder', content: undefined, path: undefined } as FileItem,
-  { name: 'code.txt', type: 'file', content: 'Fragment 2/2: 2nd key \nEndcoded in Base64: X3NfZV9jX3Vfcl9pX3RfeQ==', path: undefined } as FileItem,
+  { name: 'code.txt', type: 'file', path: '/home/user/code.txt', content: undefined } as FileItem,
   { name: 'Screenshots', type: 'folder', content: undefined, path: undefined } as FileItem,
   { name: 'sensitive_data.txt', type: 'file', path: '/home/user/sensitive_data.txt', content: '' } as FileItem,
 ]
@@ -30,7 +30,7 @@ const FileContentPopup = ({ file, onClose, isEncrypted, user, setIsSensitiveFile
   const handleDecrypt = async () => {
     if (!file.path) return;
     try {
-      const content: string = await invoke('decrypt_file_content', { filePath: file.path, password });
+      const content: string = await invoke('decrypt_file_content', { filePath: file.path, password, userId: user.id });
       setDecryptedContent(content);
       setError(null);
     } catch (err) {
@@ -41,6 +41,14 @@ const FileContentPopup = ({ file, onClose, isEncrypted, user, setIsSensitiveFile
 
   const isSensitiveFile = file.name === 'sensitive_data.txt';
 
+  useEffect(() => {
+    // Seeded files carry the user's own flags, so their content comes from the backend
+    if (isSensitiveFile || file.content !== undefined || !file.path || !user) return;
+    invoke<string>('read_seeded_file', { filePath: file.path, userId: user.id })
+      .then(setDecryptedContent)
+      .catch((err) => setError(err as string));
+  }, [file, isSensitiveFile, user]);
+
   return (
     <div className="fixed inset-0 bg-neutral-900/80 flex items-center justify-center z-50">
       <div className="bg-neutral-800/50 backdrop-blur-2xl rounded-xl shadow-lg w-[90%] h-[90%] text-white overflow-hidden border-primary border-2">
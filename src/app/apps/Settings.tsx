@@ -1,5 +1,5 @@
 'use client'
-import React, { useState, useEffect, useMemo } from 'react'
+import React, { useState, useEffect } from 'react'
 import {
   FaUserShield, 
   FaWifi, 
@@ -141,7 +141,7 @@ const ChangePasswordModal = ({
       setNewPassword("");
       setConfirmPassword("");
       if (user) {
-        await invoke("complete_task", { taskId: 2, userId: user.id });
+        await invoke("submit_task", { userId: user.id, taskId: 2, answer: null });
         triggerTaskUpdate(); // Refresh tasks 
       }
     } catch (err) {
@@ -203,6 +203,110 @@ const ChangePasswordModal = ({
   );
 };
 
+interface SecurityAlert {
+  id: number;
+  service_id: number;
+  severity: string;
+  title: string;
+  message: string;
+  is_acknowledged: boolean;
+  created_at: string;
+}
+
+interface RemediationTask {
+  id: number;
+  service_id: number;
+  title: string;
+  description: string;
+  points: number;
+  status: string;
+}
+
+const SecurityCenter = ({ userId, refreshKey }: { userId: number, refreshKey: unknown }) => {
+  const [alerts, setAlerts] = useState<SecurityAlert[]>([]);
+  const [remediations, setRemediations] = useState<RemediationTask[]>([]);
+  const [outstanding, setOutstanding] = useState<{[key: number]: string[]}>({});
+
+  const refresh = React.useCallback(() => {
+    invoke<SecurityAlert[]>('get_security_alerts', { userId }).then(setAlerts).catch(console.error);
+    invoke<RemediationTask[]>('get_remediation_tasks', { userId }).then(setRemediations).catch(console.error);
+  }, [userId]);
+
+  useEffect(() => {
+    refresh();
+  }, [refresh, refreshKey]);
+
+  const acknowledge = (alertId: number) => {
+    invoke('acknowledge_alert', { userId, alertId }).then(refresh).catch(console.error);
+  };
+
+  const verify = (remediationId: number) => {
+    invoke<{ resolved: boolean, outstanding: string[] }>('verify_remediation', { userId, remediationId })
+      .then(result => {
+        setOutstanding(prev => ({ ...prev, [remediationId]: result.outstanding }));
+        refresh();
+      })
+      .catch(console.error);
+  };
+
+  return (
+    <>
+      <div className="bg-neutral-800/50 p-4 rounded-lg">
+        <h3 className="text-lg font-semibold mb-3 text-primary">Security Alerts</h3>
+        {alerts.length > 0 ? (
+          <ul className="space-y-2">
+            {alerts.map(alert => (
+              <li key={alert.id} className="p-2 bg-neutral-700 rounded-md flex justify-between items-center gap-2">
+                <div>
+                  <p className="font-bold text-white">{alert.title} <span className="text-xs text-red-400 uppercase">{alert.severity}</span></p>
+                  <p className="text-xs text-gray-400">{alert.message}</p>
+                </div>
+                {alert.is_acknowledged ? (
+                  <span className="text-xs text-gray-400">Acknowledged</span>
+                ) : (
+                  <button onClick={() => acknowledge(alert.id)} className="px-3 py-1 text-sm bg-primary rounded-md">Acknowledge</button>
+                )}
+              </li>
+            ))}
+          </ul>
+        ) : (
+          <p className="text-gray-400">No security alerts.</p>
+        )}
+      </div>
+
+      <div className="bg-neutral-800/50 p-4 rounded-lg">
+        <h3 className="text-lg font-semibold mb-3 text-primary">Remediation</h3>
+        {remediations.length > 0 ? (
+          <ul className="space-y-2">
+            {remediations.map(task => (
+              <li key={task.id} className="p-2 bg-neutral-700 rounded-md">
+                <div className="flex justify-between items-center gap-2">
+                  <div>
+                    <p className="font-bold text-white">{task.title} <span className="text-xs text-gray-400">{task.points} pts</span></p>
+                    <p className="text-xs text-gray-400">{task.description}</p>
+                  </div>
+                  {task.status === 'resolved' ? (
+                    <span className="text-xs text-green-400">Resolved</span>
+                  ) : (
+                    <button onClick={() => verify(task.id)} className="px-3 py-1 text-sm bg-primary rounded-md">Verify</button>
+                  )}
+                </div>
+                {task.status !== 'resolved' && outstanding[task.id]?.length > 0 && (
+                  <ul className="mt-2 text-xs text-red-400 list-disc list-inside">
+                    {outstanding[task.id].map(step => <li key={step}>{step}</li>)}
+                  </ul>
+                )}
+              </li>
+            ))}
+          </ul>
+        ) : (
+          <p className="text-gray-400">Nothing to clean up.</p>
+        )}
+      </div>
+    </>
+  );
+};
+
 export default function SettingsApp() {
   const { user, purchasedServices } = useAuth()
   const appContext = React.useContext(AppContext);
@@ -210,7 +314,7 @@ export default function SettingsApp() {
   const [activeSection, setActiveSection] = useState<Section>('Security')
   const [settings, setSettings] = useState<Settings | null>(null)
   const [loading, setLoading] = useState(true);
-  const [serviceStatus, setServiceStatus] = useState<{[key: number]: boolean}>({});
+  const [serviceStatus, setServiceStatus] = useState<{[key: number]: string}>({});
   const [isChangePasswordModalOpen, setChangePasswordModalOpen] = useState(false);
 
   useEffect(() => {
@@ -224,13 +328,17 @@ export default function SettingsApp() {
 
   // Initialize service statuses
   useEffect(() => {
-    const initialStatuses: {[key: number]: boolean} = {};
-    purchasedServices.forEach(service => {
-      // In a real app, you would fetch this from the backend
-      initialStatuses[service.id] = true; // Default to enabled
-    });
-    setServiceStatus(initialStatuses);
-  }, [purchasedServices]);
+    if (!user) return;
+    invoke<{ service_id: number, status: string }[]>('get_service_states', { userId: user.id })
+      .then(states => {
+        const initialStatuses: {[key: number]: string} = {};
+        states.forEach(state => {
+          initialStatuses[state.service_id] = state.status;
+        });
+        setServiceStatus(initialStatuses);
+      })
+      .catch(console.error);
+  }, [user, purchasedServices]);
 
   const handleSettingChange = (key: keyof Settings, value: any) => {
     if (settings) {
@@ -241,15 +349,23 @@ export default function SettingsApp() {
   };
 
   const handleServiceStatusChange = (serviceId: number, isEnabled: boolean) => {
-    setServiceStatus(prev => ({ ...prev, [serviceId]: isEnabled }));
-    // Here you would invoke a backend call to persist the setting
-    // invoke('set_service_status', { userId: user.id, serviceId, isEnabled }).catch(console.error);
-    console.log(`Service ${serviceId} status changed to: ${isEnabled}`);
+    if (!user) return;
+    const previous = serviceStatus[serviceId];
+    setServiceStatus(prev => ({ ...prev, [serviceId]: isEnabled ? 'enabled' : 'disabled' }));
+    invoke<{ status: string }>('set_service_status', { userId: user.id, serviceId, isEnabled })
+      .then(state => setServiceStatus(prev => ({ ...prev, [serviceId]: state.status })))
+      .catch(err => {
+        console.error(err);
+        setServiceStatus(prev => ({ ...prev, [serviceId]: previous }));
+      });
   };
 
-  const securityServices = useMemo(() => {
-      return purchasedServices.filter(s => s.category === 'Security' || s.category === 'Antivirus');
-  }, [purchasedServices]);
+  const handleUninstall = (serviceId: number) => {
+    if (!user) return;
+    invoke<{ status: string }>('uninstall_service', { userId: user.id, serviceId })
+      .then(state => setServiceStatus(prev => ({ ...prev, [serviceId]: state.status })))
+      .catch(console.error);
+  };
 
   if (loading) {
     return <div>Loading...</div>
@@ -479,27 +595,34 @@ export default function SettingsApp() {
               </div>
 
               <div className="bg-neutral-800/50 p-4 rounded-lg">
-                <h3 className="text-lg font-semibold mb-3 text-primary">Installed Security Apps</h3>
-                {securityServices.length > 0 ? (
+                <h3 className="text-lg font-semibold mb-3 text-primary">Purchased Apps</h3>
+                {purchasedServices.length > 0 ? (
                     <ul className="space-y-2">
-                        {securityServices.map(service => (
+                        {purchasedServices.map(service => (
                             <li key={service.id} className="p-2 bg-neutral-700 rounded-md flex justify-between items-center">
                                 <div>
                                     <p className="font-bold text-white">{service.name}</p>
-                                    <p className="text-xs text-gray-400">Developer: {service.developer}</p>
+                                    <p className="text-xs text-gray-400">Developer: {service.developer} · {service.category} · {serviceStatus[service.id] ?? 'owned'}</p>
+                                </div>
+                                <div className="flex items-center gap-2">
+                                    {['installed', 'enabled', 'disabled'].includes(serviceStatus[service.id]) && (
+                                        <button onClick={() => handleUninstall(service.id)} className="px-2 py-1 text-xs bg-neutral-600 rounded-md">Uninstall</button>
+                                    )}
+                                    <Switch 
+                                        checked={serviceStatus[service.id] === 'enabled'} 
+                                        onChange={(val) => handleServiceStatusChange(service.id, val)} 
+                                    />
                                 </div>
-                                <Switch 
-                                    checked={serviceStatus[service.id] || false} 
-                                    onChange={(val) => handleServiceStatusChange(service.id, val)} 
-                                />
                             </li>
                         ))}
                     </ul>
                 ) : (
-                    <p className="text-gray-400">No additional security apps installed.</p>
+                    <p className="text-gray-400">No apps purchased yet.</p>
                 )}
               </div>
 
+              {user && <SecurityCenter userId={user.id} refreshKey={serviceStatus} />}
+
               <div className="bg-neutral-800/50 p-4 rounded-lg">
                 <h3 className="text-lg font-semibold mb-3 text-primary">Advanced Security</h3>
                 <div className="space-y-3">
@@ -1,6 +1,6 @@
 'use client'
-import React, { useState, useEffect, useMemo, useContext } from 'react';
-import { FaTasks, FaCheckCircle, FaBook, FaExclamationTriangle, FaLock, FaTimesCircle } from 'react-icons/fa';
+import React, { useState, useEffect, useCallback, useContext } from 'react';
+import { FaTasks, FaCheckCircle, FaBook, FaExclamationTriangle, FaLock } from 'react-icons/fa';
 import { invoke } from '@tauri-apps/api/core';
 import { useAuth } from '../../Context/AuthContext';
 import { useNotification } from '../../Context/NotificationContext';
@@ -12,90 +12,107 @@ interface UniversalTask {
   description: string;
   learning_module: string | null;
   points: number;
-  task_type: 'knowledge' | 'action';
-  task_data: string | null;
+  task_type: string;
+  has_answer: boolean;
   level: number;
 }
 
+type TaskStatus = 'Locked' | 'Available' | 'In Progress' | 'Completed';
+
 interface UserTask {
-  id: number;
+  id: number | null;
   user_id: number;
   universal_task_id: number;
-  status: string;
+  status: TaskStatus;
+  prerequisites: number[];
   completed_at: string | null;
-  created_at: string;
+  created_at: string | null;
 }
 
 interface DisplayTask extends UniversalTask {
   user_task_id?: number;
-  status: 'To Do' | 'In Progress' | 'Completed';
-  task_data_parsed?: any;
+  status: TaskStatus;
+  prerequisites: number[];
 }
 
-type TaskStatus = 'To Do' | 'In Progress' | 'Completed';
+interface TaskSubmission {
+  task_id: number;
+  completed: boolean;
+  message: string;
+  unlocked: number[];
+}
 
-const toTaskStatus = (status: string | undefined): TaskStatus => {
-  if (status === 'To Do' || status === 'In Progress' || status === 'Completed') {
-    return status;
-  }
-  return 'To Do';
-};
 
+type CompleteTask = (taskId: number, points: number, answer?: unknown) => Promise<string | null>;
 
-const MultiFactorChallenge = ({ task, onComplete }: { task: DisplayTask, onComplete: (taskId: number, points: number) => void }) => {
-  const [passwords, setPasswords] = useState<string[]>(Array(5).fill(''));
-  const [validation, setValidation] = useState<('correct' | 'incorrect' | 'empty')[]>(Array(5).fill('empty'));
-  const correctPasswords = useMemo(() => task.task_data_parsed?.passwords || [], [task.task_data_parsed]);
+interface HashProgress {
+  task_id: number;
+  cracked: boolean[];
+  completed: boolean;
+}
 
-  const handlePasswordChange = (index: number, value: string) => {
-    const newPasswords = [...passwords];
-    newPasswords[index] = value;
-    setPasswords(newPasswords);
+const MultiFactorChallenge = ({ task, onComplete, userId }: { task: DisplayTask, onComplete: CompleteTask, userId: number | undefined }) => {
+  const [password, setPassword] = useState('');
+  const [cracked, setCracked] = useState<boolean[]>([]);
+  const [message, setMessage] = useState<string | null>(null);
 
-    const newValidation = [...validation];
-    if (value === '') {
-      newValidation[index] = 'empty';
-    } else if (value === correctPasswords[index]) {
-      newValidation[index] = 'correct';
-    } else {
-      newValidation[index] = 'incorrect';
+  const loadProgress = useCallback(async () => {
+    if (!userId) return;
+    try {
+      const progress: HashProgress = await invoke('get_hash_progress', { userId, taskId: task.id });
+      setCracked(progress.cracked);
+    } catch (err) {
+      console.error('Failed to load hash progress:', err);
     }
-    setValidation(newValidation);
-  };
+  }, [userId, task.id]);
 
-  const allCorrect = validation.every(v => v === 'correct');
+  useEffect(() => {
+    loadProgress();
+  }, [loadProgress]);
+
+  const handleSubmit = async () => {
+    if (!password.trim()) return;
+    const failure = await onComplete(task.id, task.points, password);
+    setMessage(failure);
+    setPassword('');
+    await loadProgress();
+  };
 
   return (
     <div>
-      <p className="text-neutral-300 mb-4">Enter the 5 passwords to proceed.</p>
-      <div className="space-y-4 mb-6">
-        {passwords.map((password, index) => (
-          <div key={index} className="flex items-center gap-2 relative">
-            <input
-              type="text"
-              value={password}
-              onChange={(e) => handlePasswordChange(index, e.target.value)}
-              placeholder={`Password ${index + 1}`}
-              className="flex-grow p-2 rounded-md border border-neutral-700 bg-neutral-800 text-white"
-            />
-            {validation[index] === 'correct' && <FaCheckCircle className="text-green-500 absolute top-[30%] right-3" />}
-            {validation[index] === 'incorrect' && <FaTimesCircle className="text-red-500 absolute top-[30%] right-3" />}
+      <p className="text-neutral-300 mb-4">Enter the passwords you crack one at a time.</p>
+      <div className="flex gap-2 mb-4">
+        {cracked.map((solved, index) => (
+          <div key={index} className="flex-1 flex items-center justify-center gap-2 p-2 rounded-md border border-neutral-700 bg-neutral-800">
+            <span>Hash {index + 1}</span>
+            {solved ? <FaCheckCircle className="text-green-500" /> : <FaLock className="text-neutral-500" />}
           </div>
         ))}
       </div>
-      {allCorrect && (
-        <button
-          onClick={() => onComplete(task.id, task.points)}
-          className="w-full bg-blue-600 text-white py-3 px-4 rounded-lg hover:bg-blue-700 transition-colors flex items-center justify-center gap-2"
-        >
-          Submit
-        </button>
+      {task.status !== 'Completed' && (
+        <div className="flex gap-2 mb-4">
+          <input
+            type="text"
+            value={password}
+            onChange={(e) => setPassword(e.target.value)}
+            onKeyDown={(e) => e.key === 'Enter' && handleSubmit()}
+            placeholder="Cracked password"
+            className="flex-grow p-2 rounded-md border border-neutral-700 bg-neutral-800 text-white"
+          />
+          <button
+            onClick={handleSubmit}
+            className="bg-blue-600 text-white py-2 px-4 rounded-lg hover:bg-blue-700 transition-colors"
+          >
+            Submit
+          </button>
+        </div>
       )}
+      {message && <p className="text-neutral-300">{message}</p>}
     </div>
   );
 };
 
-const ActionTask = ({ task, onComplete, isLocked, userId }: { task: DisplayTask, onComplete: (taskId: number, points: number) => void, isLocked: boolean, userId: number | undefined }) => {
+const ActionTask = ({ task, onComplete, isLocked, userId }: { task: DisplayTask, onComplete: CompleteTask, isLocked: boolean, userId: number | undefined }) => {
   const [inputValue, setInputValue] = useState('');
   const [error, setError] = useState<string | null>(null);
 
@@ -105,7 +122,7 @@ const ActionTask = ({ task, onComplete, isLocked, userId }: { task: DisplayTask,
         setError("Complete previous levels to unlock this task.");
         return;
     }
-    if (!task.task_data_parsed) return;
+    if (!task.has_answer) return;
 
     if (!userId) {
         setError("User not found.");
@@ -113,21 +130,10 @@ const ActionTask = ({ task, onComplete, isLocked, userId }: { task: DisplayTask,
     }
 
     try {
-      let result = false;
-      if (task.title.includes('Crack the Password')) {
-        result = await invoke('verify_password_crack', { password: inputValue });
-      } else if (task.title.includes('Encrypt the Evidence')) {
-        result = await invoke('verify_file_encryption', { filePath: task.task_data_parsed.file_path, userId });
-      } else if (task.level === 6) {
-        result = await invoke('verify_hidden_file', { content: inputValue });
-      } else if (task.title.includes('Spotting Phishing Emails')) {
-        result = await invoke('verify_email_classification', { userId });
-      }
-
-      if (result) {
-        onComplete(task.id, task.points);
-      } else {
-        setError('Incorrect submission. Please try again.');
+      const answer = task.task_type === 'hidden_message' ? inputValue : null;
+      const failure = await onComplete(task.id, task.points, answer);
+      if (failure) {
+        setError(failure);
       }
     } catch (err) {
       console.error('Error verifying task:', err);
@@ -136,15 +142,13 @@ const ActionTask = ({ task, onComplete, isLocked, userId }: { task: DisplayTask,
   };
 
   const renderTaskInput = () => {
-    if (!task.task_data_parsed) return null;
-    if (task.level === 5) {
-        return <MultiFactorChallenge task={task} onComplete={onComplete} />;
+    if (!task.has_answer) return null;
+    if (task.task_type === 'cracked_passwords') {
+        return <MultiFactorChallenge task={task} onComplete={onComplete} userId={userId} />;
     }
-    if (task.title.includes('Crack the Password')) {
-      return <input type="text" value={inputValue} onChange={(e) => setInputValue(e.target.value)} placeholder="Enter the password" className="w-full p-2 rounded-md border border-neutral-700 bg-neutral-800 text-white" />;
-    } else if (task.title.includes('Encrypt the Evidence')) {
+    if (task.task_type === 'file_encrypted') {
       return <p className="text-neutral-300">Click the button below when you have encrypted the file.</p>;
-    } else if (task.level === 6) {
+    } else if (task.task_type === 'hidden_message') {
       return <input type="text" value={inputValue} onChange={(e) => setInputValue(e.target.value)} placeholder="Enter the hidden message" className="w-full p-2 rounded-md border border-neutral-700 bg-neutral-800 text-white" />;
     } 
     return null;
@@ -154,7 +158,7 @@ const ActionTask = ({ task, onComplete, isLocked, userId }: { task: DisplayTask,
     <div>
       <div className="mb-4">{renderTaskInput()}</div>
       {error && <p className="text-red-500 mb-4">{error}</p>}
-      {task.status !== 'Completed' && task.level !== 5 && (
+      {task.status !== 'Completed' && task.task_type !== 'cracked_passwords' && (
         <button
           onClick={handleSubmit}
           disabled={isLocked}
@@ -185,21 +189,17 @@ export default function TaskApp() {
       setLoading(true);
       setError(null);
       try {
-        const universalTasks: UniversalTask[] = await invoke('get_universal_tasks');
+        const universalTasks: UniversalTask[] = await invoke('get_universal_tasks', { userId: user.id });
         let userTasks: UserTask[] = [];
         userTasks = await invoke('get_user_tasks', { userId: user.id });
 
         const combinedTasks: DisplayTask[] = universalTasks.map(uTask => {
           const matchingUserTask = userTasks.find(ut => ut.universal_task_id === uTask.id);
-          let task_data_parsed;
-          if (uTask.task_data) {
-            try { task_data_parsed = JSON.parse(uTask.task_data); } catch (e) { console.error("Failed to parse task_data", e); }
-          }
           return {
             ...uTask,
-            user_task_id: matchingUserTask?.id,
-            status: toTaskStatus(matchingUserTask?.status),
-            task_data_parsed,
+            user_task_id: matchingUserTask?.id ?? undefined,
+            status: matchingUserTask?.status ?? 'Locked',
+            prerequisites: matchingUserTask?.prerequisites ?? [],
           };
         });
         setTasks(combinedTasks);
@@ -227,28 +227,32 @@ export default function TaskApp() {
 
   const { openApp } = appContext;
 
-  const completeTask = async (universalTaskId: number, points: number) => {
-    if (!user) return;
+  const completeTask: CompleteTask = async (universalTaskId, points, answer = null) => {
+    if (!user) return 'User not found.';
     try {
-      await invoke('complete_task', { taskId: universalTaskId, userId: user.id });
-      const updatedTasks = tasks.map(task =>
-        task.id === universalTaskId ? { ...task, status: 'Completed' as 'Completed' } : task
-      );
-      setTasks(updatedTasks);
-      setSelectedTask(prev => (prev && prev.id === universalTaskId ? { ...prev, status: 'Completed' } : prev));
+      const submission: TaskSubmission = await invoke('submit_task', { userId: user.id, taskId: universalTaskId, answer });
+      const statusAfter = (task: DisplayTask): TaskStatus => {
+        if (task.id === universalTaskId) return submission.completed ? 'Completed' : task.status === 'Locked' ? 'Locked' : 'In Progress';
+        return submission.unlocked.includes(task.id) ? 'Available' : task.status;
+      };
+      setTasks(tasks.map(task => ({ ...task, status: statusAfter(task) })));
+      setSelectedTask(prev => (prev ? { ...prev, status: statusAfter(prev) } : prev));
+      if (!submission.completed) return submission.message;
       showNotification('Task Complete!', points, () => openApp('Bank'));
+      return null;
     } catch (error) {
       console.error('Error completing task:', error);
+      return String(error);
     }
   };
 
 
-  const isLevelLocked = (level: number) => {
-      if (level === 1) return false; 
-      const prevLevelTasks = tasks.filter(t => t.level === level - 1);
-      if (prevLevelTasks.length === 0) return false; 
-      return !prevLevelTasks.every(t => t.status === 'Completed');
-  }
+  const prerequisiteTitles = (task: DisplayTask) =>
+    task.prerequisites
+      .map(id => tasks.find(t => t.id === id))
+      .filter((t): t is DisplayTask => !!t && t.status !== 'Completed')
+      .map(t => t.title)
+      .join(', ');
 
   if (loading) return <div className="w-full flex h-full justify-center items-center text-white">Loading tasks...</div>;
   if (error) return <div className="w-full flex h-full justify-center items-center text-red-500">{error}</div>;
@@ -258,7 +262,7 @@ export default function TaskApp() {
       <aside className="w-1/3 border-r border-neutral-700 overflow-y-auto">
         <div className="p-4 font-bold text-xl border-b border-neutral-700 flex items-center gap-2"><FaTasks /><span>Tasks</span></div>
         {tasks.map((task) => {
-          const isLocked = isLevelLocked(task.level);
+          const isLocked = task.status === 'Locked';
           return (
             <div
               key={task.id}
@@ -287,10 +291,10 @@ export default function TaskApp() {
             <p className="text-lg font-semibold text-neutral-300 mb-2">Level: {selectedTask.level}</p>
             <p className="mb-4 text-neutral-300 select-text">{selectedTask.description}</p>
             
-            {isLevelLocked(selectedTask.level) ? (
+            {selectedTask.status === 'Locked' ? (
                 <div className="flex items-center gap-2 text-yellow-500 bg-yellow-500/10 p-3 rounded-lg">
                     <FaLock />
-                    <span>Complete all tasks from Level {selectedTask.level - 1} to unlock.</span>
+                    <span>Complete {prerequisiteTitles(selectedTask)} to unlock.</span>
                 </div>
             ) : selectedTask.task_type === 'knowledge' ? (
               <>
@@ -309,7 +313,7 @@ export default function TaskApp() {
                 )}
               </>
             ) : (
-              <ActionTask task={selectedTask} onComplete={completeTask} isLocked={isLevelLocked(selectedTask.level)} userId={user?.id} />
+              <ActionTask task={selectedTask} onComplete={completeTask} isLocked={selectedTask.status === 'Locked'} userId={user?.id} />
             )}
           </div>
         ) : (